@@ -10,6 +10,7 @@ import { SoftBodiesDemo, SoftBodiesDemoConfig } from './src/softbodies_10';
 import { SkinnedSoftbodyDemo, SkinnedSoftbodyDemoConfig } from './src/softbody_skinning_12';
 import { FluidDemo, FluidDemoConfig } from './src/fluid_sim_17';
 import { FlipDemo, FlipDemoConfig } from './src/flip_18';
+import { Flip3DDemo, Flip3DDemoConfig } from './src/flip_3d';
 import { BodyChainDemo, BodyChainDemoConfig } from './src/body_chain_challenge';
 import { PositionBasedFluidDemo, PositionBasedFluidDemoConfig } from './src/fluid_2d_challenge';
 import { ParallelClothDemo, ParallelClothDemoConfig } from './src/parallel_cloth_16';
@@ -73,6 +74,11 @@ import('./pkg').then(async rust_wasm => {
             config: FlipDemoConfig,
             demo: FlipDemo,
         },
+        '18-Flip3D': {
+            title: 'Flip Fluid 3D',
+            config: Flip3DDemoConfig,
+            demo: Flip3DDemo,
+        },
         '19-Fractals': {
             title: 'Fractals',
             config: FractalsDemoConfig,
@@ -107,7 +107,7 @@ impl Body {
     }
 
     fn apply_rotation(&mut self, rot: Vec3, scale: Option<f32>) {
-        let mut scale = if let Some(s) = scale { s } else { 1.0 };
+        let mut scale = scale.unwrap_or(1.0);
 
         // Safety clamping. This happens very rarely if the solver
         // wants to turn the body by more than 30 degrees in the
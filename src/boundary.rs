@@ -0,0 +1,342 @@
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(PartialEq, Clone, Copy)]
+pub enum BoundaryKind {
+    NoSlip,
+    FreeSlip,
+    Inflow,
+    Outflow,
+    Periodic,
+}
+
+#[wasm_bindgen]
+#[derive(PartialEq, Clone, Copy)]
+pub enum BoundaryEdge {
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+#[wasm_bindgen]
+#[derive(PartialEq, Clone, Copy)]
+pub enum InflowProfile {
+    Uniform,
+    Parabolic,
+}
+
+#[derive(Clone, Copy)]
+pub struct Boundary {
+    pub kind: BoundaryKind,
+    // normal speed into the domain
+    pub inflow_speed: f32,
+    pub inflow_profile: InflowProfile,
+}
+
+impl Boundary {
+    #[must_use]
+    pub fn new(kind: BoundaryKind) -> Self {
+        Self {
+            kind,
+            inflow_speed: 0.0,
+            inflow_profile: InflowProfile::Uniform,
+        }
+    }
+
+    #[must_use]
+    pub fn inflow(speed: f32, profile: InflowProfile) -> Self {
+        Self {
+            kind: BoundaryKind::Inflow,
+            inflow_speed: speed,
+            inflow_profile: profile,
+        }
+    }
+
+    #[must_use]
+    fn is_solid(&self) -> bool {
+        matches!(
+            self.kind,
+            BoundaryKind::NoSlip | BoundaryKind::FreeSlip | BoundaryKind::Inflow
+        )
+    }
+
+    /// Inflow speed at relative position t in [0, 1] along the edge
+    #[must_use]
+    pub fn inflow_speed_at(&self, t: f32) -> f32 {
+        match self.inflow_profile {
+            InflowProfile::Uniform => self.inflow_speed,
+            // same flow rate as the uniform profile
+            InflowProfile::Parabolic => 6.0 * self.inflow_speed * t * (1.0 - t),
+        }
+    }
+}
+
+/// Boundary conditions of the four edges of a 2D MAC grid with the `i * n + j`
+/// layout, `n = num_cells_y`, which is surrounded by a layer of ghost cells.
+///
+/// Walls and inflows make the ghost cells solid and prescribe the velocity of
+/// the faces between the ghost cells and the domain. Outflows leave the ghost
+/// cells open at zero pressure. Periodic edges identify the ghost cells with
+/// the interior cells at the opposite edge, so that the faces at both edges
+/// are the same face.
+pub struct Boundaries {
+    num_cells_x: usize,
+    num_cells_y: usize,
+    edges: [Boundary; 4],
+    seam_u: Vec<f32>,
+    seam_v: Vec<f32>,
+}
+
+impl Boundaries {
+    #[must_use]
+    pub fn new(num_cells_x: usize, num_cells_y: usize, kind: BoundaryKind) -> Self {
+        Self {
+            num_cells_x,
+            num_cells_y,
+            edges: [Boundary::new(kind); 4],
+            seam_u: vec![0.0; num_cells_y],
+            seam_v: vec![0.0; num_cells_x],
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, edge: BoundaryEdge) -> &Boundary {
+        &self.edges[edge as usize]
+    }
+
+    /// Sets the boundary of an edge. As periodicity couples opposite edges,
+    /// making an edge periodic makes the opposite edge periodic too, and the
+    /// opposite edge of a formerly periodic edge becomes a free slip wall.
+    pub fn set(&mut self, edge: BoundaryEdge, boundary: Boundary) {
+        let opposite = match edge {
+            BoundaryEdge::Left => BoundaryEdge::Right,
+            BoundaryEdge::Right => BoundaryEdge::Left,
+            BoundaryEdge::Bottom => BoundaryEdge::Top,
+            BoundaryEdge::Top => BoundaryEdge::Bottom,
+        };
+        if boundary.kind == BoundaryKind::Periodic {
+            self.edges[opposite as usize].kind = BoundaryKind::Periodic;
+        } else if self.edges[edge as usize].kind == BoundaryKind::Periodic {
+            self.edges[opposite as usize].kind = BoundaryKind::FreeSlip;
+        }
+        self.edges[edge as usize] = boundary;
+    }
+
+    /// Sets the inflow speed and profile of an edge without changing its kind
+    pub fn set_inflow(&mut self, edge: BoundaryEdge, speed: f32, profile: InflowProfile) {
+        let boundary = &mut self.edges[edge as usize];
+        boundary.inflow_speed = speed;
+        boundary.inflow_profile = profile;
+    }
+
+    #[must_use]
+    pub fn is_periodic_x(&self) -> bool {
+        self.get(BoundaryEdge::Left).kind == BoundaryKind::Periodic
+    }
+
+    #[must_use]
+    pub fn is_periodic_y(&self) -> bool {
+        self.get(BoundaryEdge::Bottom).kind == BoundaryKind::Periodic
+    }
+
+    /// Marks the ghost cells in `s` as solid (0) or open (1). A corner is
+    /// solid if any of its two edges is solid.
+    pub fn set_ghost_cells(&self, s: &mut [f32]) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        let left = self.get(BoundaryEdge::Left).is_solid();
+        let right = self.get(BoundaryEdge::Right).is_solid();
+        let bottom = self.get(BoundaryEdge::Bottom).is_solid();
+        let top = self.get(BoundaryEdge::Top).is_solid();
+
+        for j in 0..n {
+            s[j] = if left { 0.0 } else { 1.0 };
+            s[(nx - 1) * n + j] = if right { 0.0 } else { 1.0 };
+        }
+        for i in 0..nx {
+            s[i * n] = if bottom || (i == 0 && left) || (i == nx - 1 && right) {
+                0.0
+            } else {
+                1.0
+            };
+            s[i * n + n - 1] = if top || (i == 0 && left) || (i == nx - 1 && right) {
+                0.0
+            } else {
+                1.0
+            };
+        }
+    }
+
+    /// Sets the normal velocities of wall and inflow faces, and the
+    /// tangential velocities of the ghost cells
+    pub fn apply_velocity(&self, u: &mut [f32], v: &mut [f32]) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+
+        // left and right edges, u is normal and v tangential
+        for j in 0..n {
+            let t = (j as f32 - 0.5) / (n - 2) as f32;
+            let t = t.clamp(0.0, 1.0);
+
+            let left = self.get(BoundaryEdge::Left);
+            match left.kind {
+                BoundaryKind::NoSlip | BoundaryKind::FreeSlip => {
+                    u[j] = 0.0;
+                    u[n + j] = 0.0;
+                }
+                BoundaryKind::Inflow => {
+                    u[j] = left.inflow_speed_at(t);
+                    u[n + j] = left.inflow_speed_at(t);
+                }
+                BoundaryKind::Outflow => u[j] = u[n + j],
+                BoundaryKind::Periodic => u[j] = u[(nx - 2) * n + j],
+            }
+            v[j] = match left.kind {
+                BoundaryKind::NoSlip => -v[n + j],
+                BoundaryKind::Periodic => v[(nx - 2) * n + j],
+                _ => v[n + j],
+            };
+
+            let right = self.get(BoundaryEdge::Right);
+            match right.kind {
+                BoundaryKind::NoSlip | BoundaryKind::FreeSlip => u[(nx - 1) * n + j] = 0.0,
+                BoundaryKind::Inflow => u[(nx - 1) * n + j] = -right.inflow_speed_at(t),
+                BoundaryKind::Outflow => {}
+                BoundaryKind::Periodic => u[(nx - 1) * n + j] = u[n + j],
+            }
+            v[(nx - 1) * n + j] = match right.kind {
+                BoundaryKind::NoSlip => -v[(nx - 2) * n + j],
+                BoundaryKind::Periodic => v[n + j],
+                _ => v[(nx - 2) * n + j],
+            };
+        }
+
+        // bottom and top edges, v is normal and u tangential
+        for i in 0..nx {
+            let t = (i as f32 - 0.5) / (nx - 2) as f32;
+            let t = t.clamp(0.0, 1.0);
+
+            let bottom = self.get(BoundaryEdge::Bottom);
+            match bottom.kind {
+                BoundaryKind::NoSlip | BoundaryKind::FreeSlip => {
+                    v[i * n] = 0.0;
+                    v[i * n + 1] = 0.0;
+                }
+                BoundaryKind::Inflow => {
+                    v[i * n] = bottom.inflow_speed_at(t);
+                    v[i * n + 1] = bottom.inflow_speed_at(t);
+                }
+                BoundaryKind::Outflow => v[i * n] = v[i * n + 1],
+                BoundaryKind::Periodic => v[i * n] = v[i * n + n - 2],
+            }
+            u[i * n] = match bottom.kind {
+                BoundaryKind::NoSlip => -u[i * n + 1],
+                BoundaryKind::Periodic => u[i * n + n - 2],
+                _ => u[i * n + 1],
+            };
+
+            let top = self.get(BoundaryEdge::Top);
+            match top.kind {
+                BoundaryKind::NoSlip | BoundaryKind::FreeSlip => v[i * n + n - 1] = 0.0,
+                BoundaryKind::Inflow => v[i * n + n - 1] = -top.inflow_speed_at(t),
+                BoundaryKind::Outflow => {}
+                BoundaryKind::Periodic => v[i * n + n - 1] = v[i * n + 1],
+            }
+            u[i * n + n - 1] = match top.kind {
+                BoundaryKind::NoSlip => -u[i * n + n - 2],
+                BoundaryKind::Periodic => u[i * n + 1],
+                _ => u[i * n + n - 2],
+            };
+        }
+    }
+
+    /// Copies cell-centered values across periodic edges. Other ghost cells
+    /// keep their values, which act as fixed inflow values.
+    pub fn apply_scalar<T: Copy>(&self, f: &mut [T]) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        if self.is_periodic_x() {
+            for j in 0..n {
+                f[j] = f[(nx - 2) * n + j];
+                f[(nx - 1) * n + j] = f[n + j];
+            }
+        }
+        if self.is_periodic_y() {
+            for i in 0..nx {
+                f[i * n] = f[i * n + n - 2];
+                f[i * n + n - 1] = f[i * n + 1];
+            }
+        }
+    }
+
+    /// Adds values accumulated in periodic ghost cells or faces, e.g. by
+    /// splatting particles, to the interior ones they are identified with.
+    /// `apply_scalar` or `apply_velocity` copy the sums back afterwards.
+    pub fn fold_periodic(&self, f: &mut [f32]) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        if self.is_periodic_x() {
+            for j in 0..n {
+                f[n + j] += f[(nx - 1) * n + j];
+                f[(nx - 2) * n + j] += f[j];
+            }
+        }
+        if self.is_periodic_y() {
+            for i in 0..nx {
+                f[i * n + 1] += f[i * n + n - 1];
+                f[i * n + n - 2] += f[i * n];
+            }
+        }
+    }
+
+    /// Wraps a position into the periodic part of the domain with grid spacing h
+    #[must_use]
+    pub fn wrap(&self, x: f32, y: f32, h: f32) -> (f32, f32) {
+        let mut x = x;
+        let mut y = y;
+        if self.is_periodic_x() {
+            x = h + (x - h).rem_euclid((self.num_cells_x - 2) as f32 * h);
+        }
+        if self.is_periodic_y() {
+            y = h + (y - h).rem_euclid((self.num_cells_y - 2) as f32 * h);
+        }
+        (x, y)
+    }
+
+    /// Stores the velocities of the periodic seam faces before a pressure
+    /// update, see `merge_periodic_faces`
+    pub fn store_periodic_faces(&mut self, u: &[f32], v: &[f32]) {
+        let n = self.num_cells_y;
+        if self.is_periodic_x() {
+            self.seam_u.copy_from_slice(&u[n..2 * n]);
+        }
+        if self.is_periodic_y() {
+            for i in 0..self.num_cells_x {
+                self.seam_v[i] = v[i * n + 1];
+            }
+        }
+    }
+
+    /// A simultaneous pressure update of all cells corrects each of the two
+    /// copies of a periodic seam face from one side only. Merges both
+    /// corrections into both copies.
+    pub fn merge_periodic_faces(&self, u: &mut [f32], v: &mut [f32]) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        if self.is_periodic_x() {
+            for j in 0..n {
+                let merged = u[n + j] + u[(nx - 1) * n + j] - self.seam_u[j];
+                u[n + j] = merged;
+                u[(nx - 1) * n + j] = merged;
+            }
+        }
+        if self.is_periodic_y() {
+            for i in 0..nx {
+                let merged = v[i * n + 1] + v[i * n + n - 1] - self.seam_v[i];
+                v[i * n + 1] = merged;
+                v[i * n + n - 1] = merged;
+            }
+        }
+    }
+}
@@ -4,7 +4,10 @@ use glam::{vec3, Vec3};
 use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
 
-use crate::mesh::{self, MeshData};
+use crate::{
+    mesh::{self, MeshData},
+    normals::SurfaceNormals,
+};
 
 const GRAVITY: Vec3 = vec3(0.0, -10.0, 0.0);
 const TIME_STEP: f32 = 1.0 / 60.0;
@@ -30,6 +33,7 @@ pub struct ClothSimulation {
     prev: Vec<Vec3>,
     vel: Vec<Vec3>,
     inv_mass: Vec<f32>,
+    normals: SurfaceNormals,
 
     stretching_ids: Vec<[usize; 2]>,
     bending_ids: Vec<[usize; 4]>,
@@ -52,7 +56,7 @@ struct Edge {
     edge_num: usize,
 }
 
-fn find_tri_neighbors(tri_ids: &Vec<[usize; 3]>) -> Vec<Option<usize>> {
+fn find_tri_neighbors(tri_ids: &[[usize; 3]]) -> Vec<Option<usize>> {
     // create common edges
     let num_edges = tri_ids.len() * 3;
     let mut edges = Vec::with_capacity(num_edges);
@@ -148,6 +152,7 @@ impl ClothSimulation {
             prev: mesh.vertices.clone(),
             vel: vec![Vec3::ZERO; num_particles],
             inv_mass: vec![0.0; num_particles],
+            normals: SurfaceNormals::new(num_particles, &mesh.tri_ids),
 
             stretching_ids: edge_ids.clone(),
             bending_ids: tri_pair_ids.clone(),
@@ -178,6 +183,11 @@ impl ClothSimulation {
         self.pos.as_ptr()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn normals(&self) -> *const Vec3 {
+        self.normals.normals.as_ptr()
+    }
+
     // We can copy since we are not performance sensitive for these two methods
     #[wasm_bindgen(getter)]
     pub fn edge_ids(&self) -> Vec<usize> {
@@ -195,6 +205,7 @@ impl ClothSimulation {
         self.pos.copy_from_slice(&self.mesh.vertices);
         self.prev.copy_from_slice(&self.pos);
         self.randomize_vels();
+        self.normals.update(&self.pos);
     }
 
     #[wasm_bindgen(setter)]
@@ -252,6 +263,7 @@ impl ClothSimulation {
         }
 
         self.randomize_vels();
+        self.normals.update(&self.pos);
     }
 
     fn pre_solve(&mut self) {
@@ -343,6 +355,7 @@ impl ClothSimulation {
             self.solve();
             self.post_solve();
         }
+        self.normals.update(&self.pos);
     }
 
     pub fn start_grab(&mut self, _: usize, pos: &[f32]) {
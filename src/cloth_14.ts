@@ -34,6 +34,7 @@ class ClothDemo implements Demo<ClothSimulation, ClothDemoProps> {
     private edgeMesh: THREE.LineSegments;
     private triMesh: THREE.Mesh;
     private positions: Float32Array; // mapped to WASM memory
+    private normals: Float32Array; // mapped to WASM memory
 
     constructor(rust_wasm: any, memory: WebAssembly.Memory, canvas: HTMLCanvasElement, scene: Scene3D, folder: GUI) {
         this.memory = memory;
@@ -95,7 +96,9 @@ class ClothDemo implements Demo<ClothSimulation, ClothDemoProps> {
         // linear heap, it will be constant thereafter, so we don't need to refresh the pointer
         // moving forward.
         const positionsPtr = this.sim.pos;
+        const normalsPtr = this.sim.normals;
         this.positions = new Float32Array(this.memory.buffer, positionsPtr, this.sim.num_particles * 3);
+        this.normals = new Float32Array(this.memory.buffer, normalsPtr, this.sim.num_particles * 3);
 
         // edge mesh
         let geometry = new THREE.BufferGeometry();
@@ -109,13 +112,13 @@ class ClothDemo implements Demo<ClothSimulation, ClothDemoProps> {
         // visual tri mesh
         geometry = new THREE.BufferGeometry();
         geometry.setAttribute('position', new THREE.BufferAttribute(this.positions, 3));
+        geometry.setAttribute('normal', new THREE.BufferAttribute(this.normals, 3));
         geometry.setIndex(tri_ids);
         const visMaterial = new THREE.MeshPhongMaterial({ color: 0xff0000, side: THREE.DoubleSide });
         this.triMesh = new THREE.Mesh(geometry, visMaterial);
         this.triMesh.castShadow = true;
         this.triMesh.layers.enable(1);
         this.scene.scene.add(this.triMesh);
-        geometry.computeVertexNormals();
         geometry.computeBoundingSphere();
 
         this.updateMesh();
@@ -125,7 +128,7 @@ class ClothDemo implements Demo<ClothSimulation, ClothDemoProps> {
         this.triMesh.geometry.attributes.position.needsUpdate = true;
         this.edgeMesh.geometry.attributes.position.needsUpdate = true;
         if (!this.props.showEdges) {
-            this.triMesh.geometry.computeVertexNormals();
+            this.triMesh.geometry.attributes.normal.needsUpdate = true;
         }
         this.triMesh.geometry.computeBoundingSphere();
     }
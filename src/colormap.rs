@@ -0,0 +1,205 @@
+use glam::{vec3, Vec3};
+use wasm_bindgen::prelude::*;
+
+// decades of dynamic range covered by the logarithmic scale
+const LOG_DECADES: f32 = 3.0;
+
+const SCI: [Vec3; 5] = [
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 1.0, 1.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(1.0, 1.0, 0.0),
+    vec3(1.0, 0.0, 0.0),
+];
+const GRAY: [Vec3; 2] = [Vec3::ZERO, Vec3::ONE];
+// matplotlib's perceptually uniform maps, sampled at nine points
+const VIRIDIS: [Vec3; 9] = [
+    vec3(0.267, 0.005, 0.329),
+    vec3(0.283, 0.141, 0.458),
+    vec3(0.230, 0.322, 0.546),
+    vec3(0.173, 0.449, 0.558),
+    vec3(0.128, 0.567, 0.551),
+    vec3(0.153, 0.683, 0.503),
+    vec3(0.369, 0.789, 0.383),
+    vec3(0.678, 0.864, 0.190),
+    vec3(0.993, 0.906, 0.144),
+];
+const MAGMA: [Vec3; 9] = [
+    vec3(0.001, 0.000, 0.014),
+    vec3(0.110, 0.063, 0.267),
+    vec3(0.316, 0.071, 0.485),
+    vec3(0.506, 0.145, 0.506),
+    vec3(0.716, 0.215, 0.475),
+    vec3(0.899, 0.315, 0.392),
+    vec3(0.987, 0.536, 0.382),
+    vec3(0.997, 0.762, 0.530),
+    vec3(0.987, 0.991, 0.750),
+];
+const INFERNO: [Vec3; 9] = [
+    vec3(0.001, 0.000, 0.014),
+    vec3(0.122, 0.047, 0.282),
+    vec3(0.333, 0.059, 0.427),
+    vec3(0.533, 0.133, 0.416),
+    vec3(0.729, 0.212, 0.333),
+    vec3(0.890, 0.349, 0.200),
+    vec3(0.976, 0.549, 0.039),
+    vec3(0.976, 0.788, 0.196),
+    vec3(0.988, 1.000, 0.643),
+];
+// Moreland's diverging map
+const COOLWARM: [Vec3; 5] = [
+    vec3(0.230, 0.299, 0.754),
+    vec3(0.552, 0.690, 0.996),
+    vec3(0.865, 0.865, 0.865),
+    vec3(0.958, 0.604, 0.482),
+    vec3(0.706, 0.016, 0.150),
+];
+// smoke turns into flames at 0.3 and the flames turn yellow above 0.5
+const FIRE: [(f32, Vec3); 7] = [
+    (0.0, Vec3::ZERO),
+    (0.3, vec3(0.2, 0.2, 0.2)),
+    (0.3, vec3(0.2, 0.1, 0.1)),
+    (0.5, vec3(1.0, 0.1, 0.1)),
+    (0.5, vec3(1.0, 0.0, 0.0)),
+    (0.98, vec3(1.0, 1.0, 0.0)),
+    (1.0, vec3(1.0, 1.0, 0.0)),
+];
+// cyclic, the first color is repeated at the end
+const FRACTAL: [Vec3; 8] = [
+    vec3(0.059, 0.008, 0.259),
+    vec3(0.749, 0.161, 0.047),
+    vec3(0.871, 0.388, 0.043),
+    vec3(0.898, 0.816, 0.055),
+    vec3(1.0, 1.0, 1.0),
+    vec3(0.4, 0.678, 0.718),
+    vec3(0.055, 0.114, 0.408),
+    vec3(0.059, 0.008, 0.259),
+];
+
+#[wasm_bindgen]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ColormapKind {
+    Sci,
+    Gray,
+    Viridis,
+    Magma,
+    Inferno,
+    Coolwarm,
+    Fire,
+    Fractal,
+    Custom,
+}
+
+#[wasm_bindgen]
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum ColormapScale {
+    Linear,
+    Log,
+}
+
+/// Maps scalar values to colors by interpolating between color stops
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Colormap {
+    #[wasm_bindgen(readonly)]
+    pub kind: ColormapKind,
+    pub scale: ColormapScale,
+    // ascending positions in [0, 1] with the color at each, equal positions
+    // make a hard step
+    stops: Vec<(f32, Vec3)>,
+}
+
+fn evenly_spaced(colors: &[Vec3]) -> Vec<(f32, Vec3)> {
+    let d = (colors.len() - 1) as f32;
+    colors
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i as f32 / d, c))
+        .collect()
+}
+
+#[wasm_bindgen]
+impl Colormap {
+    /// Creates one of the built-in colormaps, `Custom` creates `Gray`
+    #[must_use]
+    #[wasm_bindgen(constructor)]
+    pub fn new(kind: ColormapKind, scale: ColormapScale) -> Colormap {
+        let stops = match kind {
+            ColormapKind::Sci => evenly_spaced(&SCI),
+            ColormapKind::Gray | ColormapKind::Custom => evenly_spaced(&GRAY),
+            ColormapKind::Viridis => evenly_spaced(&VIRIDIS),
+            ColormapKind::Magma => evenly_spaced(&MAGMA),
+            ColormapKind::Inferno => evenly_spaced(&INFERNO),
+            ColormapKind::Coolwarm => evenly_spaced(&COOLWARM),
+            ColormapKind::Fire => FIRE.to_vec(),
+            ColormapKind::Fractal => evenly_spaced(&FRACTAL),
+        };
+        let kind = if kind == ColormapKind::Custom {
+            ColormapKind::Gray
+        } else {
+            kind
+        };
+        Self { kind, scale, stops }
+    }
+
+    /// Creates a colormap from evenly spaced colors given as consecutive
+    /// r, g, b values in [0, 1]
+    ///
+    /// # Errors
+    /// Will return `Err` if `colors` does not hold at least two colors
+    pub fn from_gradient(colors: &[f32], scale: ColormapScale) -> Result<Colormap, JsValue> {
+        if colors.len() < 6 || !colors.len().is_multiple_of(3) {
+            return Err(JsValue::from_str(
+                "gradient needs at least two colors of three components each",
+            ));
+        }
+        let colors: Vec<Vec3> = colors.chunks_exact(3).map(Vec3::from_slice).collect();
+        Ok(Self {
+            kind: ColormapKind::Custom,
+            scale,
+            stops: evenly_spaced(&colors),
+        })
+    }
+}
+
+impl Colormap {
+    /// Position of `val` within [min, max] on the colormap
+    #[must_use]
+    fn normalize(&self, val: f32, min: f32, max: f32) -> f32 {
+        let d = max - min;
+        let t = if d == 0.0 {
+            0.5
+        } else {
+            ((val - min) / d).clamp(0.0, 1.0)
+        };
+        match self.scale {
+            ColormapScale::Linear => t,
+            ColormapScale::Log => {
+                let k = 10_f32.powf(LOG_DECADES) - 1.0;
+                f32::log10(1.0 + k * t) / LOG_DECADES
+            }
+        }
+    }
+
+    /// Color of `val` within [min, max] with components in [0, 1]
+    #[must_use]
+    pub fn color(&self, val: f32, min: f32, max: f32) -> Vec3 {
+        let t = self.normalize(val, min, max);
+        for w in self.stops.windows(2) {
+            let (t0, c0) = w[0];
+            let (t1, c1) = w[1];
+            if t < t1 {
+                let d = t1 - t0;
+                let s = if d > 0.0 { (t - t0) / d } else { 0.0 };
+                return c0.lerp(c1, s);
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+
+    /// Color of `val` within [min, max] with components in [0, 255]
+    #[must_use]
+    pub fn color_255(&self, val: f32, min: f32, max: f32) -> [f32; 3] {
+        (255.0 * self.color(val, min, max)).into()
+    }
+}
@@ -1,15 +1,24 @@
 use core::f64::consts::PI;
 
-use glam::Vec2;
+use glam::{Vec2, Vec3};
 use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
 
+use crate::boundary::{Boundary, BoundaryEdge, BoundaryKind, InflowProfile};
+use crate::colormap::{Colormap, ColormapKind, ColormapScale};
+use crate::diagnostics::Diagnostics;
+use crate::mac_grid::{Field, MacGrid};
+use crate::pressure::PressureSolverKind;
+use crate::raster::{render_png, Raster};
+use crate::util::get_sci_color_255;
+
 const SIM_HEIGHT: f32 = 1.0;
 const DEFAULT_OBSTACLE_RADIUS: f32 = 0.2;
 const DEFAULT_NUM_ITERS: usize = 10;
 const DEFAULT_OVER_RELAXATION: f32 = 1.9;
 const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
+const DEFAULT_INFLOW_VELOCITY: f32 = 1.0;
 const DEFAULT_SWIRL_PROBABILITY: f32 = 50.0;
 const DEFAULT_SWIRL_MAX_RADIUS: f32 = 0.05;
 const MAX_NUM_SWIRLS: usize = 100;
@@ -18,22 +27,37 @@ const OBSTACLE_INTERACTION_VELOCITY_SCALE: f32 = 0.2;
 const SWIRL_TIME_SPAN: f32 = 1.0;
 const SWIRL_OMEGA: f32 = 20.0;
 const SWIRL_DAMPING: f32 = 10.0;
+// rate at which the flow relaxes towards the wind velocity
+const WIND_DRAG: f32 = 2.0;
+const DEFAULT_BRUSH_RADIUS: f32 = 0.1;
+const BRUSH_INTERACTION_VELOCITY_SCALE: f32 = 0.5;
 const FIRE_COOLING: f32 = 1.2;
 const SMOKE_COOLING: f32 = 0.3;
 const LIFT: f32 = 3.0;
 const ACCELERATION: f32 = 6.0;
 const SMOKE_TEMPERATURE_CUTOFF: f32 = 0.3;
-
-#[derive(Clone, Copy)]
-enum Field {
-    U,
-    V,
-    T,
+const DEFAULT_IGNITION_TEMPERATURE: f32 = 0.4;
+const DEFAULT_BURN_RATE: f32 = 1.0;
+// temperature rise and smoke released per unit of burned fuel
+const HEAT_RELEASE: f32 = 4.0;
+const SMOKE_YIELD: f32 = 1.0;
+const SMOKE_DISSIPATION: f32 = 0.2;
+const SMOKE_COLOR: [f32; 3] = [110.0, 110.0, 110.0];
+const FUEL_COLOR: [f32; 3] = [60.0, 90.0, 30.0];
+const OBSTACLE_COLOR: Vec3 = Vec3::splat(0.25); // #404040
+const SWIRL_COLOR: Vec3 = Vec3::splat(0.19); // #303030
+
+/// Adds fuel inside a disk at a constant rate until it runs dry
+struct FuelEmitter {
+    pos: Vec2,
+    radius: f32,
+    rate: f32,
+    // remaining time in seconds
+    remaining: f32,
 }
 
 #[wasm_bindgen]
 pub struct FireSimulation {
-    h: f32,
     #[wasm_bindgen(readonly)]
     pub dt: f32,
     pub num_iters: usize,
@@ -43,18 +67,20 @@ pub struct FireSimulation {
     obstacle_radius: f32,
     pub burning_obstacle: bool,
     pub burning_floor: bool,
-
-    num_cells_x: usize,
-    num_cells_y: usize,
-    #[wasm_bindgen(readonly)]
-    pub num_cells: usize,
-    u: Vec<f32>,
-    v: Vec<f32>,
+    pub ignition_temperature: f32,
+    pub burn_rate: f32,
+    fuel_emitters: Vec<FuelEmitter>,
+    wind: Vec2,
+    pub brush_radius: f32,
+    brush_pos: Vec2,
+
+    grid: MacGrid,
     new_u: Vec<f32>,
     new_v: Vec<f32>,
-    s: Vec<f32>,
     t: Vec<f32>,
-    new_t: Vec<f32>,
+    fuel: Vec<f32>,
+    smoke: Vec<f32>,
+    new_scalar: Vec<f32>,
 
     pub swirl_probability: f32,
     swirl_max_radius: f32,
@@ -63,39 +89,38 @@ pub struct FireSimulation {
     swirl_omega: Vec<f32>,
     swirl_time: Vec<f32>,
 
+    diagnostics: Diagnostics,
+
     // rendering
     width: f32,
     height: f32,
     c_scale: f32,
-    context: CanvasRenderingContext2d,
+    context: Option<CanvasRenderingContext2d>,
     pub show_obstacle: bool,
     pub show_swirls: bool,
+    pub show_divergence: bool,
+    temperature_colormap: Colormap,
 }
 
-fn set_fire_color(dest: &mut [u8; 4], val: f32) {
-    let val = f32::clamp(val, 0.0, 1.0);
-
-    let src = if val < SMOKE_TEMPERATURE_CUTOFF {
-        let s = val / SMOKE_TEMPERATURE_CUTOFF;
-        [51.0 * s, 51.0 * s, 51.0 * s]
-    } else if val < 0.5 {
-        let s = (val - SMOKE_TEMPERATURE_CUTOFF) / 0.2;
-        [51.0 + 204.0 * s, 25.5, 25.5]
-    } else {
-        let s = (val - 0.5) / 0.48;
-        [255.0, 255.0 * s, 0.0]
-    };
-
-    dest[0] = f32::floor(src[0]) as u8;
-    dest[1] = f32::floor(src[1]) as u8;
-    dest[2] = f32::floor(src[2]) as u8;
+fn blend_color(dest: &mut [u8; 4], src: [f32; 3], alpha: f32) {
+    let alpha = f32::clamp(alpha, 0.0, 1.0);
+    for k in 0..3 {
+        let c = f32::from(dest[k]);
+        dest[k] = f32::floor(c + (src[k] - c) * alpha) as u8;
+    }
 }
 
 #[wasm_bindgen]
 impl FireSimulation {
+    /// Without a `context` the simulation runs headless and is rendered with
+    /// `draw_raster` or `draw_png`
     #[must_use]
     #[wasm_bindgen(constructor)]
-    pub fn new(width: f32, height: f32, context: CanvasRenderingContext2d) -> FireSimulation {
+    pub fn new(
+        width: f32,
+        height: f32,
+        context: Option<CanvasRenderingContext2d>,
+    ) -> FireSimulation {
         let width = width.floor();
         let height = height.floor();
 
@@ -114,8 +139,7 @@ impl FireSimulation {
         }
         let obstacle_pos = Vec2::new(0.5 * num_cells_x as f32 * h, 0.3 * num_cells_y as f32 * h);
 
-        Self {
-            h,
+        let mut fire = Self {
             dt: DEFAULT_TIMESTEP,
             num_iters: DEFAULT_NUM_ITERS,
             over_relaxation: DEFAULT_OVER_RELAXATION,
@@ -124,17 +148,20 @@ impl FireSimulation {
             obstacle_radius: DEFAULT_OBSTACLE_RADIUS,
             burning_obstacle: true,
             burning_floor: false,
-
-            num_cells_x,
-            num_cells_y,
-            num_cells,
-            u: vec![0.0; num_cells],
-            v: vec![0.0; num_cells],
+            ignition_temperature: DEFAULT_IGNITION_TEMPERATURE,
+            burn_rate: DEFAULT_BURN_RATE,
+            fuel_emitters: vec![],
+            wind: Vec2::ZERO,
+            brush_radius: DEFAULT_BRUSH_RADIUS,
+            brush_pos: Vec2::ZERO,
+
+            grid: MacGrid::new(num_cells_x, num_cells_y, h, BoundaryKind::Outflow),
             new_u: vec![0.0; num_cells],
             new_v: vec![0.0; num_cells],
-            s: vec![1.0; num_cells],
             t: vec![0.0; num_cells],
-            new_t: vec![0.0; num_cells],
+            fuel: vec![0.0; num_cells],
+            smoke: vec![0.0; num_cells],
+            new_scalar: vec![0.0; num_cells],
 
             swirl_probability,
             swirl_max_radius,
@@ -143,6 +170,8 @@ impl FireSimulation {
             swirl_omega: vec![0.0; MAX_NUM_SWIRLS],
             swirl_time: vec![0.0; MAX_NUM_SWIRLS],
 
+            diagnostics: Diagnostics::new(num_cells_x, num_cells_y, h),
+
             // rendering
             width,
             height,
@@ -150,198 +179,164 @@ impl FireSimulation {
             context,
             show_obstacle: true,
             show_swirls: false,
-        }
-    }
-
-    fn solve_incompressibility(&mut self) {
-        let n = self.num_cells_y;
-        for _ in 0..self.num_iters {
-            for i in 1..self.num_cells_x - 1 {
-                for j in 1..self.num_cells_y - 1 {
-                    if self.s[i * n + j] == 0.0 {
-                        continue;
-                    }
-
-                    let sx0 = self.s[(i - 1) * n + j];
-                    let sx1 = self.s[(i + 1) * n + j];
-                    let sy0 = self.s[i * n + j - 1];
-                    let sy1 = self.s[i * n + j + 1];
-                    let s = sx0 + sx1 + sy0 + sy1;
-                    if s == 0.0 {
-                        continue;
-                    }
-
-                    let div = self.u[(i + 1) * n + j] - self.u[i * n + j] + self.v[i * n + j + 1]
-                        - self.v[i * n + j];
-
-                    let p = -div / s * self.over_relaxation;
-                    self.u[i * n + j] -= sx0 * p;
-                    self.u[(i + 1) * n + j] += sx1 * p;
-                    self.v[i * n + j] -= sy0 * p;
-                    self.v[i * n + j + 1] += sy1 * p;
-                }
-            }
-        }
-    }
-
-    fn extrapolate(&mut self) {
-        let n = self.num_cells_y;
-        for i in 0..self.num_cells_x {
-            self.u[i * n] = self.u[i * n + 1];
-            self.u[i * n + n - 1] = self.u[i * n + n - 2];
-        }
-        for j in 0..self.num_cells_y {
-            self.v[j] = self.v[n + j];
-            self.v[(self.num_cells_x - 1) * n + j] = self.v[(self.num_cells_x - 2) * n + j];
-        }
-    }
-
-    #[must_use]
-    fn sample_field(&self, x: f32, y: f32, field: Field) -> f32 {
-        let n = self.num_cells_y;
-        let h = self.h;
-        let h1 = 1.0 / h;
-        let h2 = 0.5 * h;
-        let x = x.clamp(h, self.num_cells_x as f32 * h);
-        let y = y.clamp(h, self.num_cells_y as f32 * h);
-
-        let mut dx = 0.0;
-        let mut dy = 0.0;
-        let f = match field {
-            Field::U => {
-                dy = h2;
-                &self.u
-            }
-            Field::V => {
-                dx = h2;
-                &self.v
-            }
-            Field::T => {
-                dx = h2;
-                dy = h2;
-                &self.t
-            }
+            show_divergence: false,
+            temperature_colormap: Colormap::new(ColormapKind::Fire, ColormapScale::Linear),
         };
 
-        let x0 = f32::min(f32::floor((x - dx) * h1), (self.num_cells_x - 1) as f32) as usize;
-        let tx = ((x - dx) - x0 as f32 * h) * h1;
-        let x1 = usize::min(x0 + 1, self.num_cells_x - 1);
-
-        let y0 = f32::min(f32::floor((y - dy) * h1), (self.num_cells_y - 1) as f32) as usize;
-        let ty = ((y - dy) - y0 as f32 * h) * h1;
-        let y1 = usize::min(y0 + 1, self.num_cells_y - 1);
-
-        let sx = 1.0 - tx;
-        let sy = 1.0 - ty;
-
-        sx * sy * f[x0 * n + y0]
-            + tx * sy * f[x1 * n + y0]
-            + tx * ty * f[x1 * n + y1]
-            + sx * ty * f[x0 * n + y1]
-    }
+        for edge in [
+            BoundaryEdge::Left,
+            BoundaryEdge::Right,
+            BoundaryEdge::Bottom,
+            BoundaryEdge::Top,
+        ] {
+            fire.grid
+                .boundaries
+                .set_inflow(edge, DEFAULT_INFLOW_VELOCITY, InflowProfile::Uniform);
+        }
+        fire.grid.apply_boundaries();
 
-    #[must_use]
-    fn avg_u(&self, i: usize, j: usize) -> f32 {
-        let n = self.num_cells_y;
-        (self.u[i * n + j - 1]
-            + self.u[i * n + j]
-            + self.u[(i + 1) * n + j - 1]
-            + self.u[(i + 1) * n + j])
-            * 0.25
+        fire
     }
 
-    #[must_use]
-    fn avg_v(&self, i: usize, j: usize) -> f32 {
-        let n = self.num_cells_y;
-        (self.v[(i - 1) * n + j]
-            + self.v[i * n + j]
-            + self.v[(i - 1) * n + j + 1]
-            + self.v[i * n + j + 1])
-            * 0.25
+    fn solve_incompressibility(&mut self) {
+        // the fire only needs the velocities, the pressure is not reported
+        self.grid.p.fill(0.0);
+        self.grid.solve_incompressibility(
+            PressureSolverKind::GaussSeidel,
+            self.num_iters,
+            self.over_relaxation,
+            None,
+            |_| true,
+            |_| 0.0,
+        );
     }
 
     fn advect_vel(&mut self) {
-        self.new_u.copy_from_slice(&self.u);
-        self.new_v.copy_from_slice(&self.v);
+        self.new_u.copy_from_slice(&self.grid.u);
+        self.new_v.copy_from_slice(&self.grid.v);
 
         let dt = self.dt;
-        let n = self.num_cells_y;
-        let h = self.h;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
         let h2 = 0.5 * h;
 
-        for i in 1..self.num_cells_x {
-            for j in 1..self.num_cells_y {
+        for i in 1..self.grid.num_cells_x {
+            for j in 1..self.grid.num_cells_y {
                 // u component
-                if self.s[i * n + j] != 0.0 && self.s[(i - 1) * n + j] != 0.0 && j < n - 1 {
+                if self.grid.s[i * n + j] != 0.0 && self.grid.s[(i - 1) * n + j] != 0.0 && j < n - 1
+                {
                     let mut x = i as f32 * h;
                     let mut y = j as f32 * h + h2;
-                    let mut u = self.u[i * n + j];
-                    let v = self.avg_v(i, j);
+                    let mut u = self.grid.u[i * n + j];
+                    let v = self.grid.avg_v(i, j);
                     x -= dt * u;
                     y -= dt * v;
-                    u = self.sample_field(x, y, Field::U);
+                    u = self.grid.sample_u(x, y);
                     self.new_u[i * n + j] = u;
                 }
                 // v component
-                if self.s[i * n + j] != 0.0
-                    && self.s[i * n + j - 1] != 0.0
-                    && i < self.num_cells_x - 1
+                if self.grid.s[i * n + j] != 0.0
+                    && self.grid.s[i * n + j - 1] != 0.0
+                    && i < self.grid.num_cells_x - 1
                 {
                     let mut x = i as f32 * h + h2;
                     let mut y = j as f32 * h;
-                    let u = self.avg_u(i, j);
-                    let mut v = self.v[i * n + j];
+                    let u = self.grid.avg_u(i, j);
+                    let mut v = self.grid.v[i * n + j];
                     x -= dt * u;
                     y -= dt * v;
-                    v = self.sample_field(x, y, Field::V);
+                    v = self.grid.sample_v(x, y);
                     self.new_v[i * n + j] = v;
                 }
             }
         }
 
-        self.u.copy_from_slice(&self.new_u);
-        self.v.copy_from_slice(&self.new_v);
+        self.grid.u.copy_from_slice(&self.new_u);
+        self.grid.v.copy_from_slice(&self.new_v);
     }
 
-    fn advect_temperature(&mut self) {
-        self.new_t.copy_from_slice(&self.t);
-
+    /// Advects the cell-centered temperature, fuel and smoke fields
+    fn advect_scalars(&mut self) {
         let dt = self.dt;
-        let n = self.num_cells_y;
-        let h = self.h;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
         let h2 = 0.5 * h;
 
-        for i in 1..self.num_cells_x - 1 {
-            for j in 1..self.num_cells_y - 1 {
-                if self.s[i * n + j] != 0.0 {
-                    let u = (self.u[i * n + j] + self.u[(i + 1) * n + j]) * 0.5;
-                    let v = (self.v[i * n + j] + self.v[i * n + j + 1]) * 0.5;
-                    let x = i as f32 * h + h2 - dt * u;
-                    let y = j as f32 * h + h2 - dt * v;
+        for f in [&mut self.t, &mut self.fuel, &mut self.smoke] {
+            self.new_scalar.copy_from_slice(f);
+
+            for i in 1..self.grid.num_cells_x - 1 {
+                for j in 1..self.grid.num_cells_y - 1 {
+                    if self.grid.s[i * n + j] != 0.0 {
+                        let u = (self.grid.u[i * n + j] + self.grid.u[(i + 1) * n + j]) * 0.5;
+                        let v = (self.grid.v[i * n + j] + self.grid.v[i * n + j + 1]) * 0.5;
+                        let x = i as f32 * h + h2 - dt * u;
+                        let y = j as f32 * h + h2 - dt * v;
 
-                    self.new_t[i * n + j] = self.sample_field(x, y, Field::T);
+                        self.new_scalar[i * n + j] = self.grid.sample(f, x, y, Field::Center);
+                    }
                 }
             }
+
+            f.copy_from_slice(&self.new_scalar);
+            self.grid.boundaries.apply_scalar(f);
         }
+    }
 
-        self.t.copy_from_slice(&self.new_t);
+    /// Adds the fuel of the emitters which have not run dry
+    fn emit_fuel(&mut self) {
+        let dt = self.dt;
+        for e in 0..self.fuel_emitters.len() {
+            let emitter = &mut self.fuel_emitters[e];
+            if emitter.remaining <= 0.0 {
+                continue;
+            }
+            let amount = emitter.rate * f32::min(dt, emitter.remaining);
+            emitter.remaining -= dt;
+            let FuelEmitter { pos, radius, .. } = *emitter;
+            self.for_each_cell_in_disk(pos, radius, |fire, id| fire.fuel[id] += amount);
+        }
+    }
+
+    /// Calls `f` with the id of each interior cell whose center lies in the
+    /// disk
+    fn for_each_cell_in_disk(
+        &mut self,
+        pos: Vec2,
+        radius: f32,
+        mut f: impl FnMut(&mut Self, usize),
+    ) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let r2 = radius * radius;
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                let dx = (i as f32 + 0.5) * h - pos.x;
+                let dy = (j as f32 + 0.5) * h - pos.y;
+                if dx * dx + dy * dy < r2 {
+                    f(self, i * n + j);
+                }
+            }
+        }
     }
 
     fn update_fire(&mut self) {
         let dt = self.dt;
-        let h = self.h;
+        let h = self.grid.h;
         let swirl_damping = SWIRL_DAMPING * dt;
         let swirl_probability = self.swirl_probability * h * h;
 
         let fire_cooling = FIRE_COOLING * dt;
         let smoke_cooling = SMOKE_COOLING * dt;
+        let max_burned = self.burn_rate * dt;
+        let smoke_decay = f32::max(1.0 - SMOKE_DISSIPATION * dt, 0.0);
         let acceleration = ACCELERATION * dt;
         let kernel_radius = self.swirl_max_radius;
 
         // update swirls
-        let n = self.num_cells_y;
-        let max_x = (self.num_cells_x - 1) as f32 * h;
-        let max_y = (self.num_cells_y - 1) as f32 * h;
+        let n = self.grid.num_cells_y;
+        let max_x = (self.grid.num_cells_x - 1) as f32 * h;
+        let max_y = (self.grid.num_cells_y - 1) as f32 * h;
 
         // kill swirls
         let mut num = 0;
@@ -361,8 +356,8 @@ impl FireSimulation {
             //let age_scale = self.swirl_time[i] / SWIRL_TIME_SPAN;
             let mut x = self.swirl_pos[i][0];
             let mut y = self.swirl_pos[i][1];
-            let swirl_u = (1.0 - swirl_damping) * self.sample_field(x, y, Field::U);
-            let swirl_v = (1.0 - swirl_damping) * self.sample_field(x, y, Field::V);
+            let swirl_u = (1.0 - swirl_damping) * self.grid.sample_u(x, y);
+            let swirl_v = (1.0 - swirl_damping) * self.grid.sample_v(x, y);
             x += swirl_u * dt;
             y += swirl_v * dt;
             x = x.clamp(h, max_x);
@@ -376,11 +371,11 @@ impl FireSimulation {
             let y0 = f32::max(f32::floor((y - kernel_radius) / h), 0.0) as usize;
             let x1 = f32::min(
                 f32::floor((x + kernel_radius) / h) + 1.0,
-                self.num_cells_x as f32 - 1.0,
+                self.grid.num_cells_x as f32 - 1.0,
             ) as usize;
             let y1 = f32::min(
                 f32::floor((y + kernel_radius) / h) + 1.0,
-                self.num_cells_y as f32 - 1.0,
+                self.grid.num_cells_y as f32 - 1.0,
             ) as usize;
             for i in x0..=x1 {
                 for j in y0..=y1 {
@@ -404,12 +399,12 @@ impl FireSimulation {
 
                             if dim == 0 {
                                 let target = ry * omega + swirl_u;
-                                let u = self.u[n * i + j];
-                                self.u[n * i + j] += (target - u) * s;
+                                let u = self.grid.u[n * i + j];
+                                self.grid.u[n * i + j] += (target - u) * s;
                             } else {
                                 let target = -rx * omega + swirl_v;
-                                let v = self.v[n * i + j];
-                                self.v[n * i + j] += (target - v) * s;
+                                let v = self.grid.v[n * i + j];
+                                self.grid.v[n * i + j] += (target - v) * s;
                             }
                         }
                     }
@@ -423,8 +418,8 @@ impl FireSimulation {
         let obstacle_max_r = self.obstacle_radius + h;
         let obstacle_max_r2 = obstacle_max_r * obstacle_max_r;
 
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
+        for i in 0..self.grid.num_cells_x {
+            for j in 0..self.grid.num_cells_y {
                 let t = self.t[i * n + j];
 
                 let cooling = if t < SMOKE_TEMPERATURE_CUTOFF {
@@ -433,12 +428,26 @@ impl FireSimulation {
                     fire_cooling
                 };
                 self.t[i * n + j] = f32::max(t - cooling, 0.0);
-                let v = self.v[i * n + j];
+                let v = self.grid.v[i * n + j];
                 let target_v = t * LIFT;
-                self.v[i * n + j] += (target_v - v) * acceleration;
+                self.grid.v[i * n + j] += (target_v - v) * acceleration;
 
                 let mut num_new_swirls = 0;
 
+                // combustion, fuel above the ignition temperature burns,
+                // heating the cell and releasing smoke
+                let fuel = self.fuel[i * n + j];
+                if fuel > 0.0 && self.t[i * n + j] >= self.ignition_temperature {
+                    let burned = f32::min(fuel, max_burned);
+                    self.fuel[i * n + j] = fuel - burned;
+                    self.t[i * n + j] = f32::min(self.t[i * n + j] + HEAT_RELEASE * burned, 1.0);
+                    self.smoke[i * n + j] += SMOKE_YIELD * burned;
+                    if (random() as f32) < 0.5 * swirl_probability {
+                        num_new_swirls += 1;
+                    }
+                }
+                self.smoke[i * n + j] *= smoke_decay;
+
                 // obstacle burning
                 if self.burning_obstacle {
                     let dx = (i as f32 + 0.5) * h - self.obstacle_pos[0];
@@ -455,29 +464,25 @@ impl FireSimulation {
                 // floor burning
                 if j < 4 && self.burning_floor {
                     self.t[i * n + j] = 1.0;
-                    self.u[i * n + j] = 0.0;
-                    self.v[i * n + j] = 0.0;
+                    self.grid.u[i * n + j] = 0.0;
+                    self.grid.v[i * n + j] = 0.0;
                     if (random() as f32) < swirl_probability {
                         num_new_swirls += 1;
                     }
                 }
 
                 for _ in 0..num_new_swirls {
-                    if self.num_swirls >= MAX_NUM_SWIRLS {
+                    let omega = (-1.0 + 2.0 * (random() as f32)) * SWIRL_OMEGA;
+                    if !self.spawn_swirl(Vec2::new(i as f32 * h, j as f32 * h), omega) {
                         break;
                     }
-                    let nr = self.num_swirls;
-                    self.swirl_pos[nr] = Vec2::new(i as f32 * h, j as f32 * h);
-                    self.swirl_omega[nr] = (-1.0 + 2.0 * (random() as f32)) * SWIRL_OMEGA;
-                    self.swirl_time[nr] = SWIRL_TIME_SPAN;
-                    self.num_swirls += 1;
                 }
             }
         }
 
         // smooth temperatures
-        for i in 1..self.num_cells_x - 1 {
-            for j in 1..self.num_cells_y - 1 {
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
                 let t = self.t[i * n + j];
                 if t == 1.0 {
                     let avg = (self.t[(i - 1) * n + (j - 1)]
@@ -491,12 +496,72 @@ impl FireSimulation {
         }
     }
 
+    /// Starts a swirl, returns false if there are too many swirls already
+    fn spawn_swirl(&mut self, pos: Vec2, omega: f32) -> bool {
+        if self.num_swirls >= MAX_NUM_SWIRLS {
+            return false;
+        }
+        let nr = self.num_swirls;
+        self.swirl_pos[nr] = pos;
+        self.swirl_omega[nr] = omega;
+        self.swirl_time[nr] = SWIRL_TIME_SPAN;
+        self.num_swirls += 1;
+        true
+    }
+
+    /// Relaxes the flow component along the wind direction on the open faces
+    /// towards the wind speed, the cross-wind component stays untouched
+    fn apply_wind(&mut self) {
+        if self.wind == Vec2::ZERO {
+            return;
+        }
+        let n = self.grid.num_cells_y;
+        let k = f32::min(WIND_DRAG * self.dt, 1.0);
+        let speed = self.wind.length();
+        let dir = self.wind / speed;
+
+        self.new_u.copy_from_slice(&self.grid.u);
+        self.new_v.copy_from_slice(&self.grid.v);
+
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                if self.grid.s[i * n + j] == 0.0 {
+                    continue;
+                }
+                if self.grid.s[(i - 1) * n + j] != 0.0 {
+                    let vel = Vec2::new(self.grid.u[i * n + j], self.grid.avg_v(i, j));
+                    self.new_u[i * n + j] += (speed - vel.dot(dir)) * k * dir.x;
+                }
+                if self.grid.s[i * n + j - 1] != 0.0 {
+                    let vel = Vec2::new(self.grid.avg_u(i, j), self.grid.v[i * n + j]);
+                    self.new_v[i * n + j] += (speed - vel.dot(dir)) * k * dir.y;
+                }
+            }
+        }
+
+        self.grid.u.copy_from_slice(&self.new_u);
+        self.grid.v.copy_from_slice(&self.new_v);
+    }
+
     pub fn step(&mut self) {
+        self.apply_wind();
         self.solve_incompressibility();
-        self.extrapolate();
+        self.diagnostics
+            .update_divergence(&self.grid.u, &self.grid.v, |id| self.grid.s[id] != 0.0);
+
+        self.grid
+            .boundaries
+            .apply_velocity(&mut self.grid.u, &mut self.grid.v);
         self.advect_vel();
-        self.advect_temperature();
+        self.advect_scalars();
+        self.emit_fuel();
         self.update_fire();
+
+        // the fire has no physical density, report energy per unit density
+        self.diagnostics
+            .update_kinetic_energy(1.0, &self.grid.u, &self.grid.v, |id| self.grid.s[id] != 0.0);
+        self.diagnostics
+            .update_total_mass(|id| self.t[id], |id| self.grid.s[id] != 0.0);
     }
 
     #[allow(clippy::inline_always)]
@@ -512,17 +577,34 @@ impl FireSimulation {
     }
 
     pub fn draw_buffer(&mut self, render_buffer: &mut [u8]) {
-        let h = self.h;
+        let h = self.grid.h;
         let cx = f32::floor(self.c_scale * h) as usize + 1;
         let cy = f32::floor(self.c_scale * h) as usize + 1;
-        let n = self.num_cells_y;
+        let n = self.grid.num_cells_y;
 
         let mut color = [255; 4];
-
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
-                let t = self.t[i * n + j];
-                set_fire_color(&mut color, t);
+        let div_max = self.diagnostics.max_divergence;
+
+        for i in 0..self.grid.num_cells_x {
+            for j in 0..self.grid.num_cells_y {
+                if self.show_divergence {
+                    let div = self.diagnostics.div[i * n + j];
+                    let sci_color = get_sci_color_255(div, -div_max, div_max);
+                    color[0] = f32::floor(sci_color[0]) as u8;
+                    color[1] = f32::floor(sci_color[1]) as u8;
+                    color[2] = f32::floor(sci_color[2]) as u8;
+                } else {
+                    let t = self.t[i * n + j];
+                    let t_color = self.temperature_colormap.color_255(t, 0.0, 1.0);
+                    color[0] = f32::floor(t_color[0]) as u8;
+                    color[1] = f32::floor(t_color[1]) as u8;
+                    color[2] = f32::floor(t_color[2]) as u8;
+                    // smoke and unburned fuel show behind the flames
+                    if t < SMOKE_TEMPERATURE_CUTOFF {
+                        blend_color(&mut color, SMOKE_COLOR, self.smoke[i * n + j]);
+                        blend_color(&mut color, FUEL_COLOR, self.fuel[i * n + j]);
+                    }
+                }
 
                 let x = f32::floor(self.c_x((i as f32 - 1.0) * h)) as usize;
                 let y = f32::floor(self.c_y((j as f32 + 1.0) * h)) as usize;
@@ -541,12 +623,14 @@ impl FireSimulation {
     }
 
     pub fn draw_canvas(&mut self) {
-        let c: &CanvasRenderingContext2d = &self.context;
+        let Some(c) = &self.context else {
+            return;
+        };
         let obstacle_color_hex: JsValue = JsValue::from("#404040");
         let swirl_color_hex: JsValue = JsValue::from("#303030");
 
         if self.show_obstacle {
-            let r = self.obstacle_radius + self.h;
+            let r = self.obstacle_radius + self.grid.h;
             let o = self.obstacle_pos;
 
             c.set_line_width(20.0);
@@ -588,6 +672,34 @@ impl FireSimulation {
         }
     }
 
+    /// Draws the complete frame, the cells and the overlays of `draw_canvas`,
+    /// into an RGBA buffer without a canvas
+    pub fn draw_raster(&mut self, render_buffer: &mut [u8]) {
+        self.draw_buffer(render_buffer);
+        let mut raster = Raster::new(self.width as usize, self.height as usize, render_buffer);
+
+        if self.show_obstacle {
+            let r = self.c_scale * (self.obstacle_radius + self.grid.h);
+            let center = Vec2::new(self.c_x(self.obstacle_pos.x), self.c_y(self.obstacle_pos.y));
+            raster.stroke_circle(center, r, 20.0, OBSTACLE_COLOR);
+        }
+
+        if self.show_swirls {
+            let r = self.c_scale * self.swirl_max_radius;
+            for p in &self.swirl_pos[..self.num_swirls] {
+                let center = Vec2::new(self.c_x(p.x), self.c_y(p.y));
+                raster.stroke_circle(center, r, 1.0, SWIRL_COLOR);
+            }
+        }
+    }
+
+    /// Draws the complete frame like `draw_raster` and encodes it as PNG
+    pub fn draw_png(&mut self) -> Vec<u8> {
+        render_png(self.width as usize, self.height as usize, |buffer| {
+            self.draw_raster(buffer);
+        })
+    }
+
     fn set_obstacle(&mut self, pos: Vec2, reset: bool) {
         let mut v = Vec2::ZERO;
 
@@ -599,25 +711,217 @@ impl FireSimulation {
         self.obstacle_pos = pos;
         let r = self.obstacle_radius;
         let r2 = r * r;
-        let n = self.num_cells_y;
-        let h = self.h;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
 
-        for i in 1..self.num_cells_x - 2 {
-            for j in 1..self.num_cells_y - 2 {
-                self.s[i * n + j] = 1.0;
+        for i in 1..self.grid.num_cells_x - 2 {
+            for j in 1..self.grid.num_cells_y - 2 {
+                self.grid.s[i * n + j] = 1.0;
                 let dx = (i as f32 + 0.5) * h - pos.x;
                 let dy = (j as f32 + 0.5) * h - pos.y;
 
                 if dx * dx + dy * dy < r2 {
-                    self.u[i * n + j] += v.x;
-                    self.u[(i + 1) * n + j] += v.x;
-                    self.v[i * n + j] += v.y;
-                    self.v[i * n + (j + 1)] += v.y;
+                    self.grid.u[i * n + j] += v.x;
+                    self.grid.u[(i + 1) * n + j] += v.x;
+                    self.grid.v[i * n + j] += v.y;
+                    self.grid.v[i * n + (j + 1)] += v.y;
                 }
             }
         }
     }
 
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn temperature_colormap(&self) -> Colormap {
+        self.temperature_colormap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_temperature_colormap(&mut self, colormap: Colormap) {
+        self.temperature_colormap = colormap;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_cells(&self) -> usize {
+        self.grid.num_cells
+    }
+
+    #[must_use]
+    pub fn boundary(&self, edge: BoundaryEdge) -> BoundaryKind {
+        self.grid.boundaries.get(edge).kind
+    }
+
+    /// Changes the boundary condition of a domain edge, see `Boundaries::set`
+    /// for how periodic edges pair up
+    pub fn set_boundary(&mut self, edge: BoundaryEdge, kind: BoundaryKind) {
+        let boundary = Boundary {
+            kind,
+            ..*self.grid.boundaries.get(edge)
+        };
+        self.grid.boundaries.set(edge, boundary);
+        self.grid.apply_boundaries();
+    }
+
+    #[must_use]
+    pub fn inflow_speed(&self, edge: BoundaryEdge) -> f32 {
+        self.grid.boundaries.get(edge).inflow_speed
+    }
+
+    #[must_use]
+    pub fn inflow_profile(&self, edge: BoundaryEdge) -> InflowProfile {
+        self.grid.boundaries.get(edge).inflow_profile
+    }
+
+    /// Sets the speed into the domain and the profile used if the edge is an
+    /// inflow
+    pub fn set_inflow(&mut self, edge: BoundaryEdge, speed: f32, profile: InflowProfile) {
+        self.grid.boundaries.set_inflow(edge, speed, profile);
+        self.grid.apply_boundaries();
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn max_divergence(&self) -> f32 {
+        self.diagnostics.max_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn mean_divergence(&self) -> f32 {
+        self.diagnostics.mean_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn kinetic_energy(&self) -> f32 {
+        self.diagnostics.kinetic_energy
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn total_mass(&self) -> f32 {
+        self.diagnostics.total_mass
+    }
+
+    /// Width and height of the grid in simulation units, which is the range
+    /// of the positions passed to the fuel methods
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn domain_size(&self) -> Vec<f32> {
+        vec![
+            self.grid.num_cells_x as f32 * self.grid.h,
+            self.grid.num_cells_y as f32 * self.grid.h,
+        ]
+    }
+
+    /// Fuel left in the domain, including the fuel that emitters have yet to
+    /// release
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn total_fuel(&self) -> f32 {
+        let h = self.grid.h;
+        let released = self.fuel.iter().sum::<f32>() * h * h;
+        let pending: f32 = self
+            .fuel_emitters
+            .iter()
+            .map(|e| e.rate * e.remaining.max(0.0) * PI as f32 * e.radius * e.radius)
+            .sum();
+        released + pending
+    }
+
+    /// Adds an emitter which releases fuel inside the disk at (x, y) at
+    /// `rate` per second for `duration` seconds, or forever if infinite
+    pub fn add_fuel_emitter(&mut self, x: f32, y: f32, radius: f32, rate: f32, duration: f32) {
+        self.fuel_emitters.push(FuelEmitter {
+            pos: Vec2::new(x, y),
+            radius,
+            rate,
+            remaining: duration,
+        });
+    }
+
+    /// Adds fuel inside the disk at (x, y)
+    pub fn add_fuel(&mut self, x: f32, y: f32, radius: f32, amount: f32) {
+        self.for_each_cell_in_disk(Vec2::new(x, y), radius, |fire, id| {
+            fire.fuel[id] += amount;
+        });
+    }
+
+    /// Heats the disk at (x, y) to the maximum temperature, which ignites any
+    /// fuel inside
+    pub fn ignite(&mut self, x: f32, y: f32, radius: f32) {
+        self.for_each_cell_in_disk(Vec2::new(x, y), radius, |fire, id| fire.t[id] = 1.0);
+    }
+
+    /// Removes all fuel and fuel emitters
+    pub fn clear_fuel(&mut self) {
+        self.fuel.fill(0.0);
+        self.fuel_emitters.clear();
+    }
+
+    /// Background wind velocity that the flow relaxes towards
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn wind(&self) -> Vec<f32> {
+        vec![self.wind.x, self.wind.y]
+    }
+
+    pub fn set_wind(&mut self, x: f32, y: f32) {
+        self.wind = Vec2::new(x, y);
+    }
+
+    /// Pushes the flow inside the disk at (x, y) towards the velocity
+    /// (vx, vy), with full strength at the center fading to zero at the rim
+    pub fn push(&mut self, x: f32, y: f32, radius: f32, vx: f32, vy: f32) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let pos = Vec2::new(x, y);
+
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                if self.grid.s[i * n + j] == 0.0 {
+                    continue;
+                }
+                let su = 1.0 - Vec2::new(i as f32 * h, (j as f32 + 0.5) * h).distance(pos) / radius;
+                if su > 0.0 && self.grid.s[(i - 1) * n + j] != 0.0 {
+                    self.grid.u[i * n + j] += (vx - self.grid.u[i * n + j]) * su;
+                }
+                let sv = 1.0 - Vec2::new((i as f32 + 0.5) * h, j as f32 * h).distance(pos) / radius;
+                if sv > 0.0 && self.grid.s[i * n + j - 1] != 0.0 {
+                    self.grid.v[i * n + j] += (vy - self.grid.v[i * n + j]) * sv;
+                }
+            }
+        }
+    }
+
+    /// Starts a swirl at (x, y) with angular velocity `omega`, positive is
+    /// clockwise, it lives as long as the swirls shed by the fire. Returns
+    /// false if the maximum number of swirls is reached
+    pub fn add_swirl(&mut self, x: f32, y: f32, omega: f32) -> bool {
+        self.spawn_swirl(Vec2::new(x, y), omega)
+    }
+
+    /// Pushes the flow along the path of the brush as it is dragged over
+    /// the canvas, `reset` starts a new stroke
+    pub fn push_from_canvas(&mut self, c_x: f32, c_y: f32, reset: bool) {
+        let x = c_x / self.c_scale;
+        let y = (self.height - c_y) / self.c_scale;
+        let pos = Vec2::new(x, y);
+        if !reset {
+            let v = (pos - self.brush_pos) / self.dt * BRUSH_INTERACTION_VELOCITY_SCALE;
+            self.push(x, y, self.brush_radius, v.x, v.y);
+        }
+        self.brush_pos = pos;
+    }
+
+    /// Starts a swirl at the canvas position, see `add_swirl`
+    pub fn add_swirl_from_canvas(&mut self, c_x: f32, c_y: f32, omega: f32) -> bool {
+        let x = c_x / self.c_scale;
+        let y = (self.height - c_y) / self.c_scale;
+        self.add_swirl(x, y, omega)
+    }
+
     pub fn set_obstacle_from_canvas(&mut self, c_x: f32, c_y: f32, reset: bool) {
         let x = c_x / self.c_scale;
         let y = (self.height - c_y) / self.c_scale;
@@ -1,8 +1,8 @@
 import GUI from 'lil-gui';
 import * as THREE from 'three';
 
-import { FireSimulation } from '../pkg';
-import { Demo, Scene2DCanvas, Scene2DConfig } from './lib';
+import { FireSimulation, BoundaryEdge, BoundaryKind, InflowProfile, ColormapKind, ColormapScale } from '../pkg';
+import { Demo, Scene2DCanvas, Scene2DConfig, enumToValueList, makeColormap, colormapToValues, downloadPng } from './lib';
 
 type FireDemoProps = {
     animate: boolean;
@@ -14,6 +14,32 @@ type FireDemoProps = {
     showSwirls: boolean;
     burningObstacle: boolean;
     burningFloor: boolean;
+    ignitionTemperature: number;
+    burnRate: number;
+    totalFuel: number;
+    addBurner: () => void;
+    addFuelFloor: () => void;
+    ignite: () => void;
+    clearFuel: () => void;
+    windX: number;
+    windY: number;
+    interaction: string;
+    brushRadius: number;
+    swirlOmega: number;
+    boundaryLeft: string; // enum string value
+    boundaryRight: string; // enum string value
+    boundaryBottom: string; // enum string value
+    boundaryTop: string; // enum string value
+    inflowSpeed: number;
+    inflowProfile: string; // enum string value
+    maxDivergence: number;
+    meanDivergence: number;
+    kineticEnergy: number;
+    totalMass: number;
+    showDivergence: boolean;
+    temperatureColormap: string; // enum string value
+    temperatureScale: string; // enum string value
+    savePng: () => void;
 };
 
 const FireDemoConfig: Scene2DConfig = {
@@ -47,11 +73,30 @@ class FireDemo implements Demo<FireSimulation, FireDemoProps> {
         this.props.showSwirls = this.sim.show_swirls;
         this.props.burningObstacle = this.sim.burning_obstacle;
         this.props.burningFloor = this.sim.burning_floor;
+        this.props.ignitionTemperature = this.sim.ignition_temperature;
+        this.props.burnRate = this.sim.burn_rate;
+        this.props.totalFuel = this.sim.total_fuel;
+        [this.props.windX, this.props.windY] = this.sim.wind;
+        this.props.brushRadius = this.sim.brush_radius;
+        this.updateBoundaryProps();
+        this.props.inflowSpeed = this.sim.inflow_speed(BoundaryEdge.Left);
+        this.props.inflowProfile = InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)];
+        this.props.maxDivergence = this.sim.max_divergence;
+        this.props.meanDivergence = this.sim.mean_divergence;
+        this.props.kineticEnergy = this.sim.kinetic_energy;
+        this.props.totalMass = this.sim.total_mass;
+        this.props.showDivergence = this.sim.show_divergence;
+        [this.props.temperatureColormap, this.props.temperatureScale] = colormapToValues(this.sim.temperature_colormap);
     }
 
     update() {
         if (this.props.animate) {
             this.sim.step();
+            this.props.maxDivergence = this.sim.max_divergence;
+            this.props.meanDivergence = this.sim.mean_divergence;
+            this.props.kineticEnergy = this.sim.kinetic_energy;
+            this.props.totalMass = this.sim.total_mass;
+            this.props.totalFuel = this.sim.total_fuel;
         }
     }
 
@@ -68,6 +113,7 @@ class FireDemo implements Demo<FireSimulation, FireDemoProps> {
     }
 
     private initControls(folder: GUI, canvas: HTMLCanvasElement) {
+        const [temperatureColormap, temperatureScale] = colormapToValues(this.sim.temperature_colormap);
         this.props = {
             animate: true,
             numCells: this.sim.num_cells,
@@ -78,6 +124,47 @@ class FireDemo implements Demo<FireSimulation, FireDemoProps> {
             showSwirls: this.sim.show_swirls,
             burningObstacle: this.sim.burning_obstacle,
             burningFloor: this.sim.burning_floor,
+            ignitionTemperature: this.sim.ignition_temperature,
+            burnRate: this.sim.burn_rate,
+            totalFuel: this.sim.total_fuel,
+            addBurner: () => {
+                // a burner near the floor which runs for a few seconds
+                const [width, _] = this.sim.domain_size;
+                const x = (0.1 + 0.8 * Math.random()) * width;
+                this.sim.add_fuel_emitter(x, 0.1, 0.04, 3.0, 8.0);
+            },
+            addFuelFloor: () => {
+                const [width, _] = this.sim.domain_size;
+                for (let x = 0.0; x < width; x += 0.02) {
+                    this.sim.add_fuel(x, 0.05, 0.04, 0.5);
+                }
+            },
+            ignite: () => {
+                const [width, _] = this.sim.domain_size;
+                this.sim.ignite(0.05 * width, 0.06, 0.05);
+            },
+            clearFuel: () => {
+                this.sim.clear_fuel();
+            },
+            windX: this.sim.wind[0],
+            windY: this.sim.wind[1],
+            interaction: 'obstacle',
+            brushRadius: this.sim.brush_radius,
+            swirlOmega: 20.0,
+            boundaryLeft: BoundaryKind[this.sim.boundary(BoundaryEdge.Left)],
+            boundaryRight: BoundaryKind[this.sim.boundary(BoundaryEdge.Right)],
+            boundaryBottom: BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)],
+            boundaryTop: BoundaryKind[this.sim.boundary(BoundaryEdge.Top)],
+            inflowSpeed: this.sim.inflow_speed(BoundaryEdge.Left),
+            inflowProfile: InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)],
+            maxDivergence: this.sim.max_divergence,
+            meanDivergence: this.sim.mean_divergence,
+            kineticEnergy: this.sim.kinetic_energy,
+            totalMass: this.sim.total_mass,
+            showDivergence: this.sim.show_divergence,
+            temperatureColormap,
+            temperatureScale,
+            savePng: () => downloadPng(this.sim.draw_png(), 'fire.png'),
         };
         folder.add(this.props, 'numCells').name('cells').disable().listen();
         folder.add(this.props, 'numIters').name('substeps').disable().listen();
@@ -91,10 +178,49 @@ class FireDemo implements Demo<FireSimulation, FireDemoProps> {
             }
         }).listen();
         folder.add(this.props, 'burningFloor').name('burning floor').onFinishChange((v: boolean) => (this.sim.burning_floor = v)).listen();
+        const fuel = folder.addFolder('Fuel');
+        fuel.add(this.props, 'ignitionTemperature').decimals(2).min(0.0).max(1.0).step(0.01).name('ignition temperature').onChange((v: number) => (this.sim.ignition_temperature = v)).listen();
+        fuel.add(this.props, 'burnRate').decimals(2).min(0.0).max(5.0).step(0.05).name('burn rate').onChange((v: number) => (this.sim.burn_rate = v)).listen();
+        fuel.add(this.props, 'totalFuel').decimals(4).name('total fuel').disable().listen();
+        fuel.add(this.props, 'addBurner').name('add burner');
+        fuel.add(this.props, 'addFuelFloor').name('fuel floor');
+        fuel.add(this.props, 'ignite').name('ignite left');
+        fuel.add(this.props, 'clearFuel').name('clear fuel');
+        const wind = folder.addFolder('Wind');
+        wind.add(this.props, 'windX').decimals(2).min(-2.0).max(2.0).step(0.05).name('wind x').onChange((_: number) => this.sim.set_wind(this.props.windX, this.props.windY)).listen();
+        wind.add(this.props, 'windY').decimals(2).min(-2.0).max(2.0).step(0.05).name('wind y').onChange((_: number) => this.sim.set_wind(this.props.windX, this.props.windY)).listen();
+        wind.add(this.props, 'interaction', ['obstacle', 'push', 'swirl']).name('mouse');
+        wind.add(this.props, 'brushRadius').decimals(2).min(0.02).max(0.3).step(0.01).name('brush radius').onChange((v: number) => (this.sim.brush_radius = v)).listen();
+        wind.add(this.props, 'swirlOmega').decimals(0).min(-50).max(50).step(1).name('swirl omega');
+        const bounds = folder.addFolder('Boundaries');
+        const boundaryProps: [string, BoundaryEdge][] = [
+            ['boundaryLeft', BoundaryEdge.Left],
+            ['boundaryRight', BoundaryEdge.Right],
+            ['boundaryBottom', BoundaryEdge.Bottom],
+            ['boundaryTop', BoundaryEdge.Top],
+        ];
+        for (const [prop, edge] of boundaryProps) {
+            bounds.add(this.props, prop, enumToValueList(BoundaryKind)).name(BoundaryEdge[edge].toLowerCase()).onChange((v: string) => {
+                this.sim.set_boundary(edge, Object.values(BoundaryKind).indexOf(v));
+                // periodic edges come in pairs
+                this.updateBoundaryProps();
+            }).listen();
+        }
+        bounds.add(this.props, 'inflowSpeed').decimals(2).min(0.0).max(5.0).step(0.1).name('inflow speed').onChange((_: number) => this.updateInflow()).listen();
+        bounds.add(this.props, 'inflowProfile', enumToValueList(InflowProfile)).name('inflow profile').onChange((_: string) => this.updateInflow()).listen();
+        const diag = folder.addFolder('Diagnostics');
+        diag.add(this.props, 'maxDivergence').decimals(4).name('max divergence').disable().listen();
+        diag.add(this.props, 'meanDivergence').decimals(4).name('mean divergence').disable().listen();
+        diag.add(this.props, 'kineticEnergy').decimals(4).name('kinetic energy').disable().listen();
+        diag.add(this.props, 'totalMass').decimals(4).name('total temperature').disable().listen();
         const sub = folder.addFolder('Rendering');
         sub.add(this.props, 'showObstacle').name('show obstacle').onFinishChange((v: boolean) => (this.sim.show_obstacle = v)).listen();
         sub.add(this.props, 'showSwirls').name('show swirls').onFinishChange((v: boolean) => (this.sim.show_swirls = v)).listen();
+        sub.add(this.props, 'showDivergence').name('show divergence').onFinishChange((v: boolean) => (this.sim.show_divergence = v)).listen();
+        sub.add(this.props, 'temperatureColormap', enumToValueList(ColormapKind)).name('colormap').onChange((_: string) => this.updateColormap()).listen();
+        sub.add(this.props, 'temperatureScale', enumToValueList(ColormapScale)).name('colormap scale').onChange((_: string) => this.updateColormap()).listen();
         sub.add(this.props, 'animate').listen();
+        sub.add(this.props, 'savePng').name('save png');
 
         // scene interaction
         this.mouseDown = false;
@@ -112,12 +238,40 @@ class FireDemo implements Demo<FireSimulation, FireDemoProps> {
         });
     }
 
+    private updateColormap() {
+        this.sim.temperature_colormap = makeColormap(this.rust_wasm, this.props.temperatureColormap, this.props.temperatureScale);
+    }
+
+    private updateBoundaryProps() {
+        this.props.boundaryLeft = BoundaryKind[this.sim.boundary(BoundaryEdge.Left)];
+        this.props.boundaryRight = BoundaryKind[this.sim.boundary(BoundaryEdge.Right)];
+        this.props.boundaryBottom = BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)];
+        this.props.boundaryTop = BoundaryKind[this.sim.boundary(BoundaryEdge.Top)];
+    }
+
+    // all edges share the inflow settings, they only apply to inflow edges
+    private updateInflow() {
+        const profile = Object.values(InflowProfile).indexOf(this.props.inflowProfile);
+        for (const edge of [BoundaryEdge.Left, BoundaryEdge.Right, BoundaryEdge.Bottom, BoundaryEdge.Top]) {
+            this.sim.set_inflow(edge, this.props.inflowSpeed, profile);
+        }
+    }
+
     private setMousePos(x: number, y: number, reset: boolean) {
         const mx = x - this.mouseOffset.x;
         const my = y - this.mouseOffset.y;
-        this.sim.set_obstacle_from_canvas(mx, my, reset);
-        this.props.showObstacle = true;
-        this.sim.show_obstacle = true;
+        switch (this.props.interaction) {
+            case 'push':
+                this.sim.push_from_canvas(mx, my, reset);
+                break;
+            case 'swirl':
+                this.sim.add_swirl_from_canvas(mx, my, this.props.swirlOmega);
+                break;
+            default:
+                this.sim.set_obstacle_from_canvas(mx, my, reset);
+                this.props.showObstacle = true;
+                this.sim.show_obstacle = true;
+        }
         this.props.animate = true;
     }
 
@@ -7,9 +7,17 @@
 use std::f32::consts::PI;
 
 use glam::{vec3, UVec2, Vec2, Vec3};
+use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlUniformLocation};
 
+use crate::boundary::{Boundary, BoundaryEdge, BoundaryKind, InflowProfile};
+use crate::diagnostics::Diagnostics;
+use crate::mac_grid::MacGrid;
+use crate::pressure::PressureSolverKind;
+use crate::raster::{render_png, Raster};
+use crate::rigid_body::{RigidBody, Shape};
+use crate::surface::ParticleSurface;
 use crate::util::{compile_shader, get_sci_color, link_program, set_buffers_and_attributes};
 
 const SIM_HEIGHT: f32 = 3.0;
@@ -27,13 +35,71 @@ const DEFAULT_FLIP_RATIO: f32 = 0.9;
 const DEFAULT_OVER_RELAXATION: f32 = 1.9;
 const DEFAULT_GRAVITY: f32 = -9.81;
 const DEFAULT_DT: f32 = 1.0 / 60.0;
+const DEFAULT_SURFACE_TENSION: f32 = 0.0;
+// kernel radius of the cohesion relative to the particle radius
+const COHESION_KERNEL_SCALE: f32 = 4.0;
 
 const COLOR_DIFFUSION_COEFF: f32 = 0.001;
 const PARTICLE_COLOR: Vec3 = vec3(0.0, 0.0, 1.0);
+// colors of the phases in the order they are added, the first is the default
+// fluid
+const PHASE_COLORS: [Vec3; 4] = [
+    PARTICLE_COLOR,
+    vec3(0.9, 0.6, 0.0),
+    vec3(0.8, 0.1, 0.1),
+    vec3(0.1, 0.7, 0.2),
+];
 const OBSTALCE_COLOR: Vec3 = vec3(1.0, 0.0, 0.0);
 const OBSTACLE_DISK_NUM_SEGS: usize = 100;
 const GRID_POINT_SIZE_SCALE: f32 = 0.9;
 const PARTICLE_POINT_SIZE_SCALE: f32 = 2.0;
+const SURFACE_COLOR: Vec3 = vec3(0.1, 0.3, 0.8);
+const SURFACE_OUTLINE_COLOR: Vec3 = vec3(0.7, 0.85, 1.0);
+// relative to the cell size
+const SURFACE_NODE_SPACING_SCALE: f32 = 0.5;
+// relative to the particle radius
+const SURFACE_KERNEL_RADIUS_SCALE: f32 = 4.0;
+const SURFACE_PARTICLE_RADIUS_SCALE: f32 = 1.5;
+const DEFAULT_NUM_SURFACE_SMOOTHING_ITERS: usize = 4;
+const BODY_COLOR: Vec3 = vec3(0.8, 0.5, 0.2);
+const BODY_OUTLINE_COLOR: Vec3 = vec3(0.3, 0.15, 0.0);
+
+/// Particle-grid velocity transfer. `FlipPic` blends PIC and FLIP by
+/// `flip_ratio`, while `Apic` transfers an affine velocity per particle, see
+/// Jiang et al., "The Affine Particle-In-Cell Method".
+#[wasm_bindgen(js_name = FlipTransferScheme)]
+#[derive(PartialEq, Clone, Copy)]
+pub enum TransferScheme {
+    FlipPic,
+    Apic,
+}
+
+/// Adds particles inside a rectangle at the rate at which fluid of the given
+/// velocity flows through it, and keeps the particles in it at that velocity
+struct Emitter {
+    min: Vec2,
+    max: Vec2,
+    vel: Vec2,
+    // fractional particles carried over to the next step
+    accum: f32,
+}
+
+/// A fluid whose particles share a density and a color. The density is
+/// relative to the default fluid, so that it follows changes of `density`.
+struct Phase {
+    relative_density: f32,
+    color: Vec3,
+}
+
+/// Removes all particles inside a rectangle
+struct Sink {
+    min: Vec2,
+    max: Vec2,
+}
+
+fn contains(min: Vec2, max: Vec2, x: Vec2) -> bool {
+    x.cmpge(min).all() && x.cmple(max).all()
+}
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum CellKind {
@@ -47,7 +113,6 @@ enum CellKind {
 pub struct FlipSimulation {
     #[wasm_bindgen(readonly)]
     pub density: f32,
-    h: f32,
     gravity: f32,
     #[wasm_bindgen(readonly)]
     pub dt: f32,
@@ -55,13 +120,20 @@ pub struct FlipSimulation {
     num_pressure_iters: usize,
     num_particle_iters: usize,
     pub flip_ratio: f32,
+    pub transfer_scheme: TransferScheme,
     pub over_relaxation: f32,
+    pub pressure_solver: PressureSolverKind,
     pub compensate_drift: bool,
     pub separate_particles: bool,
+    /// Surface tension coefficient in N/m
+    pub surface_tension: f32,
 
     obstacle_pos: Vec2,
     obstacle_vel: Vec2,
     obstacle_radius: f32,
+    bodies: Vec<RigidBody>,
+    // rigid body covering each solid cell
+    cell_body: Vec<Option<usize>>,
 
     particle_num_cells_x: usize,
     particle_num_cells_y: usize,
@@ -69,25 +141,34 @@ pub struct FlipSimulation {
     pub particle_num_cells: usize,
     #[wasm_bindgen(readonly)]
     pub num_particles: usize,
+    #[wasm_bindgen(readonly)]
+    pub max_particles: usize,
     particle_inv_spacing: f32,
     particle_radius: f32,
     particle_pos: Vec<Vec2>,
     particle_vel: Vec<Vec2>,
+    // velocity gradient of each velocity component, used by APIC
+    particle_affine: Vec<[Vec2; 2]>,
+    particle_phase: Vec<usize>,
+    // accelerations due to cohesion
+    particle_accel: Vec<Vec2>,
+    phases: Vec<Phase>,
     particle_density: Vec<f32>,
     particle_rest_density: f32,
-    num_cells_x: usize,
-    num_cells_y: usize,
-    #[wasm_bindgen(readonly)]
-    pub num_cells: usize,
+    emitters: Vec<Emitter>,
+    sinks: Vec<Sink>,
+    // fractional particles of each inflow edge carried over to the next step
+    inflow_accum: [f32; 4],
+    grid: MacGrid,
     inv_spacing: f32,
-    u: Vec<f32>,
-    v: Vec<f32>,
     du: Vec<f32>,
     dv: Vec<f32>,
     prev_u: Vec<f32>,
     prev_v: Vec<f32>,
-    p: Vec<f32>,
-    s: Vec<f32>,
+    // inverse fluid density of each face relative to `density`
+    inv_density_u: Vec<f32>,
+    inv_density_v: Vec<f32>,
+    diagnostics: Diagnostics,
     cell_num_particles: Vec<usize>,
     cell_first_particle: Vec<usize>,
     cell_particle_ids: Vec<usize>,
@@ -96,13 +177,19 @@ pub struct FlipSimulation {
     // rendering
     particle_color: Vec<Vec3>,
     cell_color: Vec<Vec3>,
+    surface: ParticleSurface,
+    body_triangles: Vec<Vec2>,
+    body_lines: Vec<Vec2>,
+    pub num_surface_smoothing_iters: usize,
     width: f32,
     height: f32,
     c_scale: f32,
     pub show_obstacle: bool,
     pub show_particles: bool,
     pub show_grid: bool,
-    renderer: WebGLRenderer,
+    pub show_divergence: bool,
+    pub show_surface: bool,
+    renderer: Option<WebGLRenderer>,
 }
 
 struct WebGLRenderer {
@@ -121,6 +208,7 @@ struct WebGLRenderer {
 
     mesh_program: WebGlProgram,
     disk_buffer: WebGlBuffer,
+    dynamic_mesh_buffer: WebGlBuffer,
     disk_id_buffer: WebGlBuffer,
     mesh_position_attrib_location: u32,
     mesh_domain_size_uniform: WebGlUniformLocation,
@@ -131,12 +219,18 @@ struct WebGLRenderer {
 
 #[wasm_bindgen]
 impl FlipSimulation {
+    /// Without a `context` the simulation runs headless and is rendered with
+    /// `draw_raster` or `draw_png`
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to initialize the webgl2 context and
+    /// compile/link shader programs.
     #[allow(clippy::too_many_lines)]
     #[wasm_bindgen(constructor)]
     pub fn new(
         width: f32,
         height: f32,
-        context: WebGl2RenderingContext,
+        context: Option<WebGl2RenderingContext>,
     ) -> Result<FlipSimulation, JsValue> {
         let width = width.floor();
         let height = height.floor();
@@ -163,35 +257,49 @@ impl FlipSimulation {
             (DAM_BREAK_REL_WATER_HEIGHT * domain_height - 2.0 * h - 2.0 * particle_radius) / dy,
         ) as usize;
         let num_particles = num_particles_x * num_particles_y;
-
-        let renderer = WebGLRenderer::new(
-            context,
-            width as i32,
-            height as i32,
-            num_cells_x,
-            num_cells_y,
-            h,
+        // emitters may at most fill the whole domain at the initial spacing
+        let max_particles = usize::max(
+            f32::floor((domain_width - 2.0 * h) * (domain_height - 2.0 * h) / (dx * dy)) as usize,
             num_particles,
-        )?;
+        );
+
+        let renderer = context
+            .map(|context| {
+                WebGLRenderer::new(
+                    context,
+                    width as i32,
+                    height as i32,
+                    num_cells_x,
+                    num_cells_y,
+                    h,
+                    max_particles,
+                )
+            })
+            .transpose()?;
 
         let mut fluid = Self {
             density: DEFAULT_DENSITY,
-            h,
             gravity: DEFAULT_GRAVITY,
             dt: DEFAULT_DT,
             num_substeps: DEFAULT_NUM_SUBSTEPS,
             num_pressure_iters: DEFAULT_NUM_PRESSURE_ITERS,
             num_particle_iters: DEFAULT_NUM_PARTICLE_ITERS,
             flip_ratio: DEFAULT_FLIP_RATIO,
+            transfer_scheme: TransferScheme::FlipPic,
             over_relaxation: DEFAULT_OVER_RELAXATION,
+            pressure_solver: PressureSolverKind::GaussSeidel,
             compensate_drift: true,
             separate_particles: true,
+            surface_tension: DEFAULT_SURFACE_TENSION,
 
             obstacle_pos: Vec2::ZERO,
             obstacle_vel: Vec2::ZERO,
             obstacle_radius: DEFAULT_OBSTACLE_RADIUS,
+            bodies: vec![],
+            cell_body: vec![None; num_cells],
 
             num_particles,
+            max_particles,
             particle_num_cells_x,
             particle_num_cells_y,
             particle_num_cells,
@@ -199,23 +307,30 @@ impl FlipSimulation {
             particle_radius,
             particle_pos: vec![Vec2::ZERO; num_particles],
             particle_vel: vec![Vec2::ZERO; num_particles],
-            particle_density: vec![0.0; num_particles],
+            particle_affine: vec![[Vec2::ZERO; 2]; num_particles],
+            particle_phase: vec![0; num_particles],
+            particle_accel: vec![Vec2::ZERO; num_particles],
+            phases: vec![Phase {
+                relative_density: 1.0,
+                color: PHASE_COLORS[0],
+            }],
+            particle_density: vec![0.0; num_cells],
             particle_rest_density: 0.0,
+            emitters: vec![],
+            sinks: vec![],
+            inflow_accum: [0.0; 4],
             cell_num_particles: vec![0; particle_num_cells],
             cell_first_particle: vec![0; particle_num_cells + 1],
             cell_particle_ids: vec![0; num_particles],
-            num_cells_x,
-            num_cells_y,
-            num_cells,
+            grid: MacGrid::new(num_cells_x, num_cells_y, h, BoundaryKind::FreeSlip),
             inv_spacing,
-            u: vec![0.0; num_cells],
-            v: vec![0.0; num_cells],
             du: vec![0.0; num_cells],
             dv: vec![0.0; num_cells],
             prev_u: vec![0.0; num_cells],
             prev_v: vec![0.0; num_cells],
-            p: vec![0.0; num_cells],
-            s: vec![0.0; num_cells],
+            inv_density_u: vec![1.0; num_cells],
+            inv_density_v: vec![1.0; num_cells],
+            diagnostics: Diagnostics::new(num_cells_x, num_cells_y, h),
             cell_kind: vec![CellKind::Air; num_cells],
 
             // rendering
@@ -224,10 +339,20 @@ impl FlipSimulation {
             c_scale: height / domain_height,
             particle_color: vec![PARTICLE_COLOR; num_particles],
             cell_color: vec![Vec3::ZERO; num_cells],
+            surface: ParticleSurface::new(
+                num_cells_x as f32 * h,
+                num_cells_y as f32 * h,
+                SURFACE_NODE_SPACING_SCALE * h,
+            ),
+            num_surface_smoothing_iters: DEFAULT_NUM_SURFACE_SMOOTHING_ITERS,
+            body_triangles: vec![],
+            body_lines: vec![],
 
             show_obstacle: true,
             show_particles: true,
             show_grid: false,
+            show_divergence: false,
+            show_surface: false,
             renderer,
         };
 
@@ -245,17 +370,12 @@ impl FlipSimulation {
             }
         }
 
-        // setup grid cells for fluid domain
-        let n = fluid.num_cells_y;
-        for i in 0..fluid.num_cells_x {
-            for j in 0..fluid.num_cells_y {
-                let mut s = 1.0; // fluid
-                if i == 0 || i == fluid.num_cells_x - 1 || j == 0 {
-                    s = 0.0; // solid
-                }
-                fluid.s[i * n + j] = s;
-            }
-        }
+        // setup grid cells for fluid domain, a tank open at the top
+        fluid
+            .grid
+            .boundaries
+            .set(BoundaryEdge::Top, Boundary::new(BoundaryKind::Outflow));
+        fluid.grid.boundaries.set_ghost_cells(&mut fluid.grid.s);
 
         // move obstacle out of the way for dam break
         fluid.set_obstacle(Vec2::new(domain_width * 0.6, domain_height * 0.5), true);
@@ -263,6 +383,204 @@ impl FlipSimulation {
         Ok(fluid)
     }
 
+    fn emit_particles(&mut self) {
+        // area per particle of the initial hexagonal packing
+        let r = self.particle_radius;
+        let particle_area = 2.0 * f32::sqrt(3.0) * r * r;
+
+        for e in 0..self.emitters.len() {
+            let Emitter { min, max, vel, .. } = self.emitters[e];
+
+            // particles inside the emitter move with the inflow
+            for i in 0..self.num_particles {
+                if contains(min, max, self.particle_pos[i]) {
+                    self.particle_vel[i] = vel;
+                    self.particle_affine[i] = [Vec2::ZERO; 2];
+                }
+            }
+
+            // add the volume which flowed through the emitter's cross section
+            let speed = vel.length();
+            if speed == 0.0 {
+                continue;
+            }
+            let extent = max - min;
+            let dir = vel / speed;
+            let cross_section = extent.x * dir.y.abs() + extent.y * dir.x.abs();
+            let emitter = &mut self.emitters[e];
+            emitter.accum += speed * cross_section * self.dt / particle_area;
+            let num_new = emitter.accum.floor();
+            emitter.accum -= num_new;
+
+            // a blocked emitter stays full and stops emitting
+            if self.is_region_full(min, max) {
+                continue;
+            }
+            for _ in 0..num_new as usize {
+                if self.num_particles == self.max_particles {
+                    break;
+                }
+                let x = min + extent * Vec2::new(random() as f32, random() as f32);
+                self.add_particle(x, vel);
+            }
+        }
+    }
+
+    fn has_inflow(&self) -> bool {
+        [
+            BoundaryEdge::Left,
+            BoundaryEdge::Right,
+            BoundaryEdge::Bottom,
+            BoundaryEdge::Top,
+        ]
+        .into_iter()
+        .any(|edge| self.grid.boundaries.get(edge).kind == BoundaryKind::Inflow)
+    }
+
+    /// Seeds particles along inflow edges at the rate at which the inflow
+    /// carries fluid into the domain, and keeps the particles in the layer of
+    /// cells next to the edge at the inflow velocity
+    fn emit_inflow(&mut self) {
+        let r = self.particle_radius;
+        let particle_area = 2.0 * f32::sqrt(3.0) * r * r;
+        let h = self.grid.h;
+        let size = Vec2::new(
+            (self.grid.num_cells_x - 2) as f32 * h,
+            (self.grid.num_cells_y - 2) as f32 * h,
+        );
+
+        for edge in [
+            BoundaryEdge::Left,
+            BoundaryEdge::Right,
+            BoundaryEdge::Bottom,
+            BoundaryEdge::Top,
+        ] {
+            let boundary = *self.grid.boundaries.get(edge);
+            if boundary.kind != BoundaryKind::Inflow || boundary.inflow_speed <= 0.0 {
+                continue;
+            }
+            // corner of the layer of cells, normal into the domain and tangent
+            let (min, normal, along) = match edge {
+                BoundaryEdge::Left => (Vec2::splat(h), Vec2::X, Vec2::Y),
+                BoundaryEdge::Right => (Vec2::new(size.x, h), Vec2::NEG_X, Vec2::Y),
+                BoundaryEdge::Bottom => (Vec2::splat(h), Vec2::Y, Vec2::X),
+                BoundaryEdge::Top => (Vec2::new(h, size.y), Vec2::NEG_Y, Vec2::X),
+            };
+            let length = along.dot(size);
+            let max = min + along * length + normal.abs() * h;
+            let vel_at = |t: f32| normal * boundary.inflow_speed_at(t);
+
+            for i in 0..self.num_particles {
+                let x = self.particle_pos[i];
+                if contains(min, max, x) {
+                    self.particle_vel[i] = vel_at((x - min).dot(along) / length);
+                    self.particle_affine[i] = [Vec2::ZERO; 2];
+                }
+            }
+
+            // both profiles carry the same flow rate
+            let accum = &mut self.inflow_accum[edge as usize];
+            *accum += boundary.inflow_speed * length * self.dt / particle_area;
+            let num_new = accum.floor();
+            *accum -= num_new;
+
+            if self.is_region_full(min, max) {
+                continue;
+            }
+            let max_speed = match boundary.inflow_profile {
+                InflowProfile::Uniform => boundary.inflow_speed,
+                InflowProfile::Parabolic => 1.5 * boundary.inflow_speed,
+            };
+            for _ in 0..num_new as usize {
+                if self.num_particles == self.max_particles {
+                    break;
+                }
+                // rejection sampling spreads the particles like the profile
+                let t = loop {
+                    let t = random() as f32;
+                    if random() as f32 * max_speed <= boundary.inflow_speed_at(t) {
+                        break t;
+                    }
+                };
+                let x = min + along * (t * length) + normal.abs() * (h * random() as f32);
+                self.add_particle(x, vel_at(t));
+            }
+        }
+    }
+
+    /// Adds a particle of the default phase unless `x` lies in a solid cell
+    fn add_particle(&mut self, x: Vec2, vel: Vec2) {
+        if self.grid.s[self.pos_to_cell_idx(x, false)] == 0.0 {
+            return;
+        }
+        self.particle_pos.push(x);
+        self.particle_vel.push(vel);
+        self.particle_affine.push([Vec2::ZERO; 2]);
+        self.particle_phase.push(0);
+        self.particle_color.push(PARTICLE_COLOR);
+        self.num_particles += 1;
+    }
+
+    /// Whether the mean particle density of the cells overlapping a rectangle
+    /// has reached the rest density
+    fn is_region_full(&self, min: Vec2, max: Vec2) -> bool {
+        if self.particle_rest_density == 0.0 {
+            return false;
+        }
+        let n = self.grid.num_cells_y;
+        let h1 = self.inv_spacing;
+        let x0 = usize::clamp(
+            f32::floor(min.x * h1) as usize,
+            1,
+            self.grid.num_cells_x - 2,
+        );
+        let x1 = usize::clamp(
+            f32::floor(max.x * h1) as usize,
+            1,
+            self.grid.num_cells_x - 2,
+        );
+        let y0 = usize::clamp(
+            f32::floor(min.y * h1) as usize,
+            1,
+            self.grid.num_cells_y - 2,
+        );
+        let y1 = usize::clamp(
+            f32::floor(max.y * h1) as usize,
+            1,
+            self.grid.num_cells_y - 2,
+        );
+
+        let mut sum = 0.0;
+        for i in x0..=x1 {
+            for j in y0..=y1 {
+                sum += self.particle_density[i * n + j];
+            }
+        }
+        let num_cells = ((x1 - x0 + 1) * (y1 - y0 + 1)) as f32;
+        sum / num_cells >= self.particle_rest_density
+    }
+
+    fn drain_particles(&mut self) {
+        let mut i = 0;
+        while i < self.num_particles {
+            let x = self.particle_pos[i];
+            if self
+                .sinks
+                .iter()
+                .any(|sink| contains(sink.min, sink.max, x))
+            {
+                self.particle_pos.swap_remove(i);
+                self.particle_vel.swap_remove(i);
+                self.particle_affine.swap_remove(i);
+                self.particle_phase.swap_remove(i);
+                self.particle_color.swap_remove(i);
+                self.num_particles -= 1;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
     fn integrate_particles(&mut self) {
         for i in 0..self.num_particles {
             self.particle_vel[i].y += self.dt * self.gravity;
@@ -278,7 +596,11 @@ impl FlipSimulation {
                 self.particle_num_cells_y,
             )
         } else {
-            (self.inv_spacing, self.num_cells_x, self.num_cells_y)
+            (
+                self.inv_spacing,
+                self.grid.num_cells_x,
+                self.grid.num_cells_y,
+            )
         };
 
         let xi = UVec2::clamp(
@@ -289,7 +611,8 @@ impl FlipSimulation {
         xi.x as usize * num_y + xi.y as usize
     }
 
-    fn push_particles_apart(&mut self) {
+    /// Sorts the particles into the cells of the particle grid
+    fn update_particle_grid(&mut self) {
         // count particles per cell
         self.cell_num_particles.fill(0);
         for i in 0..self.num_particles {
@@ -311,7 +634,199 @@ impl FlipSimulation {
             self.cell_first_particle[cell_idx] -= 1;
             self.cell_particle_ids[self.cell_first_particle[cell_idx]] = i;
         }
+    }
 
+    /// Cohesion between particles of the same phase, which keeps small
+    /// droplets together, see Akinci et al., "Versatile Surface Tension and
+    /// Adhesion for SPH Fluids". The curvature part of the surface tension is
+    /// applied by the pressure solve.
+    fn apply_cohesion(&mut self) {
+        let c = COHESION_KERNEL_SCALE * self.particle_radius;
+        let c_sq = c * c;
+        let h = self.grid.h;
+        let particle_area = 2.0 * f32::sqrt(3.0) * self.particle_radius * self.particle_radius;
+        // scaled like the pressure jump across a surface curved on the scale
+        // of a cell, with the kernel normalized to 1 at half its radius
+        let scale = self.surface_tension / (self.density * h * h) * particle_area / (h * h);
+        let kernel_scale = 64.0 / (c_sq * c_sq * c_sq);
+        let range = f32::ceil(c * self.particle_inv_spacing) as usize;
+
+        // fluid at the surface is sparser, and the correction factor of
+        // Akinci et al. strengthens the cohesion acting on it
+        let rel_density = |fluid: &Self, x: Vec2| {
+            if fluid.particle_rest_density > 0.0 {
+                let id = fluid.pos_to_cell_idx(x, false);
+                f32::max(
+                    fluid.particle_density[id] / fluid.particle_rest_density,
+                    0.1,
+                )
+            } else {
+                1.0
+            }
+        };
+
+        for i in 0..self.num_particles {
+            let p = self.particle_pos[i];
+            let phase = self.particle_phase[i];
+            let pxi = f32::floor(p.x * self.particle_inv_spacing) as usize;
+            let pyi = f32::floor(p.y * self.particle_inv_spacing) as usize;
+            let x0 = pxi.saturating_sub(range);
+            let y0 = pyi.saturating_sub(range);
+            let x1 = usize::min(pxi + range, self.particle_num_cells_x - 1);
+            let y1 = usize::min(pyi + range, self.particle_num_cells_y - 1);
+            let density_i = rel_density(self, p);
+
+            let mut accel = Vec2::ZERO;
+            for xi in x0..=x1 {
+                for yi in y0..=y1 {
+                    let cell_idx = xi * self.particle_num_cells_y + yi;
+                    let first = self.cell_first_particle[cell_idx];
+                    let last = self.cell_first_particle[cell_idx + 1];
+                    for j in first..last {
+                        let id = self.cell_particle_ids[j];
+                        if id == i || self.particle_phase[id] != phase {
+                            continue;
+                        }
+                        let q = self.particle_pos[id];
+                        let d = q - p;
+                        let dist_sq = d.length_squared();
+                        if dist_sq > c_sq || dist_sq == 0.0 {
+                            continue;
+                        }
+                        // repulsive up to a quarter of the radius, and most
+                        // attractive at half of it
+                        let r = f32::sqrt(dist_sq);
+                        let cr = (c - r) * (c - r) * (c - r) * r * r * r;
+                        let kernel = if 2.0 * r > c {
+                            cr
+                        } else {
+                            2.0 * cr - c_sq * c_sq * c_sq / 64.0
+                        };
+                        let k = 2.0 / (density_i + rel_density(self, q));
+                        accel += k * kernel_scale * kernel * d / r;
+                    }
+                }
+            }
+            self.particle_accel[i] = scale * accel;
+        }
+
+        self.smooth_particle_accels();
+        for i in 0..self.num_particles {
+            self.particle_vel[i] += self.dt * self.particle_accel[i];
+        }
+    }
+
+    /// Applies the pressure jump of the surface tension across the free
+    /// surface, see Hong and Kim, "Discontinuous Fluids". The air next to the
+    /// fluid takes the pressure `surface_tension * curvature` instead of
+    /// zero, which is known and can be applied to the faces before the solve.
+    fn apply_surface_pressure(&mut self) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let scale = self.surface_tension * self.dt / (self.density * h);
+        self.surface.update_level_set(
+            &self.particle_pos,
+            SURFACE_PARTICLE_RADIUS_SCALE * self.particle_radius,
+            SURFACE_KERNEL_RADIUS_SCALE * self.particle_radius,
+            self.num_surface_smoothing_iters,
+        );
+        // features smaller than a cell are not resolved
+        let curvature = |fluid: &Self, i: usize, j: usize| {
+            let x = Vec2::new(i as f32 + 0.5, j as f32 + 0.5) * h;
+            fluid
+                .surface
+                .curvature(x)
+                .clamp(-fluid.inv_spacing, fluid.inv_spacing)
+        };
+
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                let center = i * n + j;
+                if self.cell_kind[center] != CellKind::Fluid {
+                    continue;
+                }
+                let kappa = curvature(self, i, j);
+                let neighbors = [
+                    (i - 1, j, center, 1.0),
+                    (i + 1, j, center + n, -1.0),
+                    (i, j - 1, center, 1.0),
+                    (i, j + 1, center + 1, -1.0),
+                ];
+                for (k, (ni, nj, face, sign)) in neighbors.into_iter().enumerate() {
+                    if self.cell_kind[ni * n + nj] != CellKind::Air {
+                        continue;
+                    }
+                    let p = scale * 0.5 * (kappa + curvature(self, ni, nj));
+                    if k < 2 {
+                        self.grid.u[face] += sign * self.inv_density_u[face] * p;
+                    } else {
+                        self.grid.v[face] += sign * self.inv_density_v[face] * p;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Averages the particle accelerations on the grid and interpolates them
+    /// back, as FLIP would otherwise keep their noise on the particle scale.
+    /// The face arrays of the velocity transfer serve as scratch space, which
+    /// is free before the particles are transferred to the grid.
+    fn smooth_particle_accels(&mut self) {
+        let h = self.grid.h;
+        let h1 = self.inv_spacing;
+        let h2 = 0.5 * h;
+        let n = self.grid.num_cells_y;
+        let min = Vec2::splat(h);
+        let max = Vec2::new(
+            (self.grid.num_cells_x - 1) as f32 * h,
+            (self.grid.num_cells_y - 1) as f32 * h,
+        );
+        let max_cell = UVec2::new(
+            self.grid.num_cells_x as u32 - 2,
+            self.grid.num_cells_y as u32 - 2,
+        );
+
+        for component in 0..=1 {
+            let (offset, f, w) = if component == 0 {
+                (Vec2::new(0.0, h2), &mut self.prev_u, &mut self.du)
+            } else {
+                (Vec2::new(h2, 0.0), &mut self.prev_v, &mut self.dv)
+            };
+            let stencil = |x: Vec2| {
+                let x = x.clamp(min, max) - offset;
+                let x0 = UVec2::min(Vec2::floor(x * h1).as_uvec2(), max_cell);
+                let t = (x - x0.as_vec2() * h) * h1;
+                let x1 = UVec2::min(x0 + 1, max_cell);
+                let s = 1.0 - t;
+                [
+                    (x0.x as usize * n + x0.y as usize, s.x * s.y),
+                    (x1.x as usize * n + x0.y as usize, t.x * s.y),
+                    (x1.x as usize * n + x1.y as usize, t.x * t.y),
+                    (x0.x as usize * n + x1.y as usize, s.x * t.y),
+                ]
+            };
+
+            f.fill(0.0);
+            w.fill(0.0);
+            for i in 0..self.num_particles {
+                for (id, weight) in stencil(self.particle_pos[i]) {
+                    f[id] += weight * self.particle_accel[i][component];
+                    w[id] += weight;
+                }
+            }
+            for i in 0..self.num_particles {
+                let mut sum = 0.0;
+                for (id, weight) in stencil(self.particle_pos[i]) {
+                    if w[id] > 0.0 {
+                        sum += weight * f[id] / w[id];
+                    }
+                }
+                self.particle_accel[i][component] = sum;
+            }
+        }
+    }
+
+    fn push_particles_apart(&mut self) {
         // push particles apart
         let min_dist = 2.0 * self.particle_radius;
         let min_dist_sq = min_dist * min_dist;
@@ -342,10 +857,13 @@ impl FlipSimulation {
                                 continue;
                             }
                             let dist = f32::sqrt(dist_sq);
-                            let s = 0.5 * (min_dist - dist) / dist;
-                            d *= s;
-                            self.particle_pos[i] -= d;
-                            self.particle_pos[id] += d;
+                            d *= (min_dist - dist) / dist;
+                            // the lighter particle moves further
+                            let mass_i = self.phases[self.particle_phase[i]].relative_density;
+                            let mass_id = self.phases[self.particle_phase[id]].relative_density;
+                            let w = mass_id / (mass_i + mass_id);
+                            self.particle_pos[i] -= w * d;
+                            self.particle_pos[id] += (1.0 - w) * d;
 
                             // diffuse colors
                             for k in 0..3 {
@@ -365,15 +883,22 @@ impl FlipSimulation {
     }
 
     fn handle_particle_collisions(&mut self) {
-        let h = self.h;
+        let h = self.grid.h;
         let r = self.particle_radius;
         let min_dist = self.obstacle_radius + r;
         let min_dist_sq = min_dist * min_dist;
 
         let min_x = h + r;
-        let max_x = (self.num_cells_x as f32 - 1.0) * h - r;
+        let max_x = (self.grid.num_cells_x as f32 - 1.0) * h - r;
         let min_y = h + r;
-        let max_y = (self.num_cells_y as f32 - 1.0) * h - r;
+        let max_y = (self.grid.num_cells_y as f32 - 1.0) * h - r;
+        let no_slip = |edge| self.grid.boundaries.get(edge).kind == BoundaryKind::NoSlip;
+        let no_slip_left = no_slip(BoundaryEdge::Left);
+        let no_slip_right = no_slip(BoundaryEdge::Right);
+        let no_slip_bottom = no_slip(BoundaryEdge::Bottom);
+        let no_slip_top = no_slip(BoundaryEdge::Top);
+        let periodic_x = self.grid.boundaries.is_periodic_x();
+        let periodic_y = self.grid.boundaries.is_periodic_y();
 
         for i in 0..self.num_particles {
             let mut x = self.particle_pos[i];
@@ -385,22 +910,52 @@ impl FlipSimulation {
                 self.particle_vel[i] = self.obstacle_vel;
             }
 
-            // wall collisions
-            if x.x < min_x {
+            // rigid body collisions, remove the velocity into the body
+            for body in &self.bodies {
+                let (d, normal) = body.distance(x);
+                if d < r {
+                    x += (r - d) * normal;
+                    let vn = (self.particle_vel[i] - body.point_vel(x)).dot(normal);
+                    if vn < 0.0 {
+                        self.particle_vel[i] -= vn * normal;
+                    }
+                }
+            }
+
+            // particles leaving through a periodic edge enter at the opposite one
+            if periodic_x || periodic_y {
+                let (px, py) = self.grid.boundaries.wrap(x.x, x.y, h);
+                x = Vec2::new(px, py);
+            }
+
+            // wall collisions, no-slip walls also stop the tangential motion
+            if !periodic_x && x.x < min_x {
                 x.x = min_x;
                 self.particle_vel[i].x = 0.0;
+                if no_slip_left {
+                    self.particle_vel[i].y = 0.0;
+                }
             }
-            if x.x > max_x {
+            if !periodic_x && x.x > max_x {
                 x.x = max_x;
                 self.particle_vel[i].x = 0.0;
+                if no_slip_right {
+                    self.particle_vel[i].y = 0.0;
+                }
             }
-            if x.y < min_y {
+            if !periodic_y && x.y < min_y {
                 x.y = min_y;
                 self.particle_vel[i].y = 0.0;
+                if no_slip_bottom {
+                    self.particle_vel[i].x = 0.0;
+                }
             }
-            if x.y > max_y {
+            if !periodic_y && x.y > max_y {
                 x.y = max_y;
                 self.particle_vel[i].y = 0.0;
+                if no_slip_top {
+                    self.particle_vel[i].x = 0.0;
+                }
             }
             self.particle_pos[i] = x;
         }
@@ -408,24 +963,27 @@ impl FlipSimulation {
 
     #[allow(clippy::too_many_lines)]
     fn transfer_velocities(&mut self, to_grid: bool) {
-        let n = self.num_cells_y;
-        let h = self.h;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
         let h1 = self.inv_spacing;
         let h2 = 0.5 * h;
 
-        let nx = self.num_cells_x as f32;
-        let ny = self.num_cells_y as f32;
+        let nx = self.grid.num_cells_x as f32;
+        let ny = self.grid.num_cells_y as f32;
+        // across periodic edges, particles also reach the ghost nodes
+        let max_x = self.grid.num_cells_x - usize::from(!self.grid.boundaries.is_periodic_x()) - 1;
+        let max_y = self.grid.num_cells_y - usize::from(!self.grid.boundaries.is_periodic_y()) - 1;
 
         if to_grid {
-            self.prev_u.copy_from_slice(&self.u);
-            self.prev_v.copy_from_slice(&self.v);
+            self.prev_u.copy_from_slice(&self.grid.u);
+            self.prev_v.copy_from_slice(&self.grid.v);
             self.du.fill(0.0);
             self.dv.fill(0.0);
-            self.u.fill(0.0);
-            self.v.fill(0.0);
+            self.grid.u.fill(0.0);
+            self.grid.v.fill(0.0);
 
-            for i in 0..self.num_cells {
-                self.cell_kind[i] = if self.s[i] == 0.0 {
+            for i in 0..self.grid.num_cells {
+                self.cell_kind[i] = if self.grid.s[i] == 0.0 {
                     CellKind::Solid
                 } else {
                     CellKind::Air
@@ -438,27 +996,55 @@ impl FlipSimulation {
                     self.cell_kind[cell_idx] = CellKind::Fluid;
                 }
             }
+            self.grid.boundaries.apply_scalar(&mut self.cell_kind);
         }
 
         for component in 0..=1 {
-            let (dx, dy, f, prev_f, d) = if component == 0 {
-                (0.0, h2, &mut self.u, &mut self.prev_u, &mut self.du)
+            let (dx, dy, f, prev_f, d, inv_density) = if component == 0 {
+                (
+                    0.0,
+                    h2,
+                    &mut self.grid.u,
+                    &mut self.prev_u,
+                    &mut self.du,
+                    &mut self.inv_density_u,
+                )
             } else {
-                (h2, 0.0, &mut self.v, &mut self.prev_v, &mut self.dv)
+                (
+                    h2,
+                    0.0,
+                    &mut self.grid.v,
+                    &mut self.prev_v,
+                    &mut self.dv,
+                    &mut self.inv_density_v,
+                )
             };
+            if to_grid {
+                inv_density.fill(0.0);
+            }
 
             for i in 0..self.num_particles {
                 let p = self.particle_pos[i];
                 let x = f32::clamp(p.x, h, (nx - 1.0) * h);
                 let y = f32::clamp(p.y, h, (ny - 1.0) * h);
 
-                let x0 = usize::min(f32::floor((x - dx) * h1) as usize, self.num_cells_x - 2);
+                // particles wrapped onto the first face must not round into
+                // the ghost cells
+                let x0 = usize::clamp(
+                    f32::floor((x - dx) * h1) as usize,
+                    usize::from(component == 0),
+                    self.grid.num_cells_x - 2,
+                );
                 let tx = ((x - dx) - x0 as f32 * h) * h1;
-                let x1 = usize::min(x0 + 1, self.num_cells_x - 2);
+                let x1 = usize::min(x0 + 1, max_x);
 
-                let y0 = usize::min(f32::floor((y - dy) * h1) as usize, self.num_cells_y - 2);
+                let y0 = usize::clamp(
+                    f32::floor((y - dy) * h1) as usize,
+                    usize::from(component == 1),
+                    self.grid.num_cells_y - 2,
+                );
                 let ty = ((y - dy) - y0 as f32 * h) * h1;
-                let y1 = usize::min(y0 + 1, self.num_cells_y - 2);
+                let y1 = usize::min(y0 + 1, max_y);
 
                 let sx = 1.0 - tx;
                 let sy = 1.0 - ty;
@@ -473,16 +1059,36 @@ impl FlipSimulation {
                 let nr2 = x1 * n + y1;
                 let nr3 = x0 * n + y1;
 
+                // node positions relative to the particle
+                let node = |xi: usize, yi: usize| {
+                    Vec2::new(xi as f32 * h + dx - x, yi as f32 * h + dy - y)
+                };
+
                 if to_grid {
+                    // transfer momentum, weighting by the particle mass
+                    // relative to the default fluid
+                    let m = self.phases[self.particle_phase[i]].relative_density;
                     let pv = self.particle_vel[i][component];
-                    f[nr0] += pv * d0;
-                    d[nr0] += d0;
-                    f[nr1] += pv * d1;
-                    d[nr1] += d1;
-                    f[nr2] += pv * d2;
-                    d[nr2] += d2;
-                    f[nr3] += pv * d3;
-                    d[nr3] += d3;
+                    if self.transfer_scheme == TransferScheme::Apic {
+                        let c = self.particle_affine[i][component];
+                        f[nr0] += m * (pv + c.dot(node(x0, y0))) * d0;
+                        f[nr1] += m * (pv + c.dot(node(x1, y0))) * d1;
+                        f[nr2] += m * (pv + c.dot(node(x1, y1))) * d2;
+                        f[nr3] += m * (pv + c.dot(node(x0, y1))) * d3;
+                    } else {
+                        f[nr0] += m * pv * d0;
+                        f[nr1] += m * pv * d1;
+                        f[nr2] += m * pv * d2;
+                        f[nr3] += m * pv * d3;
+                    }
+                    d[nr0] += m * d0;
+                    d[nr1] += m * d1;
+                    d[nr2] += m * d2;
+                    d[nr3] += m * d3;
+                    inv_density[nr0] += d0;
+                    inv_density[nr1] += d1;
+                    inv_density[nr2] += d2;
+                    inv_density[nr3] += d3;
                 } else {
                     let offset = if component == 0 { n } else { 1 };
 
@@ -498,7 +1104,24 @@ impl FlipSimulation {
                     let v = self.particle_vel[i][component];
                     let d = valid0 * d0 + valid1 * d1 + valid2 * d2 + valid3 * d3;
 
-                    if d > 0.0 {
+                    if d > 0.0 && self.transfer_scheme == TransferScheme::Apic {
+                        let pic_v = (valid0 * d0 * f[nr0]
+                            + valid1 * d1 * f[nr1]
+                            + valid2 * d2 * f[nr2]
+                            + valid3 * d3 * f[nr3])
+                            / d;
+                        // gradients of the bilinear weights with respect to
+                        // the particle position, invalid nodes take the
+                        // particle velocity so that they do not contribute
+                        let value = |valid: f32, f: f32| valid * f + (1.0 - valid) * pic_v;
+                        let c = Vec2::new(-sy, -sx) * h1 * value(valid0, f[nr0])
+                            + Vec2::new(sy, -tx) * h1 * value(valid1, f[nr1])
+                            + Vec2::new(ty, tx) * h1 * value(valid2, f[nr2])
+                            + Vec2::new(-ty, sx) * h1 * value(valid3, f[nr3]);
+
+                        self.particle_vel[i][component] = pic_v;
+                        self.particle_affine[i][component] = c;
+                    } else if d > 0.0 {
                         let pic_v = (valid0 * d0 * f[nr0]
                             + valid1 * d1 * f[nr1]
                             + valid2 * d2 * f[nr2]
@@ -518,34 +1141,55 @@ impl FlipSimulation {
             }
 
             if to_grid {
+                self.grid.boundaries.fold_periodic(f);
+                self.grid.boundaries.fold_periodic(d);
+                self.grid.boundaries.fold_periodic(inv_density);
+
+                // the face density is the mean of the particle densities,
+                // faces without particles take the default density
                 for i in 0..f.len() {
                     if d[i] > 0.0 {
                         f[i] /= d[i];
+                        inv_density[i] /= d[i];
+                    } else {
+                        inv_density[i] = 1.0;
                     }
                 }
 
                 // restore solid cells
-                for i in 0..self.num_cells_x {
-                    for j in 0..self.num_cells_y {
+                for i in 0..self.grid.num_cells_x {
+                    for j in 0..self.grid.num_cells_y {
                         let ind = i * n + j;
                         let solid = self.cell_kind[ind] == CellKind::Solid;
                         if solid || (i > 0 && self.cell_kind[(i - 1) * n + j] == CellKind::Solid) {
-                            self.u[ind] = self.prev_u[ind];
+                            self.grid.u[ind] = self.prev_u[ind];
                         }
                         if solid || (j > 0 && self.cell_kind[i * n + j - 1] == CellKind::Solid) {
-                            self.v[ind] = self.prev_v[ind];
+                            self.grid.v[ind] = self.prev_v[ind];
                         }
                     }
                 }
             }
         }
+
+        if to_grid {
+            // prescribe the inflow faces and fill the ghost faces
+            self.grid
+                .boundaries
+                .apply_velocity(&mut self.grid.u, &mut self.grid.v);
+        }
     }
 
     fn update_particle_density(&mut self) {
-        let n = self.num_cells_y;
-        let h = self.h;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
         let h1 = self.inv_spacing;
         let h2 = 0.5 * h;
+        // across periodic edges, particles also reach the ghost cells
+        let max_cell = UVec2::new(
+            (self.grid.num_cells_x - usize::from(!self.grid.boundaries.is_periodic_x()) - 1) as u32,
+            (self.grid.num_cells_y - usize::from(!self.grid.boundaries.is_periodic_y()) - 1) as u32,
+        );
         let d = &mut self.particle_density;
 
         d.fill(0.0);
@@ -555,41 +1199,40 @@ impl FlipSimulation {
                 x,
                 Vec2::splat(h),
                 Vec2::new(
-                    (self.num_cells_x as f32 - 1.0) * h,
-                    (self.num_cells_y as f32 - 1.0) * h,
+                    (self.grid.num_cells_x as f32 - 1.0) * h,
+                    (self.grid.num_cells_y as f32 - 1.0) * h,
                 ),
             );
             let x0 = Vec2::floor((x - h2) * h1);
             let t = ((x - h2) - x0 * h) * h1;
             let x0 = x0.as_uvec2();
-            let x1 = UVec2::min(
-                x0 + 1,
-                UVec2::new(self.num_cells_x as u32 - 2, self.num_cells_y as u32 - 2),
-            );
+            let x1 = UVec2::min(x0 + 1, max_cell);
             let s = 1.0 - t;
 
             let y0 = x0.y as usize;
             let x0 = x0.x as usize;
             let y1 = x1.y as usize;
             let x1 = x1.x as usize;
-            if x0 < self.num_cells_x && y0 < self.num_cells_y {
+            if x0 < self.grid.num_cells_x && y0 < self.grid.num_cells_y {
                 d[x0 * n + y0] += s.x * s.y;
             };
-            if x1 < self.num_cells_x && y0 < self.num_cells_y {
+            if x1 < self.grid.num_cells_x && y0 < self.grid.num_cells_y {
                 d[x1 * n + y0] += t.x * s.y;
             };
-            if x1 < self.num_cells_x && y1 < self.num_cells_y {
+            if x1 < self.grid.num_cells_x && y1 < self.grid.num_cells_y {
                 d[x1 * n + y1] += t.x * t.y;
             };
-            if x0 < self.num_cells_x && y1 < self.num_cells_y {
+            if x0 < self.grid.num_cells_x && y1 < self.grid.num_cells_y {
                 d[x0 * n + y1] += s.x * t.y;
             };
         }
+        self.grid.boundaries.fold_periodic(d);
+        self.grid.boundaries.apply_scalar(d);
 
         if self.particle_rest_density == 0.0 {
             let mut sum = 0.0;
             let mut num_fluid_cells = 0.0;
-            for (i, id) in d.iter().enumerate().take(self.num_cells) {
+            for (i, id) in d.iter().enumerate().take(self.grid.num_cells) {
                 if self.cell_kind[i] == CellKind::Fluid {
                     sum += id;
                     num_fluid_cells += 1.0;
@@ -603,77 +1246,65 @@ impl FlipSimulation {
     }
 
     fn solve_incompressibility(&mut self) {
-        self.p.fill(0.0);
-        self.prev_u.clone_from_slice(&self.u);
-        self.prev_v.clone_from_slice(&self.v);
-
-        let n = self.num_cells_y;
-        let cp = self.density * self.h / self.dt;
-
-        for _ in 0..self.num_pressure_iters {
-            for i in 1..self.num_cells_x - 1 {
-                for j in 1..self.num_cells_y - 1 {
-                    if self.cell_kind[i * n + j] != CellKind::Fluid {
-                        continue;
-                    }
-                    let center = i * n + j;
-                    let left = (i - 1) * n + j;
-                    let right = (i + 1) * n + j;
-                    let bottom = i * n + j - 1;
-                    let top = i * n + j + 1;
-
-                    let sx0 = self.s[left];
-                    let sx1 = self.s[right];
-                    let sy0 = self.s[bottom];
-                    let sy1 = self.s[top];
-                    let s = sx0 + sx1 + sy0 + sy1;
-
-                    if s == 0.0 {
-                        continue;
-                    }
-
-                    let mut div = self.u[right] - self.u[center] + self.v[top] - self.v[center];
-                    if self.particle_rest_density > 0.0 && self.compensate_drift {
-                        let k = 1.0;
-                        let compression =
-                            self.particle_density[i * n + j] - self.particle_rest_density;
-                        if compression > 0.0 {
-                            div -= k * compression;
-                        }
-                    }
-
-                    let p = -div / s * self.over_relaxation;
-                    self.p[center] += cp * p;
+        self.grid.p.fill(0.0);
+        self.prev_u.clone_from_slice(&self.grid.u);
+        self.prev_v.clone_from_slice(&self.grid.v);
+        if self.surface_tension > 0.0 {
+            self.apply_surface_pressure();
+        }
 
-                    self.u[center] -= sx0 * p;
-                    self.u[right] += sx1 * p;
-                    self.v[center] -= sy0 * p;
-                    self.v[top] += sy1 * p;
+        let cell_kind = &self.cell_kind;
+        let particle_density = &self.particle_density;
+        let rest_density = self.particle_rest_density;
+        let compensate_drift = self.compensate_drift && rest_density > 0.0;
+        self.grid.solve_incompressibility(
+            self.pressure_solver,
+            self.num_pressure_iters,
+            self.over_relaxation,
+            Some((&self.inv_density_u, &self.inv_density_v)),
+            |id| cell_kind[id] == CellKind::Fluid,
+            // drift compensation, push particles out of overly dense cells
+            |id| {
+                let compression = particle_density[id] - rest_density;
+                if compensate_drift && compression > 0.0 {
+                    compression
+                } else {
+                    0.0
                 }
-            }
+            },
+        );
+        let cp = self.density * self.grid.h / self.dt;
+        for p in &mut self.grid.p {
+            *p *= cp;
         }
+        // the ghost faces copy the corrected faces
+        self.grid
+            .boundaries
+            .apply_velocity(&mut self.grid.u, &mut self.grid.v);
     }
 
     fn update_particle_colors(&mut self) {
         let h1 = self.inv_spacing;
         for i in 0..self.num_particles {
-            let mut s = 0.01;
-            self.particle_color[i] = Vec3::clamp(
-                self.particle_color[i] + Vec3::new(-s, -s, s),
-                Vec3::splat(0.0),
-                Vec3::splat(1.0),
+            // fade towards the color of the phase
+            let s = 0.01;
+            let color = self.phases[self.particle_phase[i]].color;
+            let fade = Vec3::clamp(
+                color - self.particle_color[i],
+                Vec3::splat(-s),
+                Vec3::splat(s),
             );
+            self.particle_color[i] += fade;
             let x = self.particle_pos[i];
-            let xi = usize::clamp(f32::floor(x.x * h1) as usize, 1, self.num_cells_x - 1);
-            let yi = usize::clamp(f32::floor(x.y * h1) as usize, 1, self.num_cells_y - 1);
-            let cell_idx = xi * self.num_cells_y + yi;
+            let xi = usize::clamp(f32::floor(x.x * h1) as usize, 1, self.grid.num_cells_x - 1);
+            let yi = usize::clamp(f32::floor(x.y * h1) as usize, 1, self.grid.num_cells_y - 1);
+            let cell_idx = xi * self.grid.num_cells_y + yi;
 
             let d0 = self.particle_rest_density;
             if d0 > 0.0 {
                 let rel_density = self.particle_density[cell_idx] / d0;
                 if rel_density < 0.7 {
-                    s = 0.8;
-                    self.particle_color[i] = Vec3::new(s, s, 1.0);
+                    self.particle_color[i] = color.lerp(Vec3::ONE, 0.8);
                 }
             }
         }
@@ -682,9 +1313,13 @@ impl FlipSimulation {
     fn update_cell_colors(&mut self) {
         self.cell_color.iter_mut().for_each(|c| *c = Vec3::ZERO);
 
-        for i in 0..self.num_cells {
+        for i in 0..self.grid.num_cells {
             if self.cell_kind[i] == CellKind::Solid {
                 self.cell_color[i] = Vec3::splat(0.5);
+            } else if self.cell_kind[i] == CellKind::Fluid && self.show_divergence {
+                let div_max = self.diagnostics.max_divergence;
+                let div = self.diagnostics.div[i];
+                self.cell_color[i] = get_sci_color(div, -div_max, div_max).into();
             } else if self.cell_kind[i] == CellKind::Fluid {
                 let mut d = self.particle_density[i];
                 if self.particle_rest_density > 0.0 {
@@ -695,12 +1330,32 @@ impl FlipSimulation {
         }
     }
 
-    #[allow(clippy::too_many_lines)]
-    pub fn draw(&mut self) {
+    /// Updates the colors and meshes shared by `draw` and `draw_raster`
+    fn update_render_data(&mut self) {
         self.update_particle_colors();
         self.update_cell_colors();
+        if self.show_surface {
+            self.surface.update(
+                &self.particle_pos,
+                SURFACE_PARTICLE_RADIUS_SCALE * self.particle_radius,
+                SURFACE_KERNEL_RADIUS_SCALE * self.particle_radius,
+                self.num_surface_smoothing_iters,
+            );
+        }
+        self.body_triangles.clear();
+        self.body_lines.clear();
+        for body in &self.bodies {
+            body.append_mesh(&mut self.body_triangles, &mut self.body_lines);
+        }
+    }
 
-        let gl = &mut self.renderer.context;
+    #[allow(clippy::too_many_lines)]
+    pub fn draw(&mut self) {
+        self.update_render_data();
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        let gl = &renderer.context;
 
         gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
 
@@ -708,32 +1363,32 @@ impl FlipSimulation {
 
         // draw colored grid
         if self.show_grid {
-            gl.use_program(Some(&self.renderer.particle_program));
+            gl.use_program(Some(&renderer.particle_program));
 
             // set uniforms
-            let point_size = GRID_POINT_SIZE_SCALE * self.h / sim_width * self.width;
-            gl.uniform1f(Some(&self.renderer.particle_point_size_uniform), point_size);
+            let point_size = GRID_POINT_SIZE_SCALE * self.grid.h / sim_width * self.width;
+            gl.uniform1f(Some(&renderer.particle_point_size_uniform), point_size);
             gl.uniform2f(
-                Some(&self.renderer.particle_domain_size_uniform),
+                Some(&renderer.particle_domain_size_uniform),
                 sim_width,
                 SIM_HEIGHT,
             );
-            gl.uniform1i(Some(&self.renderer.particle_mode_draw_disk_uniform), 0);
+            gl.uniform1i(Some(&renderer.particle_mode_draw_disk_uniform), 0);
 
             // set position buffer
             set_buffers_and_attributes(
                 gl,
-                &self.renderer.grid_buffer,
+                &renderer.grid_buffer,
                 2,
-                self.renderer.particle_position_attrib_location,
+                renderer.particle_position_attrib_location,
             );
 
             // set color buffer
             set_buffers_and_attributes(
                 gl,
-                &self.renderer.grid_color_buffer,
+                &renderer.grid_color_buffer,
                 3,
-                self.renderer.particle_color_attrib_location,
+                renderer.particle_color_attrib_location,
             );
             unsafe {
                 // Note that `Float32Array::view` is somewhat dangerous (hence the
@@ -747,7 +1402,7 @@ impl FlipSimulation {
                 let colors_f32_view = self.cell_color.as_ptr().cast::<f32>(); // &[Vec3] -> *const Vec3 -> *const f32
                 let colors_array_buf_view = js_sys::Float32Array::view(std::slice::from_raw_parts(
                     colors_f32_view,
-                    self.num_cells * 3,
+                    self.grid.num_cells * 3,
                 ));
                 gl.buffer_sub_data_with_i32_and_array_buffer_view(
                     WebGl2RenderingContext::ARRAY_BUFFER,
@@ -757,36 +1412,52 @@ impl FlipSimulation {
             }
 
             // draw
-            gl.draw_arrays(WebGl2RenderingContext::POINTS, 0, self.num_cells as i32);
+            gl.draw_arrays(
+                WebGl2RenderingContext::POINTS,
+                0,
+                self.grid.num_cells as i32,
+            );
 
             // cleanup
-            gl.disable_vertex_attrib_array(self.renderer.particle_position_attrib_location);
-            gl.disable_vertex_attrib_array(self.renderer.particle_color_attrib_location);
+            gl.disable_vertex_attrib_array(renderer.particle_position_attrib_location);
+            gl.disable_vertex_attrib_array(renderer.particle_color_attrib_location);
             gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
         }
 
+        // draw liquid surface
+        if self.show_surface {
+            gl.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+            renderer.draw_mesh(
+                &self.surface.triangles,
+                &self.surface.lines,
+                SURFACE_COLOR,
+                SURFACE_OUTLINE_COLOR,
+                sim_width,
+            );
+        }
+
         // draw water particles
         if self.show_particles {
             gl.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
-            gl.use_program(Some(&self.renderer.particle_program));
+            gl.use_program(Some(&renderer.particle_program));
 
             // set uniforms
             let point_size =
                 PARTICLE_POINT_SIZE_SCALE * self.particle_radius / sim_width * self.width;
-            gl.uniform1f(Some(&self.renderer.particle_point_size_uniform), point_size);
+            gl.uniform1f(Some(&renderer.particle_point_size_uniform), point_size);
             gl.uniform2f(
-                Some(&self.renderer.particle_domain_size_uniform),
+                Some(&renderer.particle_domain_size_uniform),
                 sim_width,
                 SIM_HEIGHT,
             );
-            gl.uniform1i(Some(&self.renderer.particle_mode_draw_disk_uniform), 1);
+            gl.uniform1i(Some(&renderer.particle_mode_draw_disk_uniform), 1);
 
             // set position buffer
             set_buffers_and_attributes(
                 gl,
-                &self.renderer.particle_buffer,
+                &renderer.particle_buffer,
                 2,
-                self.renderer.particle_position_attrib_location,
+                renderer.particle_position_attrib_location,
             );
             unsafe {
                 // See comment above for safety
@@ -804,9 +1475,9 @@ impl FlipSimulation {
             // set color buffer
             set_buffers_and_attributes(
                 gl,
-                &self.renderer.particle_color_buffer,
+                &renderer.particle_color_buffer,
                 3,
-                self.renderer.particle_color_attrib_location,
+                renderer.particle_color_attrib_location,
             );
             unsafe {
                 // See comment above for safety
@@ -826,42 +1497,53 @@ impl FlipSimulation {
             gl.draw_arrays(WebGl2RenderingContext::POINTS, 0, self.num_particles as i32);
 
             // cleanup
-            gl.disable_vertex_attrib_array(self.renderer.particle_position_attrib_location);
-            gl.disable_vertex_attrib_array(self.renderer.particle_color_attrib_location);
+            gl.disable_vertex_attrib_array(renderer.particle_position_attrib_location);
+            gl.disable_vertex_attrib_array(renderer.particle_color_attrib_location);
             gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
         }
 
+        // draw rigid bodies
+        if !self.bodies.is_empty() {
+            renderer.draw_mesh(
+                &self.body_triangles,
+                &self.body_lines,
+                BODY_COLOR,
+                BODY_OUTLINE_COLOR,
+                sim_width,
+            );
+        }
+
         // draw obstacle disk
         if self.show_obstacle {
             gl.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
-            gl.use_program(Some(&self.renderer.mesh_program));
+            gl.use_program(Some(&renderer.mesh_program));
             gl.uniform2f(
-                Some(&self.renderer.mesh_domain_size_uniform),
+                Some(&renderer.mesh_domain_size_uniform),
                 sim_width,
                 SIM_HEIGHT,
             );
             gl.uniform3fv_with_f32_array(
-                Some(&self.renderer.mesh_color_uniform),
+                Some(&renderer.mesh_color_uniform),
                 &OBSTALCE_COLOR.to_array(),
             );
             gl.uniform2fv_with_f32_array(
-                Some(&self.renderer.mesh_translation_uniform),
+                Some(&renderer.mesh_translation_uniform),
                 &self.obstacle_pos.to_array(),
             );
             gl.uniform1f(
-                Some(&self.renderer.mesh_scale_uniform),
+                Some(&renderer.mesh_scale_uniform),
                 self.obstacle_radius + self.particle_radius,
             );
 
             set_buffers_and_attributes(
                 gl,
-                &self.renderer.disk_buffer,
+                &renderer.disk_buffer,
                 2,
-                self.renderer.mesh_position_attrib_location,
+                renderer.mesh_position_attrib_location,
             );
             gl.bind_buffer(
                 WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
-                Some(&self.renderer.disk_id_buffer),
+                Some(&renderer.disk_id_buffer),
             );
             gl.draw_elements_with_i32(
                 WebGl2RenderingContext::TRIANGLES,
@@ -870,13 +1552,83 @@ impl FlipSimulation {
                 0,
             );
 
-            gl.disable_vertex_attrib_array(self.renderer.mesh_position_attrib_location);
+            gl.disable_vertex_attrib_array(renderer.mesh_position_attrib_location);
         }
     }
 
+    /// Draws the same frame as `draw` into an RGBA buffer without WebGL, cells
+    /// as squares and particles as disks
+    pub fn draw_raster(&mut self, render_buffer: &mut [u8]) {
+        self.update_render_data();
+        let mut raster = Raster::new(self.width as usize, self.height as usize, render_buffer);
+        raster.clear(Vec3::ZERO);
+        let c_scale = self.c_scale;
+        let height = self.height;
+        let c = |p: Vec2| Vec2::new(p.x * c_scale, height - p.y * c_scale);
+        let to_canvas = |verts: &[Vec2]| verts.iter().map(|&p| c(p)).collect::<Vec<Vec2>>();
+
+        if self.show_grid {
+            let h = self.grid.h;
+            let half_size = Vec2::splat(0.5 * GRID_POINT_SIZE_SCALE * h * c_scale);
+            for i in 0..self.grid.num_cells_x {
+                for j in 0..self.grid.num_cells_y {
+                    let center = c(Vec2::new((i as f32 + 0.5) * h, (j as f32 + 0.5) * h));
+                    let color = self.cell_color[i * self.grid.num_cells_y + j];
+                    raster.fill_rect(center - half_size, center + half_size, color, 1.0);
+                }
+            }
+        }
+
+        if self.show_surface {
+            raster.fill_triangles(&to_canvas(&self.surface.triangles), SURFACE_COLOR);
+            raster.draw_lines(&to_canvas(&self.surface.lines), 1.0, SURFACE_OUTLINE_COLOR);
+        }
+
+        if self.show_particles {
+            let r = 0.5 * PARTICLE_POINT_SIZE_SCALE * self.particle_radius * c_scale;
+            for i in 0..self.num_particles {
+                raster.fill_disk(c(self.particle_pos[i]), r, self.particle_color[i]);
+            }
+        }
+
+        if !self.bodies.is_empty() {
+            raster.fill_triangles(&to_canvas(&self.body_triangles), BODY_COLOR);
+            raster.draw_lines(&to_canvas(&self.body_lines), 1.0, BODY_OUTLINE_COLOR);
+        }
+
+        if self.show_obstacle {
+            let r = (self.obstacle_radius + self.particle_radius) * c_scale;
+            raster.fill_disk(c(self.obstacle_pos), r, OBSTALCE_COLOR);
+        }
+    }
+
+    /// Draws the frame like `draw_raster` and encodes it as PNG
+    pub fn draw_png(&mut self) -> Vec<u8> {
+        render_png(self.width as usize, self.height as usize, |buffer| {
+            self.draw_raster(buffer);
+        })
+    }
+
     pub fn step(&mut self) {
         for _ in 0..self.num_substeps {
+            if !self.emitters.is_empty() || !self.sinks.is_empty() || self.has_inflow() {
+                self.emit_particles();
+                self.emit_inflow();
+                self.drain_particles();
+                self.cell_particle_ids.resize(self.num_particles, 0);
+            }
             self.integrate_particles();
+            if !self.bodies.is_empty() {
+                self.integrate_bodies();
+                self.update_solid_cells();
+            }
+            if self.separate_particles || self.surface_tension > 0.0 {
+                self.update_particle_grid();
+            }
+            if self.surface_tension > 0.0 {
+                self.particle_accel.resize(self.num_particles, Vec2::ZERO);
+                self.apply_cohesion();
+            }
             if self.separate_particles {
                 self.push_particles_apart();
             }
@@ -884,8 +1636,32 @@ impl FlipSimulation {
             self.transfer_velocities(true);
             self.update_particle_density();
             self.solve_incompressibility();
+            self.apply_pressure_to_bodies();
+            self.diagnostics
+                .update_divergence(&self.grid.u, &self.grid.v, |id| {
+                    self.cell_kind[id] == CellKind::Fluid
+                });
             self.transfer_velocities(false);
         }
+
+        self.diagnostics
+            .update_kinetic_energy(self.density, &self.grid.u, &self.grid.v, |id| {
+                self.cell_kind[id] == CellKind::Fluid
+            });
+        // mass of the fluid cells, which exposes volume loss. The cell density
+        // is the mean of the phase densities the P2G transfer gave its faces
+        let n = self.grid.num_cells_y;
+        self.diagnostics.update_total_mass(
+            |id| {
+                let relative_density = 0.25
+                    * (1.0 / self.inv_density_u[id]
+                        + 1.0 / self.inv_density_u[id + n]
+                        + 1.0 / self.inv_density_v[id]
+                        + 1.0 / self.inv_density_v[id + 1]);
+                self.density * relative_density
+            },
+            |id| self.cell_kind[id] == CellKind::Fluid,
+        );
     }
 
     fn set_obstacle(&mut self, pos: Vec2, reset: bool) {
@@ -897,26 +1673,423 @@ impl FlipSimulation {
 
         self.obstacle_pos = pos;
         self.obstacle_vel = v;
+        self.update_solid_cells();
+    }
+
+    /// Marks the cells covered by the obstacle and the rigid bodies as solid
+    /// and sets the velocities of their faces to the velocity of the solid
+    fn update_solid_cells(&mut self) {
         let r = self.obstacle_radius;
-        let n = self.num_cells_y;
-
-        for i in 1..self.num_cells_x - 2 {
-            for j in 1..self.num_cells_y - 2 {
-                self.s[i * n + j] = 1.0;
-                let dx = (i as f32 + 0.5) * self.h - pos.x;
-                let dy = (j as f32 + 0.5) * self.h - pos.y;
-
-                if dx * dx + dy * dy < r * r {
-                    self.s[i * n + j] = 0.0;
-                    self.u[i * n + j] = v.x;
-                    self.u[(i + 1) * n + j] = v.x;
-                    self.v[i * n + j] = v.y;
-                    self.v[i * n + (j + 1)] = v.y;
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                let id = i * n + j;
+                self.grid.s[id] = 1.0;
+                self.cell_body[id] = None;
+                let x = Vec2::new(i as f32 + 0.5, j as f32 + 0.5) * h;
+
+                if (x - self.obstacle_pos).length_squared() < r * r {
+                    let v = self.obstacle_vel;
+                    self.grid.s[id] = 0.0;
+                    self.grid.u[id] = v.x;
+                    self.grid.u[id + n] = v.x;
+                    self.grid.v[id] = v.y;
+                    self.grid.v[id + 1] = v.y;
+                    continue;
+                }
+
+                for (b, body) in self.bodies.iter().enumerate() {
+                    if body.distance(x).0 < 0.0 {
+                        self.grid.s[id] = 0.0;
+                        self.cell_body[id] = Some(b);
+                        self.grid.u[id] = body.point_vel(x - Vec2::new(0.5 * h, 0.0)).x;
+                        self.grid.u[id + n] = body.point_vel(x + Vec2::new(0.5 * h, 0.0)).x;
+                        self.grid.v[id] = body.point_vel(x - Vec2::new(0.0, 0.5 * h)).y;
+                        self.grid.v[id + 1] = body.point_vel(x + Vec2::new(0.0, 0.5 * h)).y;
+                        break;
+                    }
                 }
             }
         }
     }
 
+    fn integrate_bodies(&mut self) {
+        let h = self.grid.h;
+        let min = Vec2::splat(h);
+        let max = Vec2::new(
+            (self.grid.num_cells_x - 1) as f32 * h,
+            (self.grid.num_cells_y - 1) as f32 * h,
+        );
+        let mut obstacle = RigidBody::kinematic(
+            Shape::Circle {
+                radius: self.obstacle_radius,
+            },
+            self.obstacle_pos,
+            self.obstacle_vel,
+        );
+
+        for body in &mut self.bodies {
+            body.integrate(self.dt);
+        }
+        for a in 0..self.bodies.len() {
+            let (left, right) = self.bodies.split_at_mut(a + 1);
+            let body = &mut left[a];
+            for other in right {
+                RigidBody::collide(body, other);
+            }
+            RigidBody::collide(body, &mut obstacle);
+            body.collide_with_walls(min, max);
+        }
+    }
+
+    /// Integrates the pressure over the faces between fluid cells and rigid
+    /// bodies, and applies the resulting force and torque together with
+    /// gravity for one time step. With this explicit coupling, light bodies
+    /// are unstable unless they also accelerate the fluid they displace, so
+    /// the wetted fraction of their surface adds this mass to their inertia,
+    /// and impacts overshoot unless limited by the speed of the fluid.
+    fn apply_pressure_to_bodies(&mut self) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let mut impulses = vec![(Vec2::ZERO, 0.0); self.bodies.len()];
+        let mut num_faces = vec![0; self.bodies.len()];
+        let mut num_wet_faces = vec![0; self.bodies.len()];
+        let mut fluid_speed = vec![0.0_f32; self.bodies.len()];
+
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                let id = i * n + j;
+                if self.cell_body[id].is_some() {
+                    continue;
+                }
+                let x = Vec2::new(i as f32 + 0.5, j as f32 + 0.5) * h;
+                for (neighbor, normal) in [
+                    (id - n, Vec2::NEG_X),
+                    (id + n, Vec2::X),
+                    (id - 1, Vec2::NEG_Y),
+                    (id + 1, Vec2::Y),
+                ] {
+                    let Some(b) = self.cell_body[neighbor] else {
+                        continue;
+                    };
+                    num_faces[b] += 1;
+                    if self.cell_kind[id] == CellKind::Fluid {
+                        // the pressure pushes the body away from the fluid
+                        let impulse = self.grid.p[id] * h * self.dt * normal;
+                        let r = x + 0.5 * h * normal - self.bodies[b].pos;
+                        impulses[b].0 += impulse;
+                        impulses[b].1 += r.perp_dot(impulse);
+                        num_wet_faces[b] += 1;
+                        let vel = 0.5
+                            * Vec2::new(
+                                self.grid.u[id] + self.grid.u[id + n],
+                                self.grid.v[id] + self.grid.v[id + 1],
+                            );
+                        fluid_speed[b] = fluid_speed[b].max(vel.length());
+                    }
+                }
+            }
+        }
+
+        let gravity = Vec2::new(0.0, self.gravity) * self.dt;
+        for (b, body) in self.bodies.iter_mut().enumerate() {
+            let wet_fraction = if num_faces[b] > 0 {
+                num_wet_faces[b] as f32 / num_faces[b] as f32
+            } else {
+                0.0
+            };
+            let added_mass = wet_fraction * self.density * body.area();
+            let (impulse, angular_impulse) = impulses[b];
+            body.apply_fluid_impulse(
+                impulse,
+                angular_impulse,
+                gravity,
+                added_mass,
+                fluid_speed[b],
+            );
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_cells(&self) -> usize {
+        self.grid.num_cells
+    }
+
+    #[must_use]
+    pub fn boundary(&self, edge: BoundaryEdge) -> BoundaryKind {
+        self.grid.boundaries.get(edge).kind
+    }
+
+    /// Changes the boundary condition of a domain edge, see `Boundaries::set`
+    /// for how periodic edges pair up. Particles stay inside the domain at
+    /// outflow edges, which act as open air above the liquid, and wrap around
+    /// periodic edges. Inflow edges seed new particles, add a sink to drain
+    /// them.
+    pub fn set_boundary(&mut self, edge: BoundaryEdge, kind: BoundaryKind) {
+        let boundary = Boundary {
+            kind,
+            ..*self.grid.boundaries.get(edge)
+        };
+        self.grid.boundaries.set(edge, boundary);
+        self.grid.boundaries.set_ghost_cells(&mut self.grid.s);
+    }
+
+    #[must_use]
+    pub fn inflow_speed(&self, edge: BoundaryEdge) -> f32 {
+        self.grid.boundaries.get(edge).inflow_speed
+    }
+
+    #[must_use]
+    pub fn inflow_profile(&self, edge: BoundaryEdge) -> InflowProfile {
+        self.grid.boundaries.get(edge).inflow_profile
+    }
+
+    /// Sets the speed into the domain and the profile used if the edge is an
+    /// inflow
+    pub fn set_inflow(&mut self, edge: BoundaryEdge, speed: f32, profile: InflowProfile) {
+        self.grid.boundaries.set_inflow(edge, speed, profile);
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_iters(&self) -> usize {
+        self.grid.pressure.num_iters
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_residual(&self) -> f32 {
+        self.grid.pressure.residual
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_tolerance(&self) -> f32 {
+        self.grid.pressure.tolerance
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_pressure_tolerance(&mut self, tolerance: f32) {
+        self.grid.pressure.tolerance = tolerance;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn max_divergence(&self) -> f32 {
+        self.diagnostics.max_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn mean_divergence(&self) -> f32 {
+        self.diagnostics.mean_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn kinetic_energy(&self) -> f32 {
+        self.diagnostics.kinetic_energy
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn total_mass(&self) -> f32 {
+        self.diagnostics.total_mass
+    }
+
+    /// Width and height of the fluid domain, excluding the wall cells
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn domain_size(&self) -> Vec<f32> {
+        vec![
+            (self.grid.num_cells_x - 2) as f32 * self.grid.h,
+            (self.grid.num_cells_y - 2) as f32 * self.grid.h,
+        ]
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn cell_size(&self) -> f32 {
+        self.grid.h
+    }
+
+    /// Adds a dynamic circle centered at (x, y) in simulation units. The
+    /// density is relative to the fluid, so lighter bodies float.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `radius` or `relative_density` is not positive.
+    pub fn add_circle_body(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        relative_density: f32,
+    ) -> Result<(), JsValue> {
+        if radius <= 0.0 {
+            return Err(JsValue::from_str("body radius must be positive"));
+        }
+        if relative_density <= 0.0 {
+            return Err(JsValue::from_str("body density must be positive"));
+        }
+        self.bodies.push(RigidBody::new(
+            Shape::Circle { radius },
+            Vec2::new(x, y),
+            0.0,
+            relative_density * self.density,
+        ));
+        Ok(())
+    }
+
+    /// Adds a dynamic box centered at (x, y) in simulation units, rotated
+    /// by `angle` radians. The density is relative to the fluid.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the size or `relative_density` is not positive.
+    pub fn add_box_body(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        angle: f32,
+        relative_density: f32,
+    ) -> Result<(), JsValue> {
+        if width <= 0.0 || height <= 0.0 {
+            return Err(JsValue::from_str("body size must be positive"));
+        }
+        if relative_density <= 0.0 {
+            return Err(JsValue::from_str("body density must be positive"));
+        }
+        self.bodies.push(RigidBody::new(
+            Shape::Box {
+                half_extents: 0.5 * Vec2::new(width, height),
+            },
+            Vec2::new(x, y),
+            angle,
+            relative_density * self.density,
+        ));
+        Ok(())
+    }
+
+    /// Removes all rigid bodies and frees the cells they covered
+    pub fn clear_bodies(&mut self) {
+        self.bodies.clear();
+        self.update_solid_cells();
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_bodies(&self) -> usize {
+        self.bodies.len()
+    }
+
+    /// Adds a fluid phase with a density relative to the default fluid and
+    /// returns its id. Particles are moved to the phase with `set_phase`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `relative_density` is not positive or the maximum
+    /// number of phases has been added.
+    pub fn add_phase(&mut self, relative_density: f32) -> Result<usize, JsValue> {
+        if relative_density <= 0.0 {
+            return Err(JsValue::from_str("phase density must be positive"));
+        }
+        let id = self.phases.len();
+        if id == PHASE_COLORS.len() {
+            return Err(JsValue::from_str("too many fluid phases"));
+        }
+        self.phases.push(Phase {
+            relative_density,
+            color: PHASE_COLORS[id],
+        });
+        Ok(id)
+    }
+
+    /// Assigns all particles inside the rectangle with corners (x0, y0) and
+    /// (x1, y1) in simulation units to a phase
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `phase` is not a phase id.
+    pub fn set_phase(
+        &mut self,
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        phase: usize,
+    ) -> Result<(), JsValue> {
+        if phase >= self.phases.len() {
+            return Err(JsValue::from_str("unknown fluid phase"));
+        }
+        let a = Vec2::new(x0, y0);
+        let b = Vec2::new(x1, y1);
+        for i in 0..self.num_particles {
+            if contains(a.min(b), a.max(b), self.particle_pos[i]) {
+                self.particle_phase[i] = phase;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all phases but the default fluid, which all particles return to
+    pub fn clear_phases(&mut self) {
+        self.phases.truncate(1);
+        self.particle_phase.fill(0);
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_phases(&self) -> usize {
+        self.phases.len()
+    }
+
+    /// Adds an emitter over the rectangle with corners (x0, y0) and (x1, y1)
+    /// in simulation units, measured from the outer corner of the wall
+    /// cells, which creates fluid flowing at velocity (vx, vy)
+    pub fn add_emitter(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, vx: f32, vy: f32) {
+        let a = Vec2::new(x0, y0);
+        let b = Vec2::new(x1, y1);
+        self.emitters.push(Emitter {
+            min: a.min(b),
+            max: a.max(b),
+            vel: Vec2::new(vx, vy),
+            accum: 0.0,
+        });
+    }
+
+    /// Adds a faucet, a one cell high emitter of the given width centered at
+    /// (x, y) which pours fluid downwards at the given speed
+    pub fn add_faucet(&mut self, x: f32, y: f32, width: f32, speed: f32) {
+        let w = 0.5 * width;
+        let h = 0.5 * self.grid.h;
+        self.add_emitter(x - w, y - h, x + w, y + h, 0.0, -speed);
+    }
+
+    /// Adds a sink over the rectangle with corners (x0, y0) and (x1, y1),
+    /// which drains all particles entering it
+    pub fn add_sink(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        let a = Vec2::new(x0, y0);
+        let b = Vec2::new(x1, y1);
+        self.sinks.push(Sink {
+            min: a.min(b),
+            max: a.max(b),
+        });
+    }
+
+    pub fn clear_emitters(&mut self) {
+        self.emitters.clear();
+    }
+
+    pub fn clear_sinks(&mut self) {
+        self.sinks.clear();
+    }
+
     pub fn set_obstacle_from_canvas(&mut self, c_x: f32, c_y: f32, reset: bool) {
         let x = c_x / self.c_scale;
         let y = (self.height - c_y) / self.c_scale;
@@ -926,6 +2099,54 @@ impl FlipSimulation {
 }
 
 impl WebGLRenderer {
+    /// Draws a mesh given in simulation coordinates, which changes every
+    /// frame, as triangles with an outline of line segments
+    fn draw_mesh(
+        &self,
+        triangles: &[Vec2],
+        lines: &[Vec2],
+        color: Vec3,
+        outline_color: Vec3,
+        sim_width: f32,
+    ) {
+        let gl = &self.context;
+        gl.use_program(Some(&self.mesh_program));
+        gl.uniform2f(Some(&self.mesh_domain_size_uniform), sim_width, SIM_HEIGHT);
+        gl.uniform2fv_with_f32_array(Some(&self.mesh_translation_uniform), &Vec2::ZERO.to_array());
+        gl.uniform1f(Some(&self.mesh_scale_uniform), 1.0);
+        set_buffers_and_attributes(
+            gl,
+            &self.dynamic_mesh_buffer,
+            2,
+            self.mesh_position_attrib_location,
+        );
+
+        // the mesh size changes every frame, so reallocate the buffer
+        for (verts, color, mode) in [
+            (triangles, color, WebGl2RenderingContext::TRIANGLES),
+            (lines, outline_color, WebGl2RenderingContext::LINES),
+        ] {
+            gl.uniform3fv_with_f32_array(Some(&self.mesh_color_uniform), &color.to_array());
+            unsafe {
+                // See `FlipSimulation::draw` for safety
+                let verts_f32_view = verts.as_ptr().cast::<f32>(); // &[Vec2] -> *const Vec2 -> *const f32
+                let verts_array_buf_view = js_sys::Float32Array::view(std::slice::from_raw_parts(
+                    verts_f32_view,
+                    verts.len() * 2,
+                ));
+                gl.buffer_data_with_array_buffer_view(
+                    WebGl2RenderingContext::ARRAY_BUFFER,
+                    &verts_array_buf_view,
+                    WebGl2RenderingContext::STREAM_DRAW,
+                );
+            }
+            gl.draw_arrays(mode, 0, verts.len() as i32);
+        }
+
+        gl.disable_vertex_attrib_array(self.mesh_position_attrib_location);
+        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+    }
+
     #[allow(clippy::too_many_lines)]
     fn new(
         context: WebGl2RenderingContext,
@@ -1115,6 +2336,11 @@ impl WebGLRenderer {
             );
         }
 
+        // the surface and rigid body meshes are uploaded every frame
+        let dynamic_mesh_buffer = context
+            .create_buffer()
+            .ok_or("Failed to create dynamic mesh buffer")?;
+
         context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
         let disk_id_buffer = context
             .create_buffer()
@@ -1195,6 +2421,7 @@ impl WebGLRenderer {
 
             mesh_program,
             disk_buffer,
+            dynamic_mesh_buffer,
             disk_id_buffer,
             mesh_position_attrib_location,
             mesh_domain_size_uniform,
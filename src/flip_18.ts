@@ -1,8 +1,8 @@
 import GUI from 'lil-gui';
 import * as THREE from 'three';
 
-import { FlipSimulation } from '../pkg';
-import { Demo, Scene2DWebGL, Scene2DConfig } from './lib';
+import { FlipSimulation, FlipTransferScheme, PressureSolverKind, BoundaryEdge, BoundaryKind, InflowProfile } from '../pkg';
+import { Demo, Scene2DWebGL, Scene2DConfig, enumToValueList, downloadPng } from './lib';
 
 
 type FlipDemoProps = {
@@ -10,13 +10,42 @@ type FlipDemoProps = {
     numCells: number;
     numParticles: number;
     flipRatio: number;
+    transferScheme: string; // enum string value
     density: number;
     overRelaxation: number;
+    pressureSolver: string; // enum string value
+    pressureTolerance: number;
+    pressureIters: number;
+    pressureResidual: number;
     compensateDrift: boolean;
     separateParticles: boolean;
+    surfaceTension: number;
+    boundaryLeft: string; // enum string value
+    boundaryRight: string; // enum string value
+    boundaryBottom: string; // enum string value
+    boundaryTop: string; // enum string value
+    inflowSpeed: number;
+    inflowProfile: string; // enum string value
+    faucet: boolean;
+    inflow: boolean;
+    drain: boolean;
+    phases: string;
+    numBodies: number;
+    bodyDensity: number;
+    addBox: () => void;
+    addBall: () => void;
+    clearBodies: () => void;
     showObstacle: boolean;
     showParticles: boolean;
     showGrid: boolean;
+    showSurface: boolean;
+    surfaceSmoothing: number;
+    maxDivergence: number;
+    meanDivergence: number;
+    kineticEnergy: number;
+    totalMass: number;
+    showDivergence: boolean;
+    savePng: () => void;
 };
 
 const FlipDemoConfig: Scene2DConfig = {
@@ -43,18 +72,44 @@ class FlipDemo implements Demo<FlipSimulation, FlipDemoProps> {
         this.props.numCells = this.sim.num_cells;
         this.props.numParticles = this.sim.num_particles;
         this.props.flipRatio = this.sim.flip_ratio;
+        this.props.transferScheme = FlipTransferScheme[this.sim.transfer_scheme];
         this.props.density = this.sim.density;
         this.props.overRelaxation = this.sim.over_relaxation;
+        this.props.pressureSolver = PressureSolverKind[this.sim.pressure_solver];
+        this.props.pressureTolerance = this.sim.pressure_tolerance;
+        this.props.pressureIters = this.sim.pressure_iters;
+        this.props.pressureResidual = this.sim.pressure_residual;
         this.props.separateParticles = this.sim.separate_particles;
         this.props.compensateDrift = this.sim.compensate_drift;
+        this.props.surfaceTension = this.sim.surface_tension;
+        this.updateBoundaryProps();
+        this.props.inflowSpeed = this.sim.inflow_speed(BoundaryEdge.Left);
+        this.props.inflowProfile = InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)];
+        this.updateSources();
+        this.initPhases();
+        this.initBodies();
         this.props.showObstacle = this.sim.show_obstacle;
         this.props.showParticles = this.sim.show_particles;
         this.props.showGrid = this.sim.show_grid;
+        this.props.showSurface = this.sim.show_surface;
+        this.props.surfaceSmoothing = this.sim.num_surface_smoothing_iters;
+        this.props.maxDivergence = this.sim.max_divergence;
+        this.props.meanDivergence = this.sim.mean_divergence;
+        this.props.kineticEnergy = this.sim.kinetic_energy;
+        this.props.totalMass = this.sim.total_mass;
+        this.props.showDivergence = this.sim.show_divergence;
     }
 
     update() {
         if (this.props.animate) {
             this.sim.step();
+            this.props.numParticles = this.sim.num_particles;
+            this.props.pressureIters = this.sim.pressure_iters;
+            this.props.pressureResidual = this.sim.pressure_residual;
+            this.props.maxDivergence = this.sim.max_divergence;
+            this.props.meanDivergence = this.sim.mean_divergence;
+            this.props.kineticEnergy = this.sim.kinetic_energy;
+            this.props.totalMass = this.sim.total_mass;
         }
     }
 
@@ -74,26 +129,112 @@ class FlipDemo implements Demo<FlipSimulation, FlipDemoProps> {
             numCells: this.sim.num_cells,
             numParticles: this.sim.num_particles,
             flipRatio: this.sim.flip_ratio,
+            transferScheme: FlipTransferScheme[this.sim.transfer_scheme],
             density: this.sim.density,
             overRelaxation: this.sim.over_relaxation,
+            pressureSolver: PressureSolverKind[this.sim.pressure_solver],
+            pressureTolerance: this.sim.pressure_tolerance,
+            pressureIters: this.sim.pressure_iters,
+            pressureResidual: this.sim.pressure_residual,
             separateParticles: this.sim.separate_particles,
             compensateDrift: this.sim.compensate_drift,
+            surfaceTension: this.sim.surface_tension,
+            boundaryLeft: BoundaryKind[this.sim.boundary(BoundaryEdge.Left)],
+            boundaryRight: BoundaryKind[this.sim.boundary(BoundaryEdge.Right)],
+            boundaryBottom: BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)],
+            boundaryTop: BoundaryKind[this.sim.boundary(BoundaryEdge.Top)],
+            inflowSpeed: this.sim.inflow_speed(BoundaryEdge.Left),
+            inflowProfile: InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)],
+            faucet: false,
+            inflow: false,
+            drain: false,
+            phases: 'single',
+            numBodies: this.sim.num_bodies,
+            bodyDensity: 0.5,
+            addBox: () => this.addBody(false),
+            addBall: () => this.addBody(true),
+            clearBodies: () => {
+                this.sim.clear_bodies();
+                this.props.numBodies = this.sim.num_bodies;
+            },
             showObstacle: this.sim.show_obstacle,
             showParticles: this.sim.show_particles,
             showGrid: this.sim.show_grid,
+            showSurface: this.sim.show_surface,
+            surfaceSmoothing: this.sim.num_surface_smoothing_iters,
+            maxDivergence: this.sim.max_divergence,
+            meanDivergence: this.sim.mean_divergence,
+            kineticEnergy: this.sim.kinetic_energy,
+            totalMass: this.sim.total_mass,
+            showDivergence: this.sim.show_divergence,
+            savePng: () => downloadPng(this.sim.draw_png(), 'flip.png'),
         };
         folder.add(this.props, 'numCells').name('cells').disable().listen();
         folder.add(this.props, 'numParticles').name('particles').disable().listen();
         folder.add(this.props, 'density').disable().listen();
+        folder.add(this.props, 'transferScheme', enumToValueList(FlipTransferScheme)).name('transfer').onChange((v: string) => {
+            this.sim.transfer_scheme = Object.values(FlipTransferScheme).indexOf(v);
+        }).listen();
         folder.add(this.props, 'flipRatio').decimals(2).min(0.00).max(1.00).name('flip ratio').onChange((v: number) => (this.sim.flip_ratio = v)).listen();
         folder.add(this.props, 'overRelaxation').decimals(2).min(1.00).max(1.99).name('over relaxation').onChange((v: number) => (this.sim.over_relaxation = v)).listen();
+        folder.add(this.props, 'pressureSolver', enumToValueList(PressureSolverKind)).name('pressure solver').onChange((v: string) => {
+            this.sim.pressure_solver = Object.values(PressureSolverKind).indexOf(v);
+        }).listen();
+        folder.add(this.props, 'pressureTolerance', [1e-2, 1e-3, 1e-4, 1e-5, 1e-6]).name('pressure tolerance').onChange((v: number) => (this.sim.pressure_tolerance = v)).listen();
+        folder.add(this.props, 'pressureIters').name('pressure iters').disable().listen();
+        folder.add(this.props, 'pressureResidual').decimals(6).name('pressure residual').disable().listen();
         folder.add(this.props, 'separateParticles').name('separate particles').onChange((v: boolean) => (this.sim.separate_particles = v));
         folder.add(this.props, 'compensateDrift').name('compensate drift').onChange((v: boolean) => (this.sim.compensate_drift = v));
+        folder.add(this.props, 'surfaceTension').decimals(1).min(0.0).max(10.0).name('surface tension').onChange((v: number) => (this.sim.surface_tension = v)).listen();
+        const bounds = folder.addFolder('Boundaries');
+        const boundaryProps: [string, BoundaryEdge][] = [
+            ['boundaryLeft', BoundaryEdge.Left],
+            ['boundaryRight', BoundaryEdge.Right],
+            ['boundaryBottom', BoundaryEdge.Bottom],
+            ['boundaryTop', BoundaryEdge.Top],
+        ];
+        for (const [prop, edge] of boundaryProps) {
+            bounds.add(this.props, prop, enumToValueList(BoundaryKind)).name(BoundaryEdge[edge].toLowerCase()).onChange((v: string) => {
+                this.sim.set_boundary(edge, Object.values(BoundaryKind).indexOf(v));
+                // periodic edges come in pairs
+                this.updateBoundaryProps();
+            }).listen();
+        }
+        bounds.add(this.props, 'inflowSpeed').decimals(2).min(0.0).max(5.0).step(0.1).name('inflow speed').onChange((_: number) => this.updateInflow()).listen();
+        bounds.add(this.props, 'inflowProfile', enumToValueList(InflowProfile)).name('inflow profile').onChange((_: string) => this.updateInflow()).listen();
+        const sources = folder.addFolder('Sources');
+        sources.add(this.props, 'faucet').onChange(() => this.updateSources());
+        sources.add(this.props, 'inflow').onChange(() => this.updateSources());
+        sources.add(this.props, 'drain').onChange(() => this.updateSources());
+        // layers are set up in the initial water column, so changing them restarts the scene
+        folder.add(this.props, 'phases', ['single', 'oil on water', 'heavy on light']).onChange(() => this.reset());
+        // relative to the fluid density, bodies lighter than the fluid float
+        const bodies = folder.addFolder('Bodies');
+        bodies.add(this.props, 'numBodies').name('bodies').disable().listen();
+        bodies.add(this.props, 'bodyDensity').decimals(2).min(0.1).max(3.0).name('relative density');
+        bodies.add(this.props, 'addBox').name('add box');
+        bodies.add(this.props, 'addBall').name('add ball');
+        bodies.add(this.props, 'clearBodies').name('clear');
+        const diag = folder.addFolder('Diagnostics');
+        diag.add(this.props, 'maxDivergence').decimals(4).name('max divergence').disable().listen();
+        diag.add(this.props, 'meanDivergence').decimals(4).name('mean divergence').disable().listen();
+        diag.add(this.props, 'kineticEnergy').decimals(4).name('kinetic energy').disable().listen();
+        diag.add(this.props, 'totalMass').decimals(4).name('fluid mass').disable().listen();
         const sub = folder.addFolder('Rendering');
         sub.add(this.props, 'showObstacle').name('show obstacle').onFinishChange((v: boolean) => (this.sim.show_obstacle = v)).listen();
         sub.add(this.props, 'showParticles').name('show particles').onFinishChange((v: boolean) => (this.sim.show_particles = v)).listen();
         sub.add(this.props, 'showGrid').name('show grid').onFinishChange((v: boolean) => (this.sim.show_grid = v)).listen();
+        sub.add(this.props, 'showSurface').name('show surface').onFinishChange((v: boolean) => (this.sim.show_surface = v)).listen();
+        sub.add(this.props, 'surfaceSmoothing').min(0).max(20).step(1).name('surface smoothing').onChange((v: number) => (this.sim.num_surface_smoothing_iters = v)).listen();
+        sub.add(this.props, 'showDivergence').name('show divergence').onFinishChange((v: boolean) => {
+            this.sim.show_divergence = v;
+            if (v) {
+                this.props.showGrid = true;
+                this.sim.show_grid = true;
+            }
+        }).listen();
         sub.add(this.props, 'animate').listen();
+        sub.add(this.props, 'savePng').name('save png');
 
         // scene interaction
         this.mouseDown = false;
@@ -111,6 +252,78 @@ class FlipDemo implements Demo<FlipSimulation, FlipDemoProps> {
         });
     }
 
+    private updateBoundaryProps() {
+        this.props.boundaryLeft = BoundaryKind[this.sim.boundary(BoundaryEdge.Left)];
+        this.props.boundaryRight = BoundaryKind[this.sim.boundary(BoundaryEdge.Right)];
+        this.props.boundaryBottom = BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)];
+        this.props.boundaryTop = BoundaryKind[this.sim.boundary(BoundaryEdge.Top)];
+    }
+
+    // all edges share the inflow settings, they only apply to inflow edges
+    private updateInflow() {
+        const profile = Object.values(InflowProfile).indexOf(this.props.inflowProfile);
+        for (const edge of [BoundaryEdge.Left, BoundaryEdge.Right, BoundaryEdge.Bottom, BoundaryEdge.Top]) {
+            this.sim.set_inflow(edge, this.props.inflowSpeed, profile);
+        }
+    }
+
+    private updateSources() {
+        this.sim.clear_emitters();
+        this.sim.clear_sinks();
+        // the fluid domain starts after one layer of wall cells
+        const [width, height] = this.sim.domain_size;
+        const h = this.sim.cell_size;
+        if (this.props.faucet) {
+            this.sim.add_faucet(h + 0.8 * width, h + 0.9 * height, 0.1, 2.0);
+        }
+        if (this.props.inflow) {
+            this.sim.add_emitter(h, h + 0.4 * height, h + 0.1, h + 0.5 * height, 2.0, 0.0);
+        }
+        if (this.props.drain) {
+            this.sim.add_sink(h + width - 0.3, h, h + width, h + 0.1);
+        }
+    }
+
+    private initPhases() {
+        // the upper part of the water column becomes a second fluid
+        const [width, height] = this.sim.domain_size;
+        const h = this.sim.cell_size;
+        if (this.props.phases === 'oil on water') {
+            const oil = this.sim.add_phase(0.8);
+            this.sim.set_phase(h, h + 0.45 * height, h + width, h + height, oil);
+        } else if (this.props.phases === 'heavy on light') {
+            const heavy = this.sim.add_phase(2.0);
+            this.sim.set_phase(h, h + 0.45 * height, h + width, h + height, heavy);
+        }
+    }
+
+    private initBodies() {
+        // a floating crate, a sinking block and a ball in the path of the dam break
+        const [width, height] = this.sim.domain_size;
+        const h = this.sim.cell_size;
+        const size = 0.08 * Math.min(width, height);
+        this.sim.add_box_body(h + 0.6 * width, h + 0.3 * height, size, 0.6 * size, 0.3, 0.5);
+        this.sim.add_box_body(h + 0.75 * width, h + 0.5 * height, size, size, 0.0, 2.0);
+        this.sim.add_circle_body(h + 0.9 * width, h + 0.4 * height, 0.5 * size, 0.8);
+        this.props.numBodies = this.sim.num_bodies;
+    }
+
+    private addBody(circle: boolean) {
+        // drop the body at a random position near the top of the domain
+        const [width, height] = this.sim.domain_size;
+        const h = this.sim.cell_size;
+        const size = 0.08 * Math.min(width, height);
+        const x = h + size + Math.random() * (width - 2.0 * size);
+        const y = h + height - 1.5 * size;
+        if (circle) {
+            this.sim.add_circle_body(x, y, 0.5 * size, this.props.bodyDensity);
+        } else {
+            const angle = Math.random() * Math.PI;
+            this.sim.add_box_body(x, y, size, 0.6 * size, angle, this.props.bodyDensity);
+        }
+        this.props.numBodies = this.sim.num_bodies;
+    }
+
     private setMousePos(x: number, y: number, reset: boolean) {
         const mx = x - this.mouseOffset.x;
         const my = y - this.mouseOffset.y;
@@ -0,0 +1,686 @@
+#![allow(clippy::many_single_char_names, clippy::similar_names)]
+
+use glam::{vec3, UVec3, Vec3};
+use wasm_bindgen::prelude::*;
+
+const DOMAIN_WIDTH: f32 = 1.0;
+const DOMAIN_HEIGHT: f32 = 1.0;
+const DOMAIN_DEPTH: f32 = 0.5;
+const DEFAULT_RESOLUTION: f32 = 30.0;
+const DAM_BREAK_REL_WATER_HEIGHT: f32 = 0.8;
+const DAM_BREAK_REL_WATER_WIDTH: f32 = 0.5;
+const PARTICLE_CELL_SCALE: f32 = 0.3;
+const PARTICLE_SPACING_SCALE: f32 = 2.2;
+const DEFAULT_DENSITY: f32 = 1000.0;
+const DEFAULT_NUM_SUBSTEPS: usize = 1;
+const DEFAULT_NUM_PRESSURE_ITERS: usize = 50;
+const DEFAULT_NUM_PARTICLE_ITERS: usize = 2;
+const DEFAULT_FLIP_RATIO: f32 = 0.9;
+const DEFAULT_OVER_RELAXATION: f32 = 1.9;
+const DEFAULT_GRAVITY: f32 = -9.81;
+const DEFAULT_DT: f32 = 1.0 / 60.0;
+
+const COLOR_DIFFUSION_COEFF: f32 = 0.001;
+const PARTICLE_COLOR: Vec3 = vec3(0.0, 0.0, 1.0);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    Fluid,
+    Air,
+    Solid,
+}
+
+/// 3D version of `FlipSimulation` on a MAC grid with the `(i * ny + j) * nz + k`
+/// cell layout, where `u`, `v` and `w` are stored at the left, bottom and back
+/// face of each cell. The tank is closed except for the top.
+#[allow(clippy::struct_excessive_bools)]
+#[wasm_bindgen]
+pub struct FlipSimulation3D {
+    #[wasm_bindgen(readonly)]
+    pub density: f32,
+    h: f32,
+    gravity: f32,
+    #[wasm_bindgen(readonly)]
+    pub dt: f32,
+    pub num_substeps: usize,
+    pub num_pressure_iters: usize,
+    num_particle_iters: usize,
+    pub flip_ratio: f32,
+    pub over_relaxation: f32,
+    pub compensate_drift: bool,
+    pub separate_particles: bool,
+
+    particle_num_cells_x: usize,
+    particle_num_cells_y: usize,
+    particle_num_cells_z: usize,
+    particle_num_cells: usize,
+    #[wasm_bindgen(readonly)]
+    pub num_particles: usize,
+    particle_inv_spacing: f32,
+    #[wasm_bindgen(readonly)]
+    pub particle_radius: f32,
+    particle_pos: Vec<Vec3>,
+    particle_vel: Vec<Vec3>,
+    particle_density: Vec<f32>,
+    particle_rest_density: f32,
+    particle_color: Vec<Vec3>,
+    num_cells_x: usize,
+    num_cells_y: usize,
+    num_cells_z: usize,
+    #[wasm_bindgen(readonly)]
+    pub num_cells: usize,
+    inv_spacing: f32,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    w: Vec<f32>,
+    du: Vec<f32>,
+    dv: Vec<f32>,
+    dw: Vec<f32>,
+    prev_u: Vec<f32>,
+    prev_v: Vec<f32>,
+    prev_w: Vec<f32>,
+    s: Vec<f32>,
+    cell_num_particles: Vec<usize>,
+    cell_first_particle: Vec<usize>,
+    cell_particle_ids: Vec<usize>,
+    cell_kind: Vec<CellKind>,
+}
+
+/// Returns the eight grid indices and trilinear weights used to sample a
+/// field, stored with `offset` from the cell corners, at position `p`. The
+/// stencil is clamped to the interior of the grid.
+#[must_use]
+fn trilinear_stencil(p: Vec3, offset: Vec3, h: f32, dims: UVec3) -> ([usize; 8], [f32; 8]) {
+    let h1 = 1.0 / h;
+    let max = (dims - 1).as_vec3() * h;
+    let x = p.clamp(Vec3::splat(h), max) - offset;
+
+    let x0 = UVec3::min((x * h1).floor().as_uvec3(), dims - 2);
+    let t = (x - x0.as_vec3() * h) * h1;
+    let x1 = UVec3::min(x0 + 1, dims - 2);
+    let s = 1.0 - t;
+
+    let n = (dims.y * dims.z) as usize;
+    let nz = dims.z as usize;
+    let id = |x: u32, y: u32, z: u32| x as usize * n + y as usize * nz + z as usize;
+    (
+        [
+            id(x0.x, x0.y, x0.z),
+            id(x1.x, x0.y, x0.z),
+            id(x1.x, x1.y, x0.z),
+            id(x0.x, x1.y, x0.z),
+            id(x0.x, x0.y, x1.z),
+            id(x1.x, x0.y, x1.z),
+            id(x1.x, x1.y, x1.z),
+            id(x0.x, x1.y, x1.z),
+        ],
+        [
+            s.x * s.y * s.z,
+            t.x * s.y * s.z,
+            t.x * t.y * s.z,
+            s.x * t.y * s.z,
+            s.x * s.y * t.z,
+            t.x * s.y * t.z,
+            t.x * t.y * t.z,
+            s.x * t.y * t.z,
+        ],
+    )
+}
+
+#[wasm_bindgen]
+impl FlipSimulation3D {
+    #[allow(clippy::new_without_default)]
+    #[must_use]
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> FlipSimulation3D {
+        let h = DOMAIN_HEIGHT / DEFAULT_RESOLUTION;
+        let num_cells_x = f32::floor(DOMAIN_WIDTH / h) as usize + 2;
+        let num_cells_y = f32::floor(DOMAIN_HEIGHT / h) as usize + 2;
+        let num_cells_z = f32::floor(DOMAIN_DEPTH / h) as usize + 2;
+        let num_cells = num_cells_x * num_cells_y * num_cells_z;
+        let inv_spacing = 1.0 / h;
+
+        let particle_radius = PARTICLE_CELL_SCALE * h;
+        let particle_inv_spacing = 1.0 / (PARTICLE_SPACING_SCALE * particle_radius);
+        let particle_num_cells_x = f32::floor(DOMAIN_WIDTH * particle_inv_spacing) as usize + 1;
+        let particle_num_cells_y = f32::floor(DOMAIN_HEIGHT * particle_inv_spacing) as usize + 1;
+        let particle_num_cells_z = f32::floor(DOMAIN_DEPTH * particle_inv_spacing) as usize + 1;
+        let particle_num_cells = particle_num_cells_x * particle_num_cells_y * particle_num_cells_z;
+        let num_particles = Self::dam_break_size(h, particle_radius, num_cells_z)
+            .into_iter()
+            .product();
+
+        let mut fluid = Self {
+            density: DEFAULT_DENSITY,
+            h,
+            gravity: DEFAULT_GRAVITY,
+            dt: DEFAULT_DT,
+            num_substeps: DEFAULT_NUM_SUBSTEPS,
+            num_pressure_iters: DEFAULT_NUM_PRESSURE_ITERS,
+            num_particle_iters: DEFAULT_NUM_PARTICLE_ITERS,
+            flip_ratio: DEFAULT_FLIP_RATIO,
+            over_relaxation: DEFAULT_OVER_RELAXATION,
+            compensate_drift: true,
+            separate_particles: true,
+
+            particle_num_cells_x,
+            particle_num_cells_y,
+            particle_num_cells_z,
+            particle_num_cells,
+            num_particles,
+            particle_inv_spacing,
+            particle_radius,
+            particle_pos: vec![Vec3::ZERO; num_particles],
+            particle_vel: vec![Vec3::ZERO; num_particles],
+            particle_density: vec![0.0; num_cells],
+            particle_rest_density: 0.0,
+            particle_color: vec![PARTICLE_COLOR; num_particles],
+            num_cells_x,
+            num_cells_y,
+            num_cells_z,
+            num_cells,
+            inv_spacing,
+            u: vec![0.0; num_cells],
+            v: vec![0.0; num_cells],
+            w: vec![0.0; num_cells],
+            du: vec![0.0; num_cells],
+            dv: vec![0.0; num_cells],
+            dw: vec![0.0; num_cells],
+            prev_u: vec![0.0; num_cells],
+            prev_v: vec![0.0; num_cells],
+            prev_w: vec![0.0; num_cells],
+            s: vec![0.0; num_cells],
+            cell_num_particles: vec![0; particle_num_cells],
+            cell_first_particle: vec![0; particle_num_cells + 1],
+            cell_particle_ids: vec![0; num_particles],
+            cell_kind: vec![CellKind::Air; num_cells],
+        };
+
+        // setup grid cells for fluid domain, a tank open at the top
+        let n = num_cells_y * num_cells_z;
+        for i in 0..num_cells_x {
+            for j in 0..num_cells_y {
+                for k in 0..num_cells_z {
+                    let mut s = 1.0; // fluid
+                    if i == 0 || i == num_cells_x - 1 || j == 0 || k == 0 || k == num_cells_z - 1 {
+                        s = 0.0; // solid
+                    }
+                    fluid.s[i * n + j * num_cells_z + k] = s;
+                }
+            }
+        }
+
+        fluid.reset();
+        fluid
+    }
+
+    /// Number of particles along each axis of the initial water block
+    #[must_use]
+    fn dam_break_size(h: f32, particle_radius: f32, num_cells_z: usize) -> [usize; 3] {
+        let dx = 2.0 * particle_radius;
+        let depth = (num_cells_z - 2) as f32 * h;
+        [
+            f32::floor((DAM_BREAK_REL_WATER_WIDTH * DOMAIN_WIDTH - 2.0 * h - dx) / dx) as usize,
+            f32::floor((DAM_BREAK_REL_WATER_HEIGHT * DOMAIN_HEIGHT - 2.0 * h - dx) / dx) as usize,
+            f32::floor((depth - dx) / dx) as usize,
+        ]
+    }
+
+    /// Restores the initial dam break, keeping all buffers in place so that
+    /// pointers held by JS stay valid
+    pub fn reset(&mut self) {
+        let h = self.h;
+        let r = self.particle_radius;
+        let dx = 2.0 * r;
+        let [num_x, num_y, num_z] = Self::dam_break_size(h, r, self.num_cells_z);
+
+        let mut p = 0;
+        for i in 0..num_x {
+            for j in 0..num_y {
+                for k in 0..num_z {
+                    // offset every other layer to avoid a perfectly stacked lattice
+                    let offset = if j % 2 == 0 { 0.0 } else { r };
+                    self.particle_pos[p] = Vec3::new(
+                        h + r + dx * i as f32 + offset,
+                        h + r + dx * j as f32,
+                        h + r + dx * k as f32 + offset,
+                    );
+                    p += 1;
+                }
+            }
+        }
+        self.particle_vel.fill(Vec3::ZERO);
+        self.particle_color.fill(PARTICLE_COLOR);
+        self.particle_rest_density = 0.0;
+
+        self.u.fill(0.0);
+        self.v.fill(0.0);
+        self.w.fill(0.0);
+    }
+
+    fn integrate_particles(&mut self) {
+        for i in 0..self.num_particles {
+            self.particle_vel[i].y += self.dt * self.gravity;
+            self.particle_pos[i] += self.particle_vel[i] * self.dt;
+        }
+    }
+
+    fn pos_to_cell_idx(&self, x: Vec3, particle_grid: bool) -> usize {
+        let (spacing, num_x, num_y, num_z) = if particle_grid {
+            (
+                self.particle_inv_spacing,
+                self.particle_num_cells_x,
+                self.particle_num_cells_y,
+                self.particle_num_cells_z,
+            )
+        } else {
+            (
+                self.inv_spacing,
+                self.num_cells_x,
+                self.num_cells_y,
+                self.num_cells_z,
+            )
+        };
+
+        let xi = UVec3::clamp(
+            Vec3::floor(x * spacing).as_uvec3(),
+            UVec3::ZERO,
+            UVec3::new(num_x as u32 - 1, num_y as u32 - 1, num_z as u32 - 1),
+        );
+        (xi.x as usize * num_y + xi.y as usize) * num_z + xi.z as usize
+    }
+
+    fn push_particles_apart(&mut self) {
+        // count particles per cell
+        self.cell_num_particles.fill(0);
+        for i in 0..self.num_particles {
+            let cell_idx = self.pos_to_cell_idx(self.particle_pos[i], true);
+            self.cell_num_particles[cell_idx] += 1;
+        }
+
+        // partial sums
+        let mut first = 0;
+        for i in 0..self.particle_num_cells {
+            first += self.cell_num_particles[i];
+            self.cell_first_particle[i] = first;
+        }
+        self.cell_first_particle[self.particle_num_cells] = first; // guard
+
+        // fill particles into cells
+        for i in 0..self.num_particles {
+            let cell_idx = self.pos_to_cell_idx(self.particle_pos[i], true);
+            self.cell_first_particle[cell_idx] -= 1;
+            self.cell_particle_ids[self.cell_first_particle[cell_idx]] = i;
+        }
+
+        // push particles apart
+        let min_dist = 2.0 * self.particle_radius;
+        let min_dist_sq = min_dist * min_dist;
+        let max_cell = UVec3::new(
+            self.particle_num_cells_x as u32 - 1,
+            self.particle_num_cells_y as u32 - 1,
+            self.particle_num_cells_z as u32 - 1,
+        );
+        for _ in 0..self.num_particle_iters {
+            for i in 0..self.num_particles {
+                let p = self.particle_pos[i];
+                let pi = (p * self.particle_inv_spacing).floor();
+                let c0 = (pi - 1.0).max(Vec3::ZERO).as_uvec3();
+                let c1 = UVec3::min((pi + 1.0).max(Vec3::ZERO).as_uvec3(), max_cell);
+
+                for xi in c0.x..=c1.x {
+                    for yi in c0.y..=c1.y {
+                        for zi in c0.z..=c1.z {
+                            let cell_idx = (xi as usize * self.particle_num_cells_y + yi as usize)
+                                * self.particle_num_cells_z
+                                + zi as usize;
+                            let first = self.cell_first_particle[cell_idx];
+                            let last = self.cell_first_particle[cell_idx + 1];
+                            for j in first..last {
+                                let id = self.cell_particle_ids[j];
+                                if id == i {
+                                    continue;
+                                }
+                                let q = self.particle_pos[id];
+                                let mut d = q - p;
+                                let dist_sq = d.length_squared();
+                                if dist_sq > min_dist_sq || dist_sq == 0.0 {
+                                    continue;
+                                }
+                                let dist = f32::sqrt(dist_sq);
+                                let s = 0.5 * (min_dist - dist) / dist;
+                                d *= s;
+                                self.particle_pos[i] -= d;
+                                self.particle_pos[id] += d;
+
+                                // diffuse colors
+                                let color0 = self.particle_color[i];
+                                let color1 = self.particle_color[id];
+                                let color = (color0 + color1) * 0.5;
+                                self.particle_color[i] =
+                                    color0 + (color - color0) * COLOR_DIFFUSION_COEFF;
+                                self.particle_color[id] =
+                                    color1 + (color - color1) * COLOR_DIFFUSION_COEFF;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_particle_collisions(&mut self) {
+        let h = self.h;
+        let r = self.particle_radius;
+        let min = Vec3::splat(h + r);
+        let max = Vec3::new(
+            (self.num_cells_x as f32 - 1.0) * h - r,
+            (self.num_cells_y as f32 - 1.0) * h - r,
+            (self.num_cells_z as f32 - 1.0) * h - r,
+        );
+
+        for i in 0..self.num_particles {
+            let x = self.particle_pos[i];
+            let clamped = x.clamp(min, max);
+            // wall collisions
+            for k in 0..3 {
+                if clamped[k] != x[k] {
+                    self.particle_vel[i][k] = 0.0;
+                }
+            }
+            self.particle_pos[i] = clamped;
+        }
+    }
+
+    fn transfer_velocities(&mut self, to_grid: bool) {
+        let n = self.num_cells_y * self.num_cells_z;
+        let nz = self.num_cells_z;
+        let h = self.h;
+        let h2 = 0.5 * h;
+        let dims = UVec3::new(
+            self.num_cells_x as u32,
+            self.num_cells_y as u32,
+            self.num_cells_z as u32,
+        );
+
+        if to_grid {
+            self.prev_u.copy_from_slice(&self.u);
+            self.prev_v.copy_from_slice(&self.v);
+            self.prev_w.copy_from_slice(&self.w);
+            self.du.fill(0.0);
+            self.dv.fill(0.0);
+            self.dw.fill(0.0);
+            self.u.fill(0.0);
+            self.v.fill(0.0);
+            self.w.fill(0.0);
+
+            for i in 0..self.num_cells {
+                self.cell_kind[i] = if self.s[i] == 0.0 {
+                    CellKind::Solid
+                } else {
+                    CellKind::Air
+                };
+            }
+
+            for i in 0..self.num_particles {
+                let cell_idx = self.pos_to_cell_idx(self.particle_pos[i], false);
+                if self.cell_kind[cell_idx] == CellKind::Air {
+                    self.cell_kind[cell_idx] = CellKind::Fluid;
+                }
+            }
+        }
+
+        for component in 0..3 {
+            let (offset, stride, f, prev_f, d) = match component {
+                0 => (
+                    vec3(0.0, h2, h2),
+                    n,
+                    &mut self.u,
+                    &mut self.prev_u,
+                    &mut self.du,
+                ),
+                1 => (
+                    vec3(h2, 0.0, h2),
+                    nz,
+                    &mut self.v,
+                    &mut self.prev_v,
+                    &mut self.dv,
+                ),
+                _ => (
+                    vec3(h2, h2, 0.0),
+                    1,
+                    &mut self.w,
+                    &mut self.prev_w,
+                    &mut self.dw,
+                ),
+            };
+
+            for i in 0..self.num_particles {
+                let (ids, weights) = trilinear_stencil(self.particle_pos[i], offset, h, dims);
+
+                if to_grid {
+                    let pv = self.particle_vel[i][component];
+                    for (&id, &weight) in ids.iter().zip(&weights) {
+                        f[id] += pv * weight;
+                        d[id] += weight;
+                    }
+                } else {
+                    // only faces next to a non-air cell carry a valid velocity
+                    let mut sum = 0.0;
+                    let mut pic_v = 0.0;
+                    let mut corr = 0.0;
+                    for (&id, &weight) in ids.iter().zip(&weights) {
+                        if self.cell_kind[id] == CellKind::Air
+                            && self.cell_kind[id - stride] == CellKind::Air
+                        {
+                            continue;
+                        }
+                        sum += weight;
+                        pic_v += weight * f[id];
+                        corr += weight * (f[id] - prev_f[id]);
+                    }
+
+                    if sum > 0.0 {
+                        let v = self.particle_vel[i][component];
+                        let pic_v = pic_v / sum;
+                        let flip_v = v + corr / sum;
+                        self.particle_vel[i][component] =
+                            (1.0 - self.flip_ratio) * pic_v + self.flip_ratio * flip_v;
+                    }
+                }
+            }
+
+            if to_grid {
+                for i in 0..f.len() {
+                    if d[i] > 0.0 {
+                        f[i] /= d[i];
+                    }
+                }
+
+                // restore solid cells
+                for i in 0..self.num_cells_x {
+                    for j in 0..self.num_cells_y {
+                        for k in 0..self.num_cells_z {
+                            let ind = i * n + j * nz + k;
+                            let first = match component {
+                                0 => i == 0,
+                                1 => j == 0,
+                                _ => k == 0,
+                            };
+                            let solid = self.cell_kind[ind] == CellKind::Solid;
+                            if solid || (!first && self.cell_kind[ind - stride] == CellKind::Solid)
+                            {
+                                f[ind] = prev_f[ind];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_particle_density(&mut self) {
+        let h = self.h;
+        let dims = UVec3::new(
+            self.num_cells_x as u32,
+            self.num_cells_y as u32,
+            self.num_cells_z as u32,
+        );
+        let d = &mut self.particle_density;
+
+        d.fill(0.0);
+        for i in 0..self.num_particles {
+            let (ids, weights) =
+                trilinear_stencil(self.particle_pos[i], Vec3::splat(0.5 * h), h, dims);
+            for (&id, &weight) in ids.iter().zip(&weights) {
+                d[id] += weight;
+            }
+        }
+
+        if self.particle_rest_density == 0.0 {
+            let mut sum = 0.0;
+            let mut num_fluid_cells = 0.0;
+            for (i, id) in d.iter().enumerate() {
+                if self.cell_kind[i] == CellKind::Fluid {
+                    sum += id;
+                    num_fluid_cells += 1.0;
+                }
+            }
+
+            if num_fluid_cells > 0.0 {
+                self.particle_rest_density = sum / num_fluid_cells;
+            }
+        }
+    }
+
+    fn solve_incompressibility(&mut self) {
+        let n = self.num_cells_y * self.num_cells_z;
+        let nz = self.num_cells_z;
+
+        self.prev_u.clone_from_slice(&self.u);
+        self.prev_v.clone_from_slice(&self.v);
+        self.prev_w.clone_from_slice(&self.w);
+
+        for _ in 0..self.num_pressure_iters {
+            for i in 1..self.num_cells_x - 1 {
+                for j in 1..self.num_cells_y - 1 {
+                    for k in 1..self.num_cells_z - 1 {
+                        let center = i * n + j * nz + k;
+                        if self.cell_kind[center] != CellKind::Fluid {
+                            continue;
+                        }
+                        let left = center - n;
+                        let right = center + n;
+                        let bottom = center - nz;
+                        let top = center + nz;
+                        let back = center - 1;
+                        let front = center + 1;
+
+                        let sx0 = self.s[left];
+                        let sx1 = self.s[right];
+                        let sy0 = self.s[bottom];
+                        let sy1 = self.s[top];
+                        let sz0 = self.s[back];
+                        let sz1 = self.s[front];
+                        let s = sx0 + sx1 + sy0 + sy1 + sz0 + sz1;
+
+                        if s == 0.0 {
+                            continue;
+                        }
+
+                        let mut div = self.u[right] - self.u[center] + self.v[top] - self.v[center]
+                            + self.w[front]
+                            - self.w[center];
+                        if self.particle_rest_density > 0.0 && self.compensate_drift {
+                            let k = 1.0;
+                            let compression =
+                                self.particle_density[center] - self.particle_rest_density;
+                            if compression > 0.0 {
+                                div -= k * compression;
+                            }
+                        }
+
+                        let p = -div / s * self.over_relaxation;
+                        self.u[center] -= sx0 * p;
+                        self.u[right] += sx1 * p;
+                        self.v[center] -= sy0 * p;
+                        self.v[top] += sy1 * p;
+                        self.w[center] -= sz0 * p;
+                        self.w[front] += sz1 * p;
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_particle_colors(&mut self) {
+        for i in 0..self.num_particles {
+            let mut s = 0.01;
+            self.particle_color[i] = Vec3::clamp(
+                self.particle_color[i] + Vec3::new(-s, -s, s),
+                Vec3::splat(0.0),
+                Vec3::splat(1.0),
+            );
+            let cell_idx = self.pos_to_cell_idx(self.particle_pos[i], false);
+
+            let d0 = self.particle_rest_density;
+            if d0 > 0.0 {
+                let rel_density = self.particle_density[cell_idx] / d0;
+                if rel_density < 0.7 {
+                    s = 0.8;
+                    self.particle_color[i] = Vec3::new(s, s, 1.0);
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        for _ in 0..self.num_substeps {
+            self.integrate_particles();
+            if self.separate_particles {
+                self.push_particles_apart();
+            }
+            self.handle_particle_collisions();
+            self.transfer_velocities(true);
+            self.update_particle_density();
+            self.solve_incompressibility();
+            self.transfer_velocities(false);
+        }
+        self.update_particle_colors();
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn particle_pos(&self) -> *const Vec3 {
+        // Generally, this is unsafe! We take care in JS to make sure to
+        // query the positions array pointer after heap allocations have
+        // occurred (which move the location).
+        // Positions is a Vec<Vec3>, which is a linear array of f32s in
+        // memory.
+        self.particle_pos.as_ptr()
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn particle_color(&self) -> *const Vec3 {
+        // See above comment for `particle_pos` re: safety
+        self.particle_color.as_ptr()
+    }
+
+    /// Size of the fluid domain inside the walls, which starts at (h, h, h)
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn domain_size(&self) -> Vec<f32> {
+        vec![
+            (self.num_cells_x - 2) as f32 * self.h,
+            (self.num_cells_y - 2) as f32 * self.h,
+            (self.num_cells_z - 2) as f32 * self.h,
+        ]
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn cell_size(&self) -> f32 {
+        self.h
+    }
+}
@@ -0,0 +1,115 @@
+import GUI from 'lil-gui';
+import * as THREE from 'three';
+
+import { FlipSimulation3D } from '../pkg';
+import { Demo, Scene3D, Scene3DConfig } from './lib';
+
+type Flip3DDemoProps = {
+    animate: boolean;
+    numCells: number;
+    numParticles: number;
+    flipRatio: number;
+    overRelaxation: number;
+    pressureIters: number;
+    compensateDrift: boolean;
+    separateParticles: boolean;
+};
+
+const Flip3DDemoConfig: Scene3DConfig = {
+    kind: '3D',
+    cameraYZ: [0.8, 1.5],
+    cameraLookAt: new THREE.Vector3(0, 0.3, 0),
+}
+
+class Flip3DDemo implements Demo<FlipSimulation3D, Flip3DDemoProps> {
+    sim: FlipSimulation3D;
+    scene: Scene3D;
+    props: Flip3DDemoProps;
+
+    private memory: WebAssembly.Memory;
+    private points: THREE.Points;
+    private positions: Float32Array; // mapped to WASM memory
+    private colors: Float32Array; // mapped to WASM memory
+
+    constructor(rust_wasm: any, memory: WebAssembly.Memory, _: HTMLCanvasElement, scene: Scene3D, folder: GUI) {
+        this.memory = memory;
+        this.sim = new rust_wasm.FlipSimulation3D();
+        this.scene = scene;
+        this.initControls(folder);
+    }
+
+    init() {
+        this.initMesh();
+    }
+
+    update() {
+        if (this.props.animate) {
+            this.sim.step();
+            this.updateMesh();
+        }
+    }
+
+    reset() {
+        this.sim.reset();
+        this.updateMesh();
+    }
+
+    private initControls(folder: GUI) {
+        this.props = {
+            animate: true,
+            numCells: this.sim.num_cells,
+            numParticles: this.sim.num_particles,
+            flipRatio: this.sim.flip_ratio,
+            overRelaxation: this.sim.over_relaxation,
+            pressureIters: this.sim.num_pressure_iters,
+            compensateDrift: this.sim.compensate_drift,
+            separateParticles: this.sim.separate_particles,
+        };
+        folder.add(this.props, 'numCells').name('cells').disable();
+        folder.add(this.props, 'numParticles').name('particles').disable();
+        folder.add(this.props, 'flipRatio').decimals(2).min(0.00).max(1.00).name('flip ratio').onChange((v: number) => (this.sim.flip_ratio = v));
+        folder.add(this.props, 'overRelaxation').decimals(2).min(1.00).max(1.99).name('over relaxation').onChange((v: number) => (this.sim.over_relaxation = v));
+        folder.add(this.props, 'pressureIters').min(1).max(200).step(1).name('pressure iters').onChange((v: number) => (this.sim.num_pressure_iters = v));
+        folder.add(this.props, 'separateParticles').name('separate particles').onChange((v: boolean) => (this.sim.separate_particles = v));
+        folder.add(this.props, 'compensateDrift').name('compensate drift').onChange((v: boolean) => (this.sim.compensate_drift = v));
+        folder.add(this.props, 'animate');
+    }
+
+    private initMesh() {
+        // see `HashDemo` re: mapping the particle buffers
+        const numParticles = this.sim.num_particles;
+        this.positions = new Float32Array(this.memory.buffer, this.sim.particle_pos, numParticles * 3);
+        this.colors = new Float32Array(this.memory.buffer, this.sim.particle_color, numParticles * 3);
+
+        const geometry = new THREE.BufferGeometry();
+        geometry.setAttribute('position', new THREE.BufferAttribute(this.positions, 3));
+        geometry.setAttribute('color', new THREE.BufferAttribute(this.colors, 3));
+        const material = new THREE.PointsMaterial({ size: 2.0 * this.sim.particle_radius, vertexColors: true });
+        this.points = new THREE.Points(geometry, material);
+
+        // the fluid domain starts after one layer of wall cells, center it on the ground
+        const [width, height, depth] = this.sim.domain_size;
+        const h = this.sim.cell_size;
+        const group = new THREE.Group();
+        group.position.set(-0.5 * width - h, -h, -0.5 * depth - h);
+        group.add(this.points);
+
+        const tank = new THREE.LineSegments(
+            new THREE.EdgesGeometry(new THREE.BoxGeometry(width, height, depth)),
+            new THREE.LineBasicMaterial({ color: 0xffffff }),
+        );
+        tank.position.set(0.5 * width + h, 0.5 * height + h, 0.5 * depth + h);
+        group.add(tank);
+        this.scene.scene.add(group);
+
+        this.updateMesh();
+    }
+
+    private updateMesh() {
+        this.points.geometry.attributes.position.needsUpdate = true;
+        this.points.geometry.attributes.color.needsUpdate = true;
+        this.points.geometry.computeBoundingSphere();
+    }
+}
+
+export { Flip3DDemo, Flip3DDemoConfig };
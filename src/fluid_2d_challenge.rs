@@ -1,7 +1,11 @@
 //! Adapted from my separate position-based fluid [repository](https://github.com/lucas-schuermann/pbd-fluid-rs/)
 //! using the `solver` crate and demo [setup](https://github.com/lucas-schuermann/pbd-fluid-rs/blob/master/src/lib.rs)
+//!
+//! This fluid has no surface tension yet. The `solver` crate only exposes the
+//! particle positions read-only and runs its substeps inside `State::update`,
+//! so a cohesion term needs a hook in the solver itself.
 
-use glam::vec2;
+use glam::{vec2, vec3, Vec2, Vec3};
 use wasm_bindgen::prelude::*;
 use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlUniformLocation};
 
@@ -9,13 +13,25 @@ const BLOCK_PARTICLES: usize = 400;
 const MAX_PARTICLES: usize = pbd_fluid_solver::MAX_PARTICLES;
 const POINT_SIZE: f32 = 3.0;
 const DRAW_SCALE: f32 = 250.0;
-
+// the colors of the shaders for the CPU renderer
+const PARTICLE_COLOR_1: Vec3 = vec3(0.254_901_96, 0.411_764_7, 1.0);
+const PARTICLE_COLOR_2: Vec3 = vec3(1.0, 0.254_901_96, 0.298_039_2);
+const PARTICLE_COLOR_BLOCK: usize = 1000;
+const BOUNDARY_COLOR: Vec3 = vec3(0.439_215_7, 0.501_960_8, 0.564_705_9);
+const CLEAR_COLOR_DARK: Vec3 = Vec3::splat(0.1);
+const CLEAR_COLOR_LIGHT: Vec3 = Vec3::splat(0.9);
+
+use crate::raster::{render_png, Raster};
 use crate::util::{compile_shader, link_program, set_buffers_and_attributes};
 
 #[wasm_bindgen]
 pub struct PositionBasedFluidSimulation {
     state: pbd_fluid_solver::State,
-    renderer: WebGLRenderer,
+    width: f32,
+    height: f32,
+    use_dark_colors: bool,
+    draw_single_color: bool,
+    renderer: Option<WebGLRenderer>,
 }
 
 struct WebGLRenderer {
@@ -30,11 +46,14 @@ struct WebGLRenderer {
 
 #[wasm_bindgen]
 impl PositionBasedFluidSimulation {
+    /// Without a `context` the simulation runs headless and is rendered with
+    /// `draw_raster` or `draw_png`
+    ///
     /// # Errors
     /// Will return `Err` if unable to initialize webgl2 context and compile/link shader programs.
     #[wasm_bindgen(constructor)]
     pub fn new(
-        context: WebGl2RenderingContext,
+        context: Option<WebGl2RenderingContext>,
         width: f32,
         height: f32,
         use_dark_colors: bool,
@@ -44,22 +63,36 @@ impl PositionBasedFluidSimulation {
         let x_extent = width * 0.5 / DRAW_SCALE;
         let mut state = pbd_fluid_solver::State::new(x_extent);
         state.init_dam_break(dam_particles_x, dam_particles_y);
-        let renderer = init_webgl(
-            context,
-            width as i32,
-            height as i32,
-            &state.get_boundaries(),
+        let renderer = context
+            .map(|context| {
+                init_webgl(
+                    context,
+                    width as i32,
+                    height as i32,
+                    &state.get_boundaries(),
+                    use_dark_colors,
+                )
+            })
+            .transpose()?;
+        Ok(Self {
+            state,
+            width,
+            height,
             use_dark_colors,
-        )?;
-        Ok(Self { state, renderer })
+            draw_single_color: false,
+            renderer,
+        })
     }
 
     #[wasm_bindgen(setter)]
-    pub fn set_draw_single_color(&self, enabled: bool) {
-        self.renderer.context.uniform1i(
-            Some(&self.renderer.draw_mode_single_color_uniform),
-            enabled.into(),
-        );
+    pub fn set_draw_single_color(&mut self, enabled: bool) {
+        self.draw_single_color = enabled;
+        if let Some(renderer) = &self.renderer {
+            renderer.context.uniform1i(
+                Some(&renderer.draw_mode_single_color_uniform),
+                enabled.into(),
+            );
+        }
     }
 
     #[must_use]
@@ -92,30 +125,33 @@ impl PositionBasedFluidSimulation {
     }
 
     pub fn draw(&self) {
-        self.renderer
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        renderer
             .context
             .clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
 
         // draw boundaries
         set_buffers_and_attributes(
-            &self.renderer.context,
-            &self.renderer.boundary_buffer,
+            &renderer.context,
+            &renderer.boundary_buffer,
             2,
-            self.renderer.position_attrib_location,
+            renderer.position_attrib_location,
         );
-        self.renderer
+        renderer
             .context
-            .uniform1i(Some(&self.renderer.draw_mode_boundary_uniform), 1);
-        self.renderer
+            .uniform1i(Some(&renderer.draw_mode_boundary_uniform), 1);
+        renderer
             .context
             .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 12);
 
         // draw particles
         set_buffers_and_attributes(
-            &self.renderer.context,
-            &self.renderer.particle_buffer,
+            &renderer.context,
+            &renderer.particle_buffer,
             2,
-            self.renderer.position_attrib_location,
+            renderer.position_attrib_location,
         );
         unsafe {
             // Note that `Float32Array::view` is somewhat dangerous (hence the
@@ -132,7 +168,7 @@ impl PositionBasedFluidSimulation {
                 self.state.num_particles * 2,
             ));
 
-            self.renderer
+            renderer
                 .context
                 .buffer_sub_data_with_i32_and_array_buffer_view(
                     WebGl2RenderingContext::ARRAY_BUFFER,
@@ -140,16 +176,52 @@ impl PositionBasedFluidSimulation {
                     &positions_array_buf_view,
                 );
         }
-        self.renderer
+        renderer
             .context
-            .uniform1i(Some(&self.renderer.draw_mode_boundary_uniform), 0);
+            .uniform1i(Some(&renderer.draw_mode_boundary_uniform), 0);
         #[allow(clippy::cast_possible_wrap)]
-        self.renderer.context.draw_arrays(
+        renderer.context.draw_arrays(
             WebGl2RenderingContext::POINTS,
             0,
             self.state.num_particles as i32,
         );
     }
+
+    /// Draws the same frame as `draw` into an RGBA buffer without WebGL
+    pub fn draw_raster(&self, render_buffer: &mut [u8]) {
+        let mut raster = Raster::new(self.width as usize, self.height as usize, render_buffer);
+        raster.clear(if self.use_dark_colors {
+            CLEAR_COLOR_DARK
+        } else {
+            CLEAR_COLOR_LIGHT
+        });
+        // solver origin at the bottom center, y pointing up
+        let origin = vec2(0.5 * self.width, self.height);
+        let c = |x: f32, y: f32| origin + DRAW_SCALE * vec2(x, -y);
+
+        for [x0, x1, y0, y1] in self.state.get_boundaries() {
+            raster.fill_rect(c(x0, y1), c(x1, y0), BOUNDARY_COLOR, 1.0);
+        }
+
+        let positions = &self.state.get_positions()[..self.state.num_particles];
+        for (i, p) in positions.iter().enumerate() {
+            let color = if self.draw_single_color || (i / PARTICLE_COLOR_BLOCK) & 1 == 0 {
+                PARTICLE_COLOR_1
+            } else {
+                PARTICLE_COLOR_2
+            };
+            let p: Vec2 = c(p.x, p.y);
+            raster.fill_disk(p, 0.5 * POINT_SIZE, color);
+        }
+    }
+
+    /// Draws the frame like `draw_raster` and encodes it as PNG
+    #[must_use]
+    pub fn draw_png(&self) -> Vec<u8> {
+        render_png(self.width as usize, self.height as usize, |buffer| {
+            self.draw_raster(buffer);
+        })
+    }
 }
 
 #[allow(clippy::too_many_lines)]
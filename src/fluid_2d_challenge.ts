@@ -1,7 +1,7 @@
 import GUI, { Controller } from 'lil-gui';
 
 import { PositionBasedFluidSimulation } from '../pkg';
-import { Demo, Scene2DWebGL, Scene2DConfig } from './lib';
+import { Demo, Scene2DWebGL, Scene2DConfig, downloadPng } from './lib';
 
 
 const DEFAULT_DAM_SIZE: [number, number] = [10, 1000];
@@ -17,6 +17,7 @@ type PositionBasedFluidDemoProps = {
     reset10x200: () => void;
     reset40x100: () => void;
     reset100x100: () => void;
+    savePng: () => void;
 };
 
 const PositionBasedFluidDemoConfig: Scene2DConfig = {
@@ -81,12 +82,14 @@ class PositionBasedFluidDemo implements Demo<PositionBasedFluidSimulation, Posit
             reset10x200: () => reset(10, 200),
             reset40x100: () => reset(40, 100),
             reset100x100: () => reset(100, 100),
+            savePng: () => downloadPng(this.sim.draw_png(), 'pbf.png'),
         };
         this.particlesController = folder.add(this.props, 'numParticles').name('particles').disable().listen();
         folder.add(this.props, 'viscosity', 0, 0.75, 0.005).onChange((v: number) => this.sim.viscosity = v);
         folder.add(this.props, 'substeps', 5, 10, 1).onChange((v: number) => this.sim.solver_substeps = v);
         folder.add(this.props, 'singleColor').name('draw single color').onFinishChange((v: boolean) => this.sim.draw_single_color = v);
         folder.add(this.props, 'animate');
+        folder.add(this.props, 'savePng').name('save png');
         folder.add(this.props, 'block').name('add block');
         folder.add(this.props, 'reset10x1000').name('reset 10x1000 block');
         folder.add(this.props, 'reset10x200').name('reset 10x200 block');
@@ -2,10 +2,17 @@
 
 use std::f64::consts::PI;
 
-use glam::Vec2;
+use glam::{Vec2, Vec3};
 use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
 
+use crate::boundary::{Boundary, BoundaryEdge, BoundaryKind, InflowProfile};
+use crate::colormap::{Colormap, ColormapKind, ColormapScale};
+use crate::diagnostics::Diagnostics;
+use crate::mac_grid::{Field, MacGrid};
+use crate::pressure::PressureSolverKind;
+use crate::raster::{render_png, Raster};
+use crate::tracer::{streamline, TracerKind, Tracers};
 use crate::util::get_sci_color_255;
 
 const SIM_HEIGHT: f32 = 1.0;
@@ -15,13 +22,30 @@ const DEFAULT_DENSITY: f32 = 1000.0;
 const DEFAULT_GRAVITY_Y: f32 = -9.81;
 const DEFAULT_NUM_ITERS: usize = 40;
 const DEFAULT_OVER_RELAXATION: f32 = 1.9;
+const NUM_VISCOSITY_ITERS: usize = 20;
 const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
 const DEFAULT_RESOLUTION: f32 = 100.0;
 const TUNNEL_INPUT_VELOCITY: f32 = 2.0;
+const TUNNEL_VORTICITY_CONFINEMENT: f32 = 2.0;
+const AIRFOIL_CHORD: f32 = 0.4;
+const AIRFOIL_THICKNESS: f32 = 0.12;
+const AIRFOIL_ANGLE_OF_ATTACK: f32 = 10.0;
+const AIRFOIL_NUM_SEGS: usize = 40;
+const RAYLEIGH_TAYLOR_SMOKE_DENSITY: f32 = 0.1;
+
+const FORCE_HISTORY_LENGTH: usize = 600;
 
 const RENDER_LINE_SCALE: f32 = 0.02;
 const STREAMLINE_RESOLUTION_STEP: usize = 5;
 const STREAMLINE_NUM_SEGS: usize = 15;
+const DEFAULT_MAX_TRACER_POINTS: usize = 300;
+const TRACER_COLOR_HEX: &str = "#FF00FF";
+const TRACER_COLOR: Vec3 = Vec3::new(1.0, 0.0, 1.0);
+const TRACER_LINE_WIDTH: f32 = 2.0;
+const DEFAULT_LIC_LENGTH: f32 = 15.0;
+// the averaged noise has a standard deviation shrinking with the square root
+// of the number of samples, this restores the contrast
+const LIC_CONTRAST: f32 = 0.5;
 
 #[wasm_bindgen(js_name = FluidSceneType)]
 #[derive(PartialEq, Clone, Copy)]
@@ -30,13 +54,131 @@ pub enum SceneType {
     HiresTunnel,
     Tank,
     Paint,
+    Airfoil,
+    RayleighTaylor,
+}
+
+#[wasm_bindgen(js_name = FluidAdvectionScheme)]
+#[derive(PartialEq, Clone, Copy)]
+pub enum AdvectionScheme {
+    SemiLagrangian,
+    MacCormack,
+}
+
+/// Quantity coloring the line integral convolution texture
+#[wasm_bindgen]
+#[derive(PartialEq, Clone, Copy)]
+pub enum LicModulation {
+    None,
+    Speed,
+    Pressure,
+}
+
+/// Ring buffer of the last `capacity` samples. Every sample is stored twice,
+/// `capacity` apart, so the samples are always contiguous and oldest first
+/// without shifting them on each push.
+struct History {
+    samples: Vec<Vec2>,
+    head: usize, // next slot to write
+    len: usize,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: vec![Vec2::ZERO; 2 * capacity],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, sample: Vec2) {
+        let capacity = self.samples.len() / 2;
+        self.samples[self.head] = sample;
+        self.samples[self.head + capacity] = sample;
+        self.head = (self.head + 1) % capacity;
+        self.len = usize::min(self.len + 1, capacity);
+    }
+
+    fn as_slice(&self) -> &[Vec2] {
+        let end = self.head + self.samples.len() / 2;
+        &self.samples[end - self.len..end]
+    }
+}
+
+/// Rigid polygonal obstacle, which may translate and rotate
+struct Obstacle {
+    vertices: Vec<Vec2>, // relative to pos
+    radius: f32,         // bounding circle
+    pos: Vec2,
+    angle: f32,
+    vel: Vec2,
+    omega: f32,
+}
+
+impl Obstacle {
+    fn new(vertices: &[Vec2]) -> Self {
+        let pos = vertices.iter().sum::<Vec2>() / vertices.len() as f32;
+        let vertices: Vec<Vec2> = vertices.iter().map(|&v| v - pos).collect();
+        let radius = vertices.iter().fold(0.0, |r: f32, v| r.max(v.length()));
+        Self {
+            vertices,
+            radius,
+            pos,
+            angle: 0.0,
+            vel: Vec2::ZERO,
+            omega: 0.0,
+        }
+    }
+
+    fn world_vertices(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let rot = Vec2::from_angle(self.angle);
+        self.vertices.iter().map(move |&v| self.pos + rot.rotate(v))
+    }
+
+    /// Even-odd point in polygon test
+    fn contains(&self, p: Vec2) -> bool {
+        let d = p - self.pos;
+        if d.length_squared() > self.radius * self.radius {
+            return false;
+        }
+        let p = Vec2::from_angle(-self.angle).rotate(d);
+        let mut inside = false;
+        let mut prev = self.vertices[self.vertices.len() - 1];
+        for &curr in &self.vertices {
+            if (curr.y > p.y) != (prev.y > p.y)
+                && p.x < prev.x + (p.y - prev.y) / (curr.y - prev.y) * (curr.x - prev.x)
+            {
+                inside = !inside;
+            }
+            prev = curr;
+        }
+        inside
+    }
 }
 
-#[derive(Clone, Copy)]
-enum Field {
-    U,
-    V,
-    S,
+/// Symmetric NACA 4-digit airfoil with the leading edge at the origin
+fn naca_airfoil(chord: f32, thickness: f32, num_segs: usize) -> Vec<Vec2> {
+    let half_thickness = |x: f32| {
+        5.0 * thickness
+            * chord
+            * (0.2969 * x.sqrt() - 0.1260 * x - 0.3516 * x * x + 0.2843 * x * x * x
+                - 0.1036 * x * x * x * x)
+    };
+    // cosine spacing resolves the rounded leading edge
+    let xs: Vec<f32> = (0..=num_segs)
+        .map(|k| 0.5 * (1.0 - f32::cos(std::f32::consts::PI * k as f32 / num_segs as f32)))
+        .collect();
+    let upper = xs
+        .iter()
+        .rev()
+        .map(|&x| Vec2::new(x * chord, half_thickness(x)));
+    let lower = xs
+        .iter()
+        .skip(1)
+        .take(num_segs - 1)
+        .map(|&x| Vec2::new(x * chord, -half_thickness(x)));
+    upper.chain(lower).collect()
 }
 
 #[allow(clippy::struct_excessive_bools)]
@@ -44,46 +186,77 @@ enum Field {
 pub struct FluidSimulation {
     #[wasm_bindgen(readonly)]
     pub density: f32,
-    h: f32,
     gravity: f32,
     #[wasm_bindgen(readonly)]
     pub dt: f32,
     pub num_iters: usize,
     pub over_relaxation: f32,
+    pub pressure_solver: PressureSolverKind,
+    pub advection_scheme: AdvectionScheme,
+    pub vorticity_confinement: f32,
+    pub viscosity: f32,
+    pub smoke_density: f32,
+
+    // force measurement
+    pub reference_velocity: f32,
+    pub reference_length: f32,
+    #[wasm_bindgen(readonly)]
+    pub drag_coefficient: f32,
+    #[wasm_bindgen(readonly)]
+    pub lift_coefficient: f32,
+    force_history: History, // (drag, lift) coefficients
 
     obstacle_pos: Vec2,
+    obstacle_vel: Vec2,
     obstacle_radius: f32,
+    has_obstacle: bool,
+    obstacles: Vec<Obstacle>,
     frame_number: f32, // store as f32 to be used in sin modulation
 
-    num_cells_x: usize,
-    num_cells_y: usize,
-    #[wasm_bindgen(readonly)]
-    pub num_cells: usize,
-    u: Vec<f32>,
-    v: Vec<f32>,
+    grid: MacGrid,
     new_u: Vec<f32>,
     new_v: Vec<f32>,
-    p: Vec<f32>,
-    s: Vec<f32>,
+    walls: Vec<f32>,
+    mask: Vec<bool>,
     m: Vec<f32>,
+    rho: Vec<f32>,
     new_m: Vec<f32>,
+    advected: Vec<f32>,
+    curl: Vec<f32>,
+    diagnostics: Diagnostics,
+    tracers: Tracers,
 
     // rendering
     width: f32,
     height: f32,
     c_scale: f32,
-    context: CanvasRenderingContext2d,
+    context: Option<CanvasRenderingContext2d>,
     pub show_obstacle: bool,
     pub show_streamlines: bool,
     pub show_velocities: bool,
     pub show_pressure: bool,
     pub show_smoke: bool,
-    show_smoke_gradient: bool,
+    pub show_divergence: bool,
+    pub show_forces: bool,
+    pub show_tracers: bool,
+    pub show_lic: bool,
+    pub lic_modulation: LicModulation,
+    pub lic_length: f32,
+    lic_noise: Vec<f32>,
+    lic_colormap: Colormap,
+    pressure_colormap: Colormap,
+    smoke_colormap: Colormap,
 }
 
-fn splat_color(color: &mut [u8; 4], val: f32) {
-    let val = f32::floor(val) as u8;
-    color[0..=2].fill(val);
+/// Reproducible white noise in [0, 1) from an integer hash of `k`
+fn hash_noise(k: usize) -> f32 {
+    let mut x = k as u32;
+    x = (x ^ 61) ^ (x >> 16);
+    x = x.wrapping_mul(9);
+    x ^= x >> 4;
+    x = x.wrapping_mul(0x27d4_eb2d);
+    x ^= x >> 15;
+    (x >> 8) as f32 / (1 << 24) as f32
 }
 
 fn set_color(dest: &mut [u8; 4], src: &[f32; 3]) {
@@ -94,13 +267,15 @@ fn set_color(dest: &mut [u8; 4], src: &[f32; 3]) {
 
 #[wasm_bindgen]
 impl FluidSimulation {
+    /// Without a `context` the simulation runs headless and is rendered with
+    /// `draw_raster` or `draw_png`
     #[must_use]
     #[wasm_bindgen(constructor)]
     pub fn new(
         scene_type: SceneType,
         width: f32,
         height: f32,
-        context: CanvasRenderingContext2d,
+        context: Option<CanvasRenderingContext2d>,
     ) -> FluidSimulation {
         let width = width.floor();
         let height = height.floor();
@@ -119,27 +294,41 @@ impl FluidSimulation {
 
         let mut fluid = Self {
             density: DEFAULT_DENSITY,
-            h,
             gravity: DEFAULT_GRAVITY_Y,
             dt: DEFAULT_TIMESTEP,
             num_iters: DEFAULT_NUM_ITERS,
             over_relaxation: DEFAULT_OVER_RELAXATION,
+            pressure_solver: PressureSolverKind::GaussSeidel,
+            advection_scheme: AdvectionScheme::SemiLagrangian,
+            vorticity_confinement: 0.0,
+            viscosity: 0.0,
+            smoke_density: 0.0,
+
+            reference_velocity: TUNNEL_INPUT_VELOCITY,
+            reference_length: 2.0 * DEFAULT_OBSTACLE_RADIUS,
+            drag_coefficient: 0.0,
+            lift_coefficient: 0.0,
+            force_history: History::new(FORCE_HISTORY_LENGTH),
 
             obstacle_pos: DEFAULT_OBSTACLE_POS,
+            obstacle_vel: Vec2::ZERO,
             obstacle_radius: DEFAULT_OBSTACLE_RADIUS,
+            has_obstacle: false,
+            obstacles: vec![],
             frame_number: 0.0,
 
-            num_cells_x,
-            num_cells_y,
-            num_cells,
-            u: vec![0.0; num_cells],
-            v: vec![0.0; num_cells],
+            grid: MacGrid::new(num_cells_x, num_cells_y, h, BoundaryKind::FreeSlip),
             new_u: vec![0.0; num_cells],
             new_v: vec![0.0; num_cells],
-            p: vec![0.0; num_cells],
-            s: vec![0.0; num_cells],
+            walls: vec![1.0; num_cells],
+            mask: vec![false; num_cells],
             m: vec![1.0; num_cells],
+            rho: vec![DEFAULT_DENSITY; num_cells],
             new_m: vec![0.0; num_cells],
+            advected: vec![0.0; num_cells],
+            curl: vec![0.0; num_cells],
+            diagnostics: Diagnostics::new(num_cells_x, num_cells_y, h),
+            tracers: Tracers::new(DEFAULT_MAX_TRACER_POINTS),
 
             // rendering
             width,
@@ -152,66 +341,108 @@ impl FluidSimulation {
             show_velocities: false,
             show_pressure: false,
             show_smoke: false,
-            show_smoke_gradient: false,
+            show_divergence: false,
+            show_forces: false,
+            show_tracers: true,
+            show_lic: false,
+            lic_modulation: LicModulation::None,
+            lic_length: DEFAULT_LIC_LENGTH,
+            lic_noise: vec![],
+            lic_colormap: Colormap::new(ColormapKind::Viridis, ColormapScale::Linear),
+            pressure_colormap: Colormap::new(ColormapKind::Sci, ColormapScale::Linear),
+            smoke_colormap: Colormap::new(ColormapKind::Gray, ColormapScale::Linear),
         };
 
+        for edge in [
+            BoundaryEdge::Left,
+            BoundaryEdge::Right,
+            BoundaryEdge::Bottom,
+            BoundaryEdge::Top,
+        ] {
+            fluid
+                .grid
+                .boundaries
+                .set_inflow(edge, TUNNEL_INPUT_VELOCITY, InflowProfile::Uniform);
+        }
+
         match scene_type {
             SceneType::Tank => fluid.setup_tank(),
-            SceneType::WindTunnel | SceneType::HiresTunnel => fluid.setup_tunnel(scene_type),
+            SceneType::RayleighTaylor => fluid.setup_rayleigh_taylor(),
+            SceneType::WindTunnel | SceneType::HiresTunnel | SceneType::Airfoil => {
+                fluid.setup_tunnel(scene_type);
+            }
             SceneType::Paint => fluid.setup_paint(),
         }
+        fluid.apply_boundaries();
 
         fluid
     }
 
     fn setup_tank(&mut self) {
-        let n = self.num_cells_y;
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
-                let mut s = 1.0; // fluid
-                if i == 0 || i == self.num_cells_x - 1 || j == 0 {
-                    s = 0.0; // solid
-                }
-                self.s[i * n + j] = s;
-            }
-        }
+        self.grid
+            .boundaries
+            .set(BoundaryEdge::Top, Boundary::new(BoundaryKind::Outflow));
 
         self.show_pressure = true;
         self.show_obstacle = false;
     }
 
-    fn setup_tunnel(&mut self, scene_type: SceneType) {
-        let n = self.num_cells_y;
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
-                let mut s = 1.0; // fluid
-                if i == 0 || j == 0 || j == self.num_cells_y - 1 {
-                    s = 0.0; //solid
-                }
-                self.s[i * n + j] = s;
-                if i == 1 {
-                    self.u[i * n + j] = TUNNEL_INPUT_VELOCITY;
-                }
+    /// A closed tank with heavier smoke above the ambient fluid, which sinks
+    /// in Rayleigh-Taylor fingers
+    fn setup_rayleigh_taylor(&mut self) {
+        let n = self.grid.num_cells_y;
+        for i in 0..self.grid.num_cells_x {
+            for j in self.grid.num_cells_y / 2 + 1..self.grid.num_cells_y {
+                self.m[i * n + j] = 0.0;
             }
         }
+        self.smoke_density = RAYLEIGH_TAYLOR_SMOKE_DENSITY;
 
-        let pipe_height = 0.1 * self.num_cells_y as f32;
-        let min_j = f32::floor(0.5 * self.num_cells_y as f32 - 0.5 * pipe_height) as usize;
-        let max_j = f32::floor(0.5 * self.num_cells_y as f32 + 0.5 * pipe_height) as usize;
+        self.show_smoke = true;
+        self.show_obstacle = false;
+    }
+
+    fn setup_tunnel(&mut self, scene_type: SceneType) {
+        self.grid.boundaries.set(
+            BoundaryEdge::Left,
+            Boundary::inflow(TUNNEL_INPUT_VELOCITY, InflowProfile::Uniform),
+        );
+        self.grid
+            .boundaries
+            .set(BoundaryEdge::Right, Boundary::new(BoundaryKind::Outflow));
+
+        let pipe_height = 0.1 * self.grid.num_cells_y as f32;
+        let min_j = f32::floor(0.5 * self.grid.num_cells_y as f32 - 0.5 * pipe_height) as usize;
+        let max_j = f32::floor(0.5 * self.grid.num_cells_y as f32 + 0.5 * pipe_height) as usize;
 
         for j in min_j..max_j {
             self.m[j] = 0.0; // solid
         }
 
-        self.set_obstacle(Vec2::new(0.4, 0.5), true, false);
+        if scene_type == SceneType::Airfoil {
+            let rot = Vec2::from_angle(-AIRFOIL_ANGLE_OF_ATTACK.to_radians());
+            let vertices: Vec<Vec2> =
+                naca_airfoil(AIRFOIL_CHORD, AIRFOIL_THICKNESS, AIRFOIL_NUM_SEGS)
+                    .iter()
+                    .map(|&v| Vec2::new(0.3, 0.5) + rot.rotate(v))
+                    .collect();
+            self.obstacles.push(Obstacle::new(&vertices));
+            self.reference_length = AIRFOIL_CHORD;
+        } else {
+            self.set_obstacle(Vec2::new(0.4, 0.5), true, false);
+        }
 
         self.gravity = 0.0;
+        self.advection_scheme = AdvectionScheme::MacCormack;
+        self.vorticity_confinement = TUNNEL_VORTICITY_CONFINEMENT;
 
         self.show_smoke = true;
+        self.show_forces = true;
 
         if scene_type == SceneType::HiresTunnel {
             self.dt = 1.0 / 120.0;
             self.num_iters = 100;
+            self.pressure_solver = PressureSolverKind::ConjugateGradient;
 
             self.show_pressure = true;
         }
@@ -223,196 +454,412 @@ impl FluidSimulation {
         self.obstacle_radius = 0.1;
 
         self.show_smoke = true;
-        self.show_smoke_gradient = true;
+        self.smoke_colormap = Colormap::new(ColormapKind::Sci, ColormapScale::Linear);
         self.show_obstacle = false;
     }
 
+    /// Updates the per-cell density, where pure smoke (m = 0) is
+    /// `1 + smoke_density` times as heavy as the ambient fluid
+    fn update_density(&mut self) {
+        for (rho, m) in self.rho.iter_mut().zip(&self.m) {
+            *rho = self.density * (1.0 + self.smoke_density * (1.0 - m));
+        }
+    }
+
     fn integrate(&mut self) {
-        let n = self.num_cells_y;
-        for i in 1..self.num_cells_x {
-            for j in 1..self.num_cells_y - 1 {
-                if self.s[i * n + j] != 0.0 && self.s[i * n + j - 1] != 0.0 {
-                    self.v[i * n + j] += self.gravity * self.dt;
+        let n = self.grid.num_cells_y;
+        for i in 1..self.grid.num_cells_x {
+            for j in 1..self.grid.num_cells_y - 1 {
+                if self.grid.s[i * n + j] != 0.0 && self.grid.s[i * n + j - 1] != 0.0 {
+                    // Boussinesq approximation, the density only varies in the buoyancy
+                    let rho = 0.5 * (self.rho[i * n + j] + self.rho[i * n + j - 1]);
+                    self.grid.v[i * n + j] += self.gravity * self.dt * rho / self.density;
                 }
             }
         }
     }
 
-    fn solve_incompressibility(&mut self) {
-        let n = self.num_cells_y;
-        let cp = self.density * self.h / self.dt;
-        for _ in 0..self.num_iters {
-            for i in 1..self.num_cells_x - 1 {
-                for j in 1..self.num_cells_y - 1 {
-                    if self.s[i * n + j] == 0.0 {
-                        continue;
-                    }
+    /// Implicit viscosity, solves (I - dt nu L) u' = u for both velocity
+    /// components with Gauss-Seidel. Solid faces keep the obstacle velocity.
+    fn apply_viscosity(&mut self) {
+        if self.viscosity == 0.0 {
+            return;
+        }
 
-                    let sx0 = self.s[(i - 1) * n + j];
-                    let sx1 = self.s[(i + 1) * n + j];
-                    let sy0 = self.s[i * n + j - 1];
-                    let sy1 = self.s[i * n + j + 1];
-                    let s = sx0 + sx1 + sy0 + sy1;
-                    if s == 0.0 {
+        let n = self.grid.num_cells_y;
+        let a = self.viscosity * self.dt / (self.grid.h * self.grid.h);
+        let inv_diag = 1.0 / (1.0 + 4.0 * a);
+        self.new_u.copy_from_slice(&self.grid.u);
+        self.new_v.copy_from_slice(&self.grid.v);
+
+        for _ in 0..NUM_VISCOSITY_ITERS {
+            for i in 1..self.grid.num_cells_x - 1 {
+                for j in 1..self.grid.num_cells_y - 1 {
+                    let id = i * n + j;
+                    if self.grid.s[id] == 0.0 {
                         continue;
                     }
-
-                    let div = self.u[(i + 1) * n + j] - self.u[i * n + j] + self.v[i * n + j + 1]
-                        - self.v[i * n + j];
-                    let p = -div / s * self.over_relaxation;
-                    self.p[i * n + j] += cp * p;
-
-                    self.u[i * n + j] -= sx0 * p;
-                    self.u[(i + 1) * n + j] += sx1 * p;
-                    self.v[i * n + j] -= sy0 * p;
-                    self.v[i * n + j + 1] += sy1 * p;
+                    if self.grid.s[id - n] != 0.0 {
+                        let sum = self.grid.u[id - n]
+                            + self.grid.u[id + n]
+                            + self.grid.u[id - 1]
+                            + self.grid.u[id + 1];
+                        self.grid.u[id] = (self.new_u[id] + a * sum) * inv_diag;
+                    }
+                    if self.grid.s[id - 1] != 0.0 {
+                        let sum = self.grid.v[id - n]
+                            + self.grid.v[id + n]
+                            + self.grid.v[id - 1]
+                            + self.grid.v[id + 1];
+                        self.grid.v[id] = (self.new_v[id] + a * sum) * inv_diag;
+                    }
                 }
             }
         }
     }
 
-    fn extrapolate(&mut self) {
-        let n = self.num_cells_y;
-        for i in 0..self.num_cells_x {
-            self.u[i * n] = self.u[i * n + 1];
-            self.u[i * n + n - 1] = self.u[i * n + n - 2];
+    fn apply_vorticity_confinement(&mut self) {
+        if self.vorticity_confinement == 0.0 {
+            return;
         }
-        for j in 0..self.num_cells_y {
-            self.v[j] = self.v[n + j];
-            self.v[(self.num_cells_x - 1) * n + j] = self.v[(self.num_cells_x - 2) * n + j];
+
+        let n = self.grid.num_cells_y;
+        let h1 = 1.0 / self.grid.h;
+
+        // curl at cell centers from central differences of the averaged velocities
+        self.curl.fill(0.0);
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                let dv_dx = (self.grid.v[(i + 1) * n + j] + self.grid.v[(i + 1) * n + j + 1]
+                    - self.grid.v[(i - 1) * n + j]
+                    - self.grid.v[(i - 1) * n + j + 1])
+                    * 0.25
+                    * h1;
+                let du_dy = (self.grid.u[i * n + j + 1] + self.grid.u[(i + 1) * n + j + 1]
+                    - self.grid.u[i * n + j - 1]
+                    - self.grid.u[(i + 1) * n + j - 1])
+                    * 0.25
+                    * h1;
+                self.curl[i * n + j] = dv_dx - du_dy;
+            }
+        }
+
+        // push velocity towards regions of higher vorticity magnitude,
+        // f = eps * h * (N x w) with N the normalized gradient of |w|
+        let scale = self.vorticity_confinement * self.grid.h * self.dt;
+        for i in 2..self.grid.num_cells_x - 2 {
+            for j in 2..self.grid.num_cells_y - 2 {
+                if self.grid.s[i * n + j] == 0.0 {
+                    continue;
+                }
+                let grad = Vec2::new(
+                    self.curl[(i + 1) * n + j].abs() - self.curl[(i - 1) * n + j].abs(),
+                    self.curl[i * n + j + 1].abs() - self.curl[i * n + j - 1].abs(),
+                );
+                let normal = grad.normalize_or_zero();
+                let w = self.curl[i * n + j];
+                let f = Vec2::new(normal.y * w, -normal.x * w) * scale;
+
+                // distribute the cell-centered force to the fluid faces of the cell
+                if self.grid.s[(i - 1) * n + j] != 0.0 {
+                    self.grid.u[i * n + j] += 0.5 * f.x;
+                }
+                if self.grid.s[(i + 1) * n + j] != 0.0 {
+                    self.grid.u[(i + 1) * n + j] += 0.5 * f.x;
+                }
+                if self.grid.s[i * n + j - 1] != 0.0 {
+                    self.grid.v[i * n + j] += 0.5 * f.y;
+                }
+                if self.grid.s[i * n + j + 1] != 0.0 {
+                    self.grid.v[i * n + j + 1] += 0.5 * f.y;
+                }
+            }
         }
     }
 
-    #[must_use]
-    fn sample_field(&self, x: f32, y: f32, field: Field) -> f32 {
-        let n = self.num_cells_y;
-        let h = self.h;
-        let h1 = 1.0 / h;
-        let h2 = 0.5 * h;
-        let x = x.clamp(h, self.num_cells_x as f32 * h);
-        let y = y.clamp(h, self.num_cells_y as f32 * h);
+    fn solve_incompressibility(&mut self) {
+        let cp = self.density * self.grid.h / self.dt;
+        self.grid.solve_incompressibility(
+            self.pressure_solver,
+            self.num_iters,
+            self.over_relaxation,
+            None,
+            |_| true,
+            |_| 0.0,
+        );
+        for p in &mut self.grid.p {
+            *p *= cp;
+        }
+    }
 
-        let mut dx = 0.0;
-        let mut dy = 0.0;
-        let f = match field {
-            Field::U => {
-                dy = h2;
-                &self.u
-            }
-            Field::V => {
-                dx = h2;
-                &self.v
-            }
-            Field::S => {
-                dx = h2;
-                dy = h2;
-                &self.m
+    /// Integrates the pressure and viscous forces on all obstacle cells,
+    /// excluding the domain walls, and records the drag and lift coefficients
+    fn measure_forces(&mut self) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let mu = self.density * self.viscosity;
+
+        let mut force = Vec2::ZERO;
+        for i in 1..self.grid.num_cells_x - 2 {
+            for j in 1..self.grid.num_cells_y - 2 {
+                let id = i * n + j;
+                if self.grid.s[id] != 0.0 || self.walls[id] == 0.0 {
+                    continue;
+                }
+                let wall_vel = Vec2::new(
+                    0.5 * (self.grid.u[id] + self.grid.u[id + n]),
+                    0.5 * (self.grid.v[id] + self.grid.v[id + 1]),
+                );
+                for (nb, normal) in [
+                    (id - n, Vec2::NEG_X),
+                    (id + n, Vec2::X),
+                    (id - 1, Vec2::NEG_Y),
+                    (id + 1, Vec2::Y),
+                ] {
+                    if self.grid.s[nb] == 0.0 {
+                        continue;
+                    }
+                    // pressure acts against the outward normal of each fluid facing side
+                    force -= self.grid.p[nb] * normal * h;
+
+                    // shear from the tangential slip over half a cell
+                    let vel = Vec2::new(
+                        0.5 * (self.grid.u[nb] + self.grid.u[nb + n]),
+                        0.5 * (self.grid.v[nb] + self.grid.v[nb + 1]),
+                    );
+                    let slip = vel - wall_vel;
+                    let tangential = slip - normal * slip.dot(normal);
+                    force += mu * tangential / (0.5 * h) * h;
+                }
             }
-        };
-
-        let x0 = f32::min(f32::floor((x - dx) * h1), (self.num_cells_x - 1) as f32) as usize;
-        let tx = ((x - dx) - x0 as f32 * h) * h1;
-        let x1 = usize::min(x0 + 1, self.num_cells_x - 1);
+        }
 
-        let y0 = f32::min(f32::floor((y - dy) * h1), (self.num_cells_y - 1) as f32) as usize;
-        let ty = ((y - dy) - y0 as f32 * h) * h1;
-        let y1 = usize::min(y0 + 1, self.num_cells_y - 1);
+        let q = 0.5 * self.density * self.reference_velocity.powi(2) * self.reference_length;
+        if q > 0.0 {
+            self.drag_coefficient = force.x / q;
+            self.lift_coefficient = force.y / q;
+        }
 
-        let sx = 1.0 - tx;
-        let sy = 1.0 - ty;
+        self.force_history
+            .push(Vec2::new(self.drag_coefficient, self.lift_coefficient));
+    }
 
-        sx * sy * f[x0 * n + y0]
-            + tx * sy * f[x1 * n + y0]
-            + tx * ty * f[x1 * n + y1]
-            + sx * ty * f[x0 * n + y1]
+    #[must_use]
+    fn field(&self, field: Field) -> &[f32] {
+        match field {
+            Field::U => &self.grid.u,
+            Field::V => &self.grid.v,
+            Field::Center => &self.m,
+        }
     }
 
     #[must_use]
-    fn avg_u(&self, i: usize, j: usize) -> f32 {
-        let n = self.num_cells_y;
-        (self.u[i * n + j - 1]
-            + self.u[i * n + j]
-            + self.u[(i + 1) * n + j - 1]
-            + self.u[(i + 1) * n + j])
-            * 0.25
+    fn velocity(&self, p: Vec2) -> Vec2 {
+        Vec2::new(self.grid.sample_u(p.x, p.y), self.grid.sample_v(p.x, p.y))
     }
 
+    /// Wraps `p` around periodic boundaries, or returns `None` if it lies
+    /// outside of the fluid
     #[must_use]
-    fn avg_v(&self, i: usize, j: usize) -> f32 {
-        let n = self.num_cells_y;
-        (self.v[(i - 1) * n + j]
-            + self.v[i * n + j]
-            + self.v[(i - 1) * n + j + 1]
-            + self.v[i * n + j + 1])
-            * 0.25
+    fn constrain_tracer(&self, p: Vec2) -> Option<Vec2> {
+        let h = self.grid.h;
+        let (x, y) = self.grid.boundaries.wrap(p.x, p.y, h);
+        let i = f32::floor(x / h);
+        let j = f32::floor(y / h);
+        if i < 1.0
+            || j < 1.0
+            || i >= (self.grid.num_cells_x - 1) as f32
+            || j >= (self.grid.num_cells_y - 1) as f32
+            || self.grid.s[i as usize * self.grid.num_cells_y + j as usize] == 0.0
+        {
+            return None;
+        }
+        Some(Vec2::new(x, y))
     }
 
-    fn advect_vel(&mut self) {
-        self.new_u.copy_from_slice(&self.u);
-        self.new_v.copy_from_slice(&self.v);
+    fn advect_tracers(&mut self) {
+        if self.tracers.is_empty() {
+            return;
+        }
+        let mut tracers = std::mem::replace(&mut self.tracers, Tracers::new(0));
+        tracers.step(self.dt, |p| self.velocity(p), |p| self.constrain_tracer(p));
+        self.tracers = tracers;
+    }
 
-        let dt = self.dt;
-        let n = self.num_cells_y;
-        let h = self.h;
-        let h2 = 0.5 * h;
+    #[must_use]
+    fn sample_field(&self, x: f32, y: f32, field: Field) -> f32 {
+        self.grid.sample(self.field(field), x, y, field)
+    }
 
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
-                // u component
-                if self.s[i * n + j] != 0.0 && self.s[(i - 1) * n + j] != 0.0 && j < n - 1 {
-                    let mut x = i as f32 * h;
-                    let mut y = j as f32 * h + h2;
-                    let mut u = self.u[i * n + j];
-                    let v = self.avg_v(i, j);
-                    x -= dt * u;
-                    y -= dt * v;
-                    u = self.sample_field(x, y, Field::U);
-                    self.new_u[i * n + j] = u;
+    /// Returns the sample position and velocity of the `field` value stored at
+    /// cell (i, j), or `None` if that value is not advected
+    #[must_use]
+    fn advection_origin(&self, field: Field, i: usize, j: usize) -> Option<(Vec2, Vec2)> {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let h2 = 0.5 * h;
+        match field {
+            Field::U => {
+                if i > 0
+                    && self.grid.s[i * n + j] != 0.0
+                    && self.grid.s[(i - 1) * n + j] != 0.0
+                    && j < n - 1
+                {
+                    Some((
+                        Vec2::new(i as f32 * h, j as f32 * h + h2),
+                        Vec2::new(self.grid.u[i * n + j], self.grid.avg_v(i, j)),
+                    ))
+                } else {
+                    None
                 }
-                // v component
-                if self.s[i * n + j] != 0.0
-                    && self.s[i * n + j - 1] != 0.0
-                    && i < self.num_cells_x - 1
+            }
+            Field::V => {
+                if j > 0
+                    && self.grid.s[i * n + j] != 0.0
+                    && self.grid.s[i * n + j - 1] != 0.0
+                    && i < self.grid.num_cells_x - 1
+                {
+                    Some((
+                        Vec2::new(i as f32 * h + h2, j as f32 * h),
+                        Vec2::new(self.grid.avg_u(i, j), self.grid.v[i * n + j]),
+                    ))
+                } else {
+                    None
+                }
+            }
+            Field::Center => {
+                if i > 0
+                    && i < self.grid.num_cells_x - 1
+                    && j > 0
+                    && j < n - 1
+                    && self.grid.s[i * n + j] != 0.0
                 {
-                    let mut x = i as f32 * h + h2;
-                    let mut y = j as f32 * h;
-                    let u = self.avg_u(i, j);
-                    let mut v = self.v[i * n + j];
-                    x -= dt * u;
-                    y -= dt * v;
-                    v = self.sample_field(x, y, Field::V);
-                    self.new_v[i * n + j] = v;
+                    Some((
+                        Vec2::new(i as f32 * h + h2, j as f32 * h + h2),
+                        Vec2::new(
+                            (self.grid.u[i * n + j] + self.grid.u[(i + 1) * n + j]) * 0.5,
+                            (self.grid.v[i * n + j] + self.grid.v[i * n + j + 1]) * 0.5,
+                        ),
+                    ))
+                } else {
+                    None
                 }
             }
         }
-
-        self.u.copy_from_slice(&self.new_u);
-        self.v.copy_from_slice(&self.new_v);
     }
 
-    fn advect_smoke(&mut self) {
-        self.new_m.copy_from_slice(&self.m);
+    fn new_field(&mut self, field: Field) -> &mut [f32] {
+        match field {
+            Field::U => &mut self.new_u,
+            Field::V => &mut self.new_v,
+            Field::Center => &mut self.new_m,
+        }
+    }
 
+    /// Advects `field` into its `new_*` buffer, which must hold a copy of the
+    /// field beforehand so that values which are not advected are kept.
+    fn advect_field(&mut self, field: Field) {
         let dt = self.dt;
-        let n = self.num_cells_y;
-        let h = self.h;
-        let h2 = 0.5 * h;
+        let n = self.grid.num_cells_y;
+
+        // semi-Lagrangian step, trace backwards and sample the old field
+        for i in 0..self.grid.num_cells_x {
+            for j in 0..self.grid.num_cells_y {
+                if let Some((pos, vel)) = self.advection_origin(field, i, j) {
+                    let pos = pos - dt * vel;
+                    let val = self.sample_field(pos.x, pos.y, field);
+                    self.new_field(field)[i * n + j] = val;
+                }
+            }
+        }
 
-        for i in 1..self.num_cells_x - 1 {
-            for j in 1..self.num_cells_y - 1 {
-                if self.s[i * n + j] != 0.0 {
-                    let u = (self.u[i * n + j] + self.u[(i + 1) * n + j]) * 0.5;
-                    let v = (self.v[i * n + j] + self.v[i * n + j + 1]) * 0.5;
-                    let x = i as f32 * h + h2 - dt * u;
-                    let y = j as f32 * h + h2 - dt * v;
+        if self.advection_scheme != AdvectionScheme::MacCormack {
+            return;
+        }
 
-                    self.new_m[i * n + j] = self.sample_field(x, y, Field::S);
-                }
+        // MacCormack correction: advect the result back in time, and use half
+        // of the round-trip error to correct the forward step
+        let mut advected = std::mem::take(&mut self.advected);
+        advected.copy_from_slice(self.new_field(field));
+        for i in 0..self.grid.num_cells_x {
+            for j in 0..self.grid.num_cells_y {
+                let Some((pos, vel)) = self.advection_origin(field, i, j) else {
+                    continue;
+                };
+                let f = self.field(field);
+                let ahead = pos + dt * vel;
+                let backward = self.grid.sample(&advected, ahead.x, ahead.y, field);
+                let val = advected[i * n + j] + 0.5 * (f[i * n + j] - backward);
+
+                // clamp to the values used by the semi-Lagrangian sample to
+                // prevent over- and undershoots
+                let back = pos - dt * vel;
+                let (ids, _) = self.grid.sample_stencil(back.x, back.y, field);
+                let min = ids.iter().map(|&id| f[id]).fold(f32::MAX, f32::min);
+                let max = ids.iter().map(|&id| f[id]).fold(f32::MIN, f32::max);
+                self.new_field(field)[i * n + j] = val.clamp(min, max);
             }
         }
+        self.advected = advected;
+    }
+
+    fn advect_vel(&mut self) {
+        self.new_u.copy_from_slice(&self.grid.u);
+        self.new_v.copy_from_slice(&self.grid.v);
+
+        self.advect_field(Field::U);
+        self.advect_field(Field::V);
+
+        self.grid.u.copy_from_slice(&self.new_u);
+        self.grid.v.copy_from_slice(&self.new_v);
+    }
+
+    fn advect_smoke(&mut self) {
+        self.new_m.copy_from_slice(&self.m);
+        self.advect_field(Field::Center);
         self.m.copy_from_slice(&self.new_m);
     }
 
+    /// Rebuilds the solid cells from the domain walls, the obstacle mask and
+    /// all obstacles, and sets the velocities of solid faces to the velocity
+    /// of the obstacle they belong to
+    fn update_solids(&mut self) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let r = self.obstacle_radius;
+
+        self.grid.s.copy_from_slice(&self.walls);
+
+        for i in 1..self.grid.num_cells_x - 2 {
+            for j in 1..self.grid.num_cells_y - 2 {
+                let center = Vec2::new((i as f32 + 0.5) * h, (j as f32 + 0.5) * h);
+                // linear velocity, angular velocity and center of rotation
+                let motion =
+                    if self.has_obstacle && center.distance_squared(self.obstacle_pos) < r * r {
+                        Some((self.obstacle_vel, 0.0, self.obstacle_pos))
+                    } else if let Some(o) = self.obstacles.iter().find(|o| o.contains(center)) {
+                        Some((o.vel, o.omega, o.pos))
+                    } else if self.mask[i * n + j] {
+                        Some((Vec2::ZERO, 0.0, Vec2::ZERO))
+                    } else {
+                        None
+                    };
+                let Some((vel, omega, c)) = motion else {
+                    continue;
+                };
+                let vel_at = |x: f32, y: f32| vel + omega * (Vec2::new(x, y) - c).perp();
+
+                let (x, y) = (i as f32 * h, j as f32 * h);
+                self.grid.s[i * n + j] = 0.0;
+                self.m[i * n + j] = 1.0;
+                self.grid.u[i * n + j] = vel_at(x, y + 0.5 * h).x;
+                self.grid.u[(i + 1) * n + j] = vel_at(x + h, y + 0.5 * h).x;
+                self.grid.v[i * n + j] = vel_at(x + 0.5 * h, y).y;
+                self.grid.v[i * n + (j + 1)] = vel_at(x + 0.5 * h, y + h).y;
+            }
+        }
+    }
+
     fn set_obstacle(&mut self, pos: Vec2, reset: bool, modulate: bool) {
         let mut v = Vec2::ZERO;
 
@@ -421,32 +868,342 @@ impl FluidSimulation {
         }
 
         self.obstacle_pos = pos;
-        let r = self.obstacle_radius;
-        let n = self.num_cells_y;
-        let h = self.h;
-
-        for i in 1..self.num_cells_x - 2 {
-            for j in 1..self.num_cells_y - 2 {
-                self.s[i * n + j] = 1.0;
-                let dx = (i as f32 + 0.5) * h - pos.x;
-                let dy = (j as f32 + 0.5) * h - pos.y;
-
-                if dx * dx + dy * dy < r * r {
-                    self.s[i * n + j] = 0.0;
-                    self.m[i * n + j] = if modulate {
-                        0.5 + 0.5 * f32::sin(0.1 * self.frame_number)
-                    } else {
-                        1.0
-                    };
-                    self.u[i * n + j] = v.x;
-                    self.u[(i + 1) * n + j] = v.x;
-                    self.v[i * n + j] = v.y;
-                    self.v[i * n + (j + 1)] = v.y;
+        self.obstacle_vel = v;
+        self.has_obstacle = true;
+        self.update_solids();
+
+        if modulate {
+            let r = self.obstacle_radius;
+            let n = self.grid.num_cells_y;
+            let h = self.grid.h;
+            for i in 1..self.grid.num_cells_x - 2 {
+                for j in 1..self.grid.num_cells_y - 2 {
+                    let dx = (i as f32 + 0.5) * h - pos.x;
+                    let dy = (j as f32 + 0.5) * h - pos.y;
+
+                    if dx * dx + dy * dy < r * r {
+                        self.m[i * n + j] = 0.5 + 0.5 * f32::sin(0.1 * self.frame_number);
+                    }
                 }
             }
         }
     }
 
+    /// Applies the boundary conditions to the domain walls, the solid cells
+    /// and the boundary velocities
+    fn apply_boundaries(&mut self) {
+        self.grid.boundaries.set_ghost_cells(&mut self.walls);
+        self.update_solids();
+        self.grid
+            .boundaries
+            .apply_velocity(&mut self.grid.u, &mut self.grid.v);
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_colormap(&self) -> Colormap {
+        self.pressure_colormap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_pressure_colormap(&mut self, colormap: Colormap) {
+        self.pressure_colormap = colormap;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn smoke_colormap(&self) -> Colormap {
+        self.smoke_colormap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_smoke_colormap(&mut self, colormap: Colormap) {
+        self.smoke_colormap = colormap;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn lic_colormap(&self) -> Colormap {
+        self.lic_colormap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_lic_colormap(&mut self, colormap: Colormap) {
+        self.lic_colormap = colormap;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_cells(&self) -> usize {
+        self.grid.num_cells
+    }
+
+    #[must_use]
+    pub fn boundary(&self, edge: BoundaryEdge) -> BoundaryKind {
+        self.grid.boundaries.get(edge).kind
+    }
+
+    /// Changes the boundary condition of a domain edge, see `Boundaries::set`
+    /// for how periodic edges pair up
+    pub fn set_boundary(&mut self, edge: BoundaryEdge, kind: BoundaryKind) {
+        let boundary = Boundary {
+            kind,
+            ..*self.grid.boundaries.get(edge)
+        };
+        self.grid.boundaries.set(edge, boundary);
+        self.apply_boundaries();
+    }
+
+    #[must_use]
+    pub fn inflow_speed(&self, edge: BoundaryEdge) -> f32 {
+        self.grid.boundaries.get(edge).inflow_speed
+    }
+
+    #[must_use]
+    pub fn inflow_profile(&self, edge: BoundaryEdge) -> InflowProfile {
+        self.grid.boundaries.get(edge).inflow_profile
+    }
+
+    /// Sets the speed into the domain and the profile used if the edge is an
+    /// inflow
+    pub fn set_inflow(&mut self, edge: BoundaryEdge, speed: f32, profile: InflowProfile) {
+        self.grid.boundaries.set_inflow(edge, speed, profile);
+        self.apply_boundaries();
+    }
+
+    /// Adds a static polygonal obstacle from flat x, y vertex coordinates in
+    /// simulation space and returns its id
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `vertices` does not hold at least three x, y pairs.
+    pub fn add_polygon_obstacle(&mut self, vertices: &[f32]) -> Result<usize, JsValue> {
+        if vertices.len() < 6 || !vertices.len().is_multiple_of(2) {
+            return Err(JsValue::from_str(
+                "polygon needs at least three vertices of two coordinates each",
+            ));
+        }
+        let vertices: Vec<Vec2> = vertices
+            .chunks_exact(2)
+            .map(|v| Vec2::new(v[0], v[1]))
+            .collect();
+        self.obstacles.push(Obstacle::new(&vertices));
+        self.update_solids();
+        Ok(self.obstacles.len() - 1)
+    }
+
+    /// Moves obstacle `id` so that its center, the mean of its vertices, is at
+    /// (x, y) and rotates it by `angle` radians from its initial orientation.
+    /// Unless `reset` is set, the obstacle imparts the implied linear and
+    /// angular velocities on the fluid.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `id` is not an obstacle id.
+    pub fn set_polygon_obstacle(
+        &mut self,
+        id: usize,
+        x: f32,
+        y: f32,
+        angle: f32,
+        reset: bool,
+    ) -> Result<(), JsValue> {
+        if id >= self.obstacles.len() {
+            return Err(JsValue::from_str("unknown obstacle"));
+        }
+        let dt = self.dt;
+        let o = &mut self.obstacles[id];
+        let pos = Vec2::new(x, y);
+        if reset {
+            o.vel = Vec2::ZERO;
+            o.omega = 0.0;
+        } else {
+            o.vel = (pos - o.pos) / dt;
+            o.omega = (angle - o.angle) / dt;
+        }
+        o.pos = pos;
+        o.angle = angle;
+        self.update_solids();
+        Ok(())
+    }
+
+    /// Marks cells as solid from an RGBA buffer of the canvas size, the
+    /// format of `draw_buffer`. Opaque dark pixels are solid.
+    pub fn set_obstacle_mask(&mut self, mask: &[u8]) {
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let width = self.width as usize;
+        let height = self.height as usize;
+
+        self.mask.fill(false);
+        for i in 1..self.grid.num_cells_x - 2 {
+            for j in 1..self.grid.num_cells_y - 2 {
+                let x = self.c_x((i as f32 + 0.5) * h);
+                let y = self.c_y((j as f32 + 0.5) * h);
+                if x < 0.0 || y < 0.0 || x as usize >= width || y as usize >= height {
+                    continue;
+                }
+                let p = 4 * (y as usize * width + x as usize);
+                if p + 4 > mask.len() {
+                    continue;
+                }
+                let lum =
+                    (u32::from(mask[p]) + u32::from(mask[p + 1]) + u32::from(mask[p + 2])) / 3;
+                self.mask[i * n + j] = mask[p + 3] >= 128 && lum < 128;
+            }
+        }
+        self.update_solids();
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+        self.mask.fill(false);
+        self.update_solids();
+    }
+
+    /// Width and height of the simulation domain, including the ghost cells
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn domain_size(&self) -> Vec<f32> {
+        vec![
+            self.grid.num_cells_x as f32 * self.grid.h,
+            self.grid.num_cells_y as f32 * self.grid.h,
+        ]
+    }
+
+    /// Traces the streamline from (x, y) through the current velocity field
+    /// with `num_segs` segments of total arc `length`, upstream for negative
+    /// lengths, and returns its points as consecutive x, y values
+    #[must_use]
+    pub fn streamline(&self, x: f32, y: f32, length: f32, num_segs: usize) -> Vec<f32> {
+        let seg_len = length / num_segs.max(1) as f32;
+        streamline(
+            Vec2::new(x, y),
+            seg_len,
+            num_segs,
+            |p| self.velocity(p),
+            |p| self.constrain_tracer(p),
+        )
+        .iter()
+        .flat_map(Vec2::to_array)
+        .collect()
+    }
+
+    /// Adds a streakline, which releases a tracer particle at (x, y) every
+    /// step, and returns its id
+    pub fn add_streakline(&mut self, x: f32, y: f32) -> usize {
+        self.tracers.add(TracerKind::Streakline, Vec2::new(x, y))
+    }
+
+    /// Adds a pathline, the trajectory of a single tracer particle released
+    /// at (x, y), and returns its id
+    pub fn add_pathline(&mut self, x: f32, y: f32) -> usize {
+        self.tracers.add(TracerKind::Pathline, Vec2::new(x, y))
+    }
+
+    pub fn clear_tracers(&mut self) {
+        self.tracers.clear();
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_tracers(&self) -> usize {
+        self.tracers.len()
+    }
+
+    /// Points of tracer `id` as consecutive x, y values, streaklines start at
+    /// their seed and pathlines end at the current particle position. Empty
+    /// if `id` is not a tracer id.
+    #[must_use]
+    pub fn tracer_polyline(&self, id: usize) -> Vec<f32> {
+        if id >= self.tracers.len() {
+            return vec![];
+        }
+        self.tracers.points(id).flat_map(|p| p.to_array()).collect()
+    }
+
+    /// Number of points after which the oldest tracer particles are dropped
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn max_tracer_points(&self) -> usize {
+        self.tracers.max_points
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_tracer_points(&mut self, max_points: usize) {
+        self.tracers.max_points = max_points;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn force_history(&self) -> *const Vec2 {
+        // Generally, this is unsafe! We take care in JS to make sure to
+        // query the history array pointer after each step. The history is
+        // allocated with its full capacity, so it does not move, but the
+        // start of its oldest-first window does.
+        // The history is a slice of Vec2s, which is a linear array of f32s in
+        // memory.
+        self.force_history.as_slice().as_ptr()
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn force_history_len(&self) -> usize {
+        self.force_history.len
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn num_obstacles(&self) -> usize {
+        self.obstacles.len()
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_iters(&self) -> usize {
+        self.grid.pressure.num_iters
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_residual(&self) -> f32 {
+        self.grid.pressure.residual
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn pressure_tolerance(&self) -> f32 {
+        self.grid.pressure.tolerance
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_pressure_tolerance(&mut self, tolerance: f32) {
+        self.grid.pressure.tolerance = tolerance;
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn max_divergence(&self) -> f32 {
+        self.diagnostics.max_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn mean_divergence(&self) -> f32 {
+        self.diagnostics.mean_divergence
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn kinetic_energy(&self) -> f32 {
+        self.diagnostics.kinetic_energy
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn total_mass(&self) -> f32 {
+        self.diagnostics.total_mass
+    }
+
     pub fn set_obstacle_from_canvas(&mut self, c_x: f32, c_y: f32, reset: bool, modulate: bool) {
         let x = c_x / self.c_scale;
         let y = (self.height - c_y) / self.c_scale;
@@ -467,29 +1224,43 @@ impl FluidSimulation {
     }
 
     pub fn draw_buffer(&mut self, render_buffer: &mut [u8]) {
-        let h = self.h;
+        if self.show_lic {
+            self.draw_lic(render_buffer);
+            return;
+        }
+        let h = self.grid.h;
         let cx = f32::floor(self.c_scale * h) as usize + 1;
         let cy = f32::floor(self.c_scale * h) as usize + 1;
-        let n = self.num_cells_y;
+        let n = self.grid.num_cells_y;
 
         let mut color = [255; 4];
 
-        let mut p_min = self.p[0];
-        let mut p_max = self.p[0];
+        let mut p_min = self.grid.p[0];
+        let mut p_max = self.grid.p[0];
         if self.show_pressure {
-            for i in 0..self.num_cells {
-                p_min = f32::min(p_min, self.p[i]);
-                p_max = f32::max(p_max, self.p[i]);
+            for i in 0..self.grid.num_cells {
+                p_min = f32::min(p_min, self.grid.p[i]);
+                p_max = f32::max(p_max, self.grid.p[i]);
             }
         }
 
-        for i in 0..self.num_cells_x {
-            for j in 0..self.num_cells_y {
+        let div_max = self.diagnostics.max_divergence;
+
+        for i in 0..self.grid.num_cells_x {
+            for j in 0..self.grid.num_cells_y {
                 let ind = i * n + j;
-                if self.show_pressure {
-                    let p = self.p[ind];
+                if self.show_divergence {
+                    if self.grid.s[ind] == 0.0 {
+                        color[0..=2].fill(0);
+                    } else {
+                        let div = self.diagnostics.div[ind];
+                        let sci_color = get_sci_color_255(div, -div_max, div_max);
+                        set_color(&mut color, &sci_color);
+                    }
+                } else if self.show_pressure {
+                    let p = self.grid.p[ind];
                     let s = self.m[ind];
-                    let sci_color = get_sci_color_255(p, p_min, p_max);
+                    let sci_color = self.pressure_colormap.color_255(p, p_min, p_max);
                     if self.show_smoke {
                         set_color(
                             &mut color,
@@ -504,15 +1275,14 @@ impl FluidSimulation {
                     }
                 } else if self.show_smoke {
                     let s = self.m[ind];
-                    if self.show_smoke_gradient {
-                        let sci_color = get_sci_color_255(s, 0.0, 1.0);
-                        set_color(&mut color, &sci_color);
-                    } else {
-                        splat_color(&mut color, 255.0 * s);
-                    }
-                } else if self.s[ind] == 0.0 {
+                    let color_255 = self.smoke_colormap.color_255(s, 0.0, 1.0);
+                    set_color(&mut color, &color_255);
+                } else if self.grid.s[ind] == 0.0 {
                     color[0..=2].fill(0);
                 }
+                if self.show_obstacle && self.mask[ind] {
+                    color[0..=2].fill(128);
+                }
                 let x = f32::floor(self.c_x((i as f32 - 1.0) * h)) as usize;
                 let y = f32::floor(self.c_y((j as f32 + 1.0) * h)) as usize;
                 for yi in y..y + cy {
@@ -530,20 +1300,22 @@ impl FluidSimulation {
     }
 
     pub fn draw_canvas(&mut self) {
-        let c = &self.context;
+        let Some(c) = &self.context else {
+            return;
+        };
         let black_hex: JsValue = JsValue::from("#000000");
         let grey_hex: JsValue = JsValue::from("#DDDDDD");
         let white_hex: JsValue = JsValue::from("#FFFFFF");
 
         if self.show_velocities {
             c.set_stroke_style(&black_hex);
-            let h = self.h;
-            let n = self.num_cells_y;
+            let h = self.grid.h;
+            let n = self.grid.num_cells_y;
 
-            for i in 0..self.num_cells_x {
-                for j in 0..self.num_cells_y {
-                    let u = self.u[i * n + j];
-                    let v = self.v[i * n + j];
+            for i in 0..self.grid.num_cells_x {
+                for j in 0..self.grid.num_cells_y {
+                    let u = self.grid.u[i * n + j];
+                    let v = self.grid.v[i * n + j];
 
                     let i = i as f32;
                     let j = j as f32;
@@ -568,35 +1340,60 @@ impl FluidSimulation {
         }
 
         if self.show_streamlines {
-            let seg_len = self.h * RENDER_LINE_SCALE;
             c.set_stroke_style(&black_hex);
 
-            for i in (1..(self.num_cells_x - 1)).step_by(STREAMLINE_RESOLUTION_STEP) {
-                for j in (1..(self.num_cells_y - 1)).step_by(STREAMLINE_RESOLUTION_STEP) {
-                    let mut x = (i as f32 + 0.5) * self.h;
-                    let mut y = (j as f32 + 0.5) * self.h;
-                    c.begin_path();
-                    c.move_to(self.c_x(x).into(), self.c_y(y).into());
-                    for _ in 0..STREAMLINE_NUM_SEGS {
-                        let u = self.sample_field(x, y, Field::U);
-                        let v = self.sample_field(x, y, Field::V);
-                        let l = f32::sqrt(u * u + v * v);
-                        x += u / l * seg_len;
-                        y += v / l * seg_len;
-                        x += u * 0.01;
-                        y += v * 0.01;
-                        if x > self.num_cells_x as f32 * self.h {
-                            break;
-                        }
-                        c.line_to(self.c_x(x).into(), self.c_y(y).into());
+            for line in self.streamlines() {
+                c.begin_path();
+                for (k, p) in line.iter().enumerate() {
+                    if k == 0 {
+                        c.move_to(self.c_x(p.x).into(), self.c_y(p.y).into());
+                    } else {
+                        c.line_to(self.c_x(p.x).into(), self.c_y(p.y).into());
                     }
-                    c.stroke();
                 }
+                c.stroke();
             }
         }
 
+        if self.show_tracers {
+            c.set_stroke_style(&JsValue::from(TRACER_COLOR_HEX));
+            c.set_line_width(TRACER_LINE_WIDTH.into());
+            for id in 0..self.tracers.len() {
+                c.begin_path();
+                for (k, p) in self.tracers.points(id).enumerate() {
+                    if k == 0 {
+                        c.move_to(self.c_x(p.x).into(), self.c_y(p.y).into());
+                    } else {
+                        c.line_to(self.c_x(p.x).into(), self.c_y(p.y).into());
+                    }
+                }
+                c.stroke();
+            }
+            c.set_line_width(1.0);
+        }
+
         if self.show_obstacle {
-            let r = self.obstacle_radius + self.h;
+            c.set_fill_style(&white_hex);
+            c.set_stroke_style(&black_hex);
+            c.set_line_width(3.0);
+            for o in &self.obstacles {
+                c.begin_path();
+                for (k, v) in o.world_vertices().enumerate() {
+                    if k == 0 {
+                        c.move_to(self.c_x(v.x).into(), self.c_y(v.y).into());
+                    } else {
+                        c.line_to(self.c_x(v.x).into(), self.c_y(v.y).into());
+                    }
+                }
+                c.close_path();
+                c.fill();
+                c.stroke();
+            }
+            c.set_line_width(1.0);
+        }
+
+        if self.show_obstacle && self.has_obstacle {
+            let r = self.obstacle_radius + self.grid.h;
             let o = self.obstacle_pos;
             if self.show_pressure {
                 c.set_stroke_style(&black_hex);
@@ -632,17 +1429,339 @@ impl FluidSimulation {
             c.stroke();
             c.set_line_width(1.0);
         }
+
+        if self.show_forces && self.force_history.len > 0 {
+            self.draw_force_history();
+        }
+    }
+
+    /// Draws a line integral convolution of the velocity field, white noise
+    /// averaged along the streamline through every pixel over `lic_length`
+    /// pixels in both directions, colored by `lic_modulation`
+    fn draw_lic(&mut self, render_buffer: &mut [u8]) {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        if self.lic_noise.len() != width * height {
+            self.lic_noise = (0..width * height).map(hash_noise).collect();
+        }
+
+        let n = self.grid.num_cells_y;
+        let h = self.grid.h;
+        let c_scale = self.c_scale;
+        // pixels are shifted by one cell like the cells of `draw_buffer`
+        let to_sim = |px: Vec2| Vec2::new(px.x / c_scale + h, (self.height - px.y) / c_scale);
+        let cell = |p: Vec2| {
+            let i = usize::min(f32::max(p.x / h, 0.0) as usize, self.grid.num_cells_x - 1);
+            let j = usize::min(f32::max(p.y / h, 0.0) as usize, n - 1);
+            i * n + j
+        };
+        // unit step in pixels along the flow, y points down on the canvas
+        let dir = |px: Vec2| {
+            let v = self.velocity(to_sim(px));
+            Vec2::new(v.x, -v.y).normalize_or_zero()
+        };
+
+        let (q_min, q_max) = match self.lic_modulation {
+            LicModulation::None => (0.0, 1.0),
+            LicModulation::Speed => (0.0, self.max_speed()),
+            LicModulation::Pressure => self
+                .grid
+                .p
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), &p| {
+                    (min.min(p), max.max(p))
+                }),
+        };
+
+        let num_steps = f32::max(self.lic_length, 0.0) as usize;
+        for py in 0..height {
+            for px in 0..width {
+                let p0 = Vec2::new(px as f32 + 0.5, py as f32 + 0.5);
+                let id = cell(to_sim(p0));
+                let offset = 4 * (py * width + px);
+                let color = if self.show_obstacle && self.mask[id] {
+                    Vec3::splat(0.5)
+                } else if self.grid.s[id] == 0.0 {
+                    Vec3::ZERO
+                } else {
+                    let mut sum = self.lic_noise[py * width + px];
+                    let mut count = 1;
+                    for sign in [1.0, -1.0] {
+                        let mut p = p0;
+                        for _ in 0..num_steps {
+                            let d = dir(p);
+                            if d == Vec2::ZERO {
+                                break;
+                            }
+                            p += sign * d;
+                            if p.x < 0.0 || p.y < 0.0 || p.x >= self.width || p.y >= self.height {
+                                break;
+                            }
+                            sum += self.lic_noise[p.y as usize * width + p.x as usize];
+                            count += 1;
+                        }
+                    }
+                    let mean = sum / count as f32;
+                    let g = 0.5 + (mean - 0.5) * LIC_CONTRAST * (count as f32).sqrt();
+                    let g = g.clamp(0.0, 1.0);
+                    match self.lic_modulation {
+                        LicModulation::None => Vec3::splat(g),
+                        LicModulation::Speed => {
+                            let speed = self.velocity(to_sim(p0)).length();
+                            self.lic_colormap.color(speed, q_min, q_max) * (0.5 + g)
+                        }
+                        LicModulation::Pressure => {
+                            let pressure = self.grid.p[id];
+                            self.lic_colormap.color(pressure, q_min, q_max) * (0.5 + g)
+                        }
+                    }
+                };
+                let color = (255.0 * color.clamp(Vec3::ZERO, Vec3::ONE)).floor();
+                render_buffer[offset..offset + 4].copy_from_slice(&[
+                    color.x as u8,
+                    color.y as u8,
+                    color.z as u8,
+                    255,
+                ]);
+            }
+        }
+    }
+
+    /// Largest speed at the cell centers of open cells
+    #[must_use]
+    fn max_speed(&self) -> f32 {
+        let n = self.grid.num_cells_y;
+        let mut max: f32 = 0.0;
+        for i in 1..self.grid.num_cells_x - 1 {
+            for j in 1..self.grid.num_cells_y - 1 {
+                if self.grid.s[i * n + j] == 0.0 {
+                    continue;
+                }
+                let u = 0.5 * (self.grid.u[i * n + j] + self.grid.u[(i + 1) * n + j]);
+                let v = 0.5 * (self.grid.v[i * n + j] + self.grid.v[i * n + j + 1]);
+                max = max.max(Vec2::new(u, v).length());
+            }
+        }
+        max
+    }
+
+    /// Draws the complete frame, the cells and the overlays of `draw_canvas`,
+    /// into an RGBA buffer without a canvas
+    pub fn draw_raster(&mut self, render_buffer: &mut [u8]) {
+        self.draw_buffer(render_buffer);
+        let mut raster = Raster::new(self.width as usize, self.height as usize, render_buffer);
+        let c = |p: Vec2| Vec2::new(self.c_x(p.x), self.c_y(p.y));
+
+        if self.show_velocities {
+            let h = self.grid.h;
+            let n = self.grid.num_cells_y;
+            for i in 0..self.grid.num_cells_x {
+                for j in 0..self.grid.num_cells_y {
+                    let u = self.grid.u[i * n + j];
+                    let v = self.grid.v[i * n + j];
+                    let x = i as f32 * h;
+                    let y = j as f32 * h;
+                    let left = Vec2::new(x, y + 0.5 * h);
+                    let bottom = Vec2::new(x + 0.5 * h, y);
+                    raster.draw_line(
+                        c(left),
+                        c(left + Vec2::new(u * RENDER_LINE_SCALE, 0.0)),
+                        1.0,
+                        Vec3::ZERO,
+                    );
+                    raster.draw_line(
+                        c(bottom),
+                        c(bottom + Vec2::new(0.0, v * RENDER_LINE_SCALE)),
+                        1.0,
+                        Vec3::ZERO,
+                    );
+                }
+            }
+        }
+
+        if self.show_streamlines {
+            for line in self.streamlines() {
+                let line: Vec<Vec2> = line.into_iter().map(c).collect();
+                raster.draw_polyline(&line, 1.0, Vec3::ZERO);
+            }
+        }
+
+        if self.show_tracers {
+            for id in 0..self.tracers.len() {
+                let line: Vec<Vec2> = self.tracers.points(id).map(c).collect();
+                raster.draw_polyline(&line, TRACER_LINE_WIDTH, TRACER_COLOR);
+            }
+        }
+
+        if self.show_obstacle {
+            for o in &self.obstacles {
+                let vertices: Vec<Vec2> = o.world_vertices().map(c).collect();
+                raster.fill_polygon(&vertices, Vec3::ONE);
+                raster.draw_polyline(&vertices, 3.0, Vec3::ZERO);
+                raster.draw_line(vertices[vertices.len() - 1], vertices[0], 3.0, Vec3::ZERO);
+            }
+        }
+
+        if self.show_obstacle && self.has_obstacle {
+            let center = c(self.obstacle_pos);
+            let r = self.c_scale * (self.obstacle_radius + self.grid.h);
+            raster.fill_disk(center, r, Vec3::ONE);
+            raster.stroke_circle(center, r, 3.0, Vec3::ZERO);
+        }
+
+        if self.show_forces && self.force_history.len > 0 {
+            // the plot without the axis labels of the canvas version
+            let w = 0.3 * self.width;
+            let ht = 0.2 * self.height;
+            let x0 = 10.0;
+            let y0 = self.height - ht - 10.0;
+            let max = self
+                .force_history
+                .as_slice()
+                .iter()
+                .fold(0.1, |max: f32, f| max.max(f.x.abs()).max(f.y.abs()));
+            let scale = 0.5 * ht / max;
+            let dx = w / FORCE_HISTORY_LENGTH as f32;
+
+            raster.fill_rect(
+                Vec2::new(x0, y0),
+                Vec2::new(x0 + w, y0 + ht),
+                Vec3::ONE,
+                0.8,
+            );
+            raster.draw_line(
+                Vec2::new(x0, y0 + 0.5 * ht),
+                Vec2::new(x0 + w, y0 + 0.5 * ht),
+                1.0,
+                Vec3::splat(0.533),
+            );
+            for (color, coefficient) in [(Vec3::X, 0), (Vec3::Z, 1)] {
+                let line: Vec<Vec2> = self
+                    .force_history
+                    .as_slice()
+                    .iter()
+                    .enumerate()
+                    .map(|(k, f)| {
+                        Vec2::new(x0 + k as f32 * dx, y0 + 0.5 * ht - f[coefficient] * scale)
+                    })
+                    .collect();
+                raster.draw_polyline(&line, 1.0, color);
+            }
+        }
+    }
+
+    /// Draws the complete frame like `draw_raster` and encodes it as PNG
+    pub fn draw_png(&mut self) -> Vec<u8> {
+        render_png(self.width as usize, self.height as usize, |buffer| {
+            self.draw_raster(buffer);
+        })
+    }
+
+    /// Streamlines seeded on a coarse grid of cell centers, in simulation
+    /// coordinates
+    fn streamlines(&self) -> Vec<Vec<Vec2>> {
+        let seg_len = self.grid.h * RENDER_LINE_SCALE;
+        let mut lines = vec![];
+        for i in (1..(self.grid.num_cells_x - 1)).step_by(STREAMLINE_RESOLUTION_STEP) {
+            for j in (1..(self.grid.num_cells_y - 1)).step_by(STREAMLINE_RESOLUTION_STEP) {
+                let mut x = (i as f32 + 0.5) * self.grid.h;
+                let mut y = (j as f32 + 0.5) * self.grid.h;
+                let mut line = vec![Vec2::new(x, y)];
+                for _ in 0..STREAMLINE_NUM_SEGS {
+                    let u = self.sample_field(x, y, Field::U);
+                    let v = self.sample_field(x, y, Field::V);
+                    let l = f32::sqrt(u * u + v * v);
+                    x += u / l * seg_len;
+                    y += v / l * seg_len;
+                    x += u * 0.01;
+                    y += v * 0.01;
+                    if x > self.grid.num_cells_x as f32 * self.grid.h {
+                        break;
+                    }
+                    line.push(Vec2::new(x, y));
+                }
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Plots the drag (red) and lift (blue) coefficient history in the lower
+    /// left corner of the canvas
+    fn draw_force_history(&self) {
+        let Some(c) = &self.context else {
+            return;
+        };
+        let w: f64 = (0.3 * self.width).into();
+        let ht: f64 = (0.2 * self.height).into();
+        let x0 = 10.0;
+        let y0: f64 = f64::from(self.height) - ht - 10.0;
+
+        let max = self
+            .force_history
+            .as_slice()
+            .iter()
+            .fold(0.1, |max: f32, f| max.max(f.x.abs()).max(f.y.abs()));
+        let scale = 0.5 * ht / f64::from(max);
+        let dx = w / FORCE_HISTORY_LENGTH as f64;
+
+        c.set_fill_style(&JsValue::from("rgba(255, 255, 255, 0.8)"));
+        c.fill_rect(x0, y0, w, ht);
+        c.set_stroke_style(&JsValue::from("#888888"));
+        c.begin_path();
+        c.move_to(x0, y0 + 0.5 * ht);
+        c.line_to(x0 + w, y0 + 0.5 * ht);
+        c.stroke();
+
+        for (color, coefficient) in [("#FF0000", 0), ("#0000FF", 1)] {
+            c.set_stroke_style(&JsValue::from(color));
+            c.begin_path();
+            for (k, f) in self.force_history.as_slice().iter().enumerate() {
+                let x = x0 + k as f64 * dx;
+                let y = y0 + 0.5 * ht - f64::from(f[coefficient]) * scale;
+                if k == 0 {
+                    c.move_to(x, y);
+                } else {
+                    c.line_to(x, y);
+                }
+            }
+            c.stroke();
+        }
+
+        c.set_fill_style(&JsValue::from("#000000"));
+        c.fill_text(&format!("{max:.2}"), x0 + 2.0, y0 + 10.0)
+            .unwrap(); // known safe
+        c.fill_text(&format!("-{max:.2}"), x0 + 2.0, y0 + ht - 2.0)
+            .unwrap(); // known safe
     }
 
     pub fn step(&mut self) {
+        self.update_density();
         self.integrate();
+        self.apply_vorticity_confinement();
+        self.apply_viscosity();
 
-        self.p.fill(0.0);
+        self.grid.p.fill(0.0);
         self.solve_incompressibility();
-
-        self.extrapolate();
+        self.diagnostics
+            .update_divergence(&self.grid.u, &self.grid.v, |id| self.grid.s[id] != 0.0);
+        self.measure_forces();
+
+        self.grid
+            .boundaries
+            .apply_velocity(&mut self.grid.u, &mut self.grid.v);
+        self.advect_tracers();
         self.advect_vel();
         self.advect_smoke();
+        self.grid.boundaries.apply_scalar(&mut self.m);
+
+        self.diagnostics
+            .update_kinetic_energy(self.density, &self.grid.u, &self.grid.v, |id| {
+                self.grid.s[id] != 0.0
+            });
+        // m is 1 for clear fluid, so the smoke concentration is 1 - m
+        self.diagnostics
+            .update_total_mass(|id| 1.0 - self.m[id], |id| self.grid.s[id] != 0.0);
 
         self.frame_number += 1.0;
     }
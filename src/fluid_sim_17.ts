@@ -1,10 +1,11 @@
 import GUI from 'lil-gui';
 import * as THREE from 'three';
 
-import { FluidSimulation, FluidSceneType } from '../pkg';
-import { Demo, Scene2DCanvas, Scene2DConfig, enumToValueList } from './lib';
+import { FluidSimulation, FluidSceneType, FluidAdvectionScheme, PressureSolverKind, BoundaryEdge, BoundaryKind, InflowProfile, ColormapKind, ColormapScale, LicModulation } from '../pkg';
+import { Demo, Scene2DCanvas, Scene2DConfig, enumToValueList, makeColormap, colormapToValues, downloadPng } from './lib';
 
 const DEFAULT_SCENE = FluidSceneType.WindTunnel;
+const TRACER_RAKE_SIZE = 10;
 
 type FluidDemoProps = {
     scene: string; // enum string value
@@ -13,11 +14,48 @@ type FluidDemoProps = {
     numIters: number;
     density: number;
     overRelaxation: number;
+    pressureSolver: string; // enum string value
+    pressureTolerance: number;
+    pressureIters: number;
+    pressureResidual: number;
+    advectionScheme: string; // enum string value
+    vorticityConfinement: number;
+    viscosity: number;
+    smokeDensity: number;
+    boundaryLeft: string; // enum string value
+    boundaryRight: string; // enum string value
+    boundaryBottom: string; // enum string value
+    boundaryTop: string; // enum string value
+    inflowSpeed: number;
+    inflowProfile: string; // enum string value
     showObstacle: boolean;
     showStreamlines: boolean;
     showVelocities: boolean;
     showPressure: boolean;
     showSmoke: boolean;
+    pressureColormap: string; // enum string value
+    pressureScale: string; // enum string value
+    smokeColormap: string; // enum string value
+    showLic: boolean;
+    licModulation: string; // enum string value
+    licColormap: string; // enum string value
+    licLength: number;
+    maxDivergence: number;
+    meanDivergence: number;
+    kineticEnergy: number;
+    totalMass: number;
+    showDivergence: boolean;
+    dragCoefficient: number;
+    liftCoefficient: number;
+    showForces: boolean;
+    showTracers: boolean;
+    maxTracerPoints: number;
+    addStreaklines: () => void;
+    addPathlines: () => void;
+    clearTracers: () => void;
+    loadObstacleMask: () => void;
+    clearObstacles: () => void;
+    savePng: () => void;
 };
 
 const FluidDemoConfig: Scene2DConfig = {
@@ -47,12 +85,40 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
         this.props.numIters = this.sim.num_iters;
         this.props.density = this.sim.density;
         this.props.overRelaxation = this.sim.over_relaxation;
+        this.props.pressureSolver = PressureSolverKind[this.sim.pressure_solver];
+        this.props.pressureTolerance = this.sim.pressure_tolerance;
+        this.props.pressureIters = this.sim.pressure_iters;
+        this.props.pressureResidual = this.sim.pressure_residual;
+        this.props.advectionScheme = FluidAdvectionScheme[this.sim.advection_scheme];
+        this.props.vorticityConfinement = this.sim.vorticity_confinement;
+        this.props.viscosity = this.sim.viscosity;
+        this.props.smokeDensity = this.sim.smoke_density;
+        this.updateBoundaryProps();
+        this.props.inflowSpeed = this.sim.inflow_speed(BoundaryEdge.Left);
+        this.props.inflowProfile = InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)];
         this.props.showObstacle = this.sim.show_obstacle;
         this.props.showStreamlines = this.sim.show_streamlines;
         this.props.showVelocities = this.sim.show_velocities;
         this.props.showPressure = this.sim.show_pressure;
         this.props.showSmoke = this.sim.show_smoke;
-        if (this.props.scene === FluidSceneType[FluidSceneType.WindTunnel] && this.props.showSmoke === true && this.props.showPressure === false) {
+        [this.props.pressureColormap, this.props.pressureScale] = colormapToValues(this.sim.pressure_colormap);
+        [this.props.smokeColormap] = colormapToValues(this.sim.smoke_colormap);
+        this.props.showLic = this.sim.show_lic;
+        this.props.licModulation = LicModulation[this.sim.lic_modulation];
+        [this.props.licColormap] = colormapToValues(this.sim.lic_colormap);
+        this.props.licLength = this.sim.lic_length;
+        this.props.maxDivergence = this.sim.max_divergence;
+        this.props.meanDivergence = this.sim.mean_divergence;
+        this.props.kineticEnergy = this.sim.kinetic_energy;
+        this.props.totalMass = this.sim.total_mass;
+        this.props.showDivergence = this.sim.show_divergence;
+        this.props.dragCoefficient = this.sim.drag_coefficient;
+        this.props.liftCoefficient = this.sim.lift_coefficient;
+        this.props.showForces = this.sim.show_forces;
+        this.props.showTracers = this.sim.show_tracers;
+        this.props.maxTracerPoints = this.sim.max_tracer_points;
+        const isTunnel = this.props.scene === FluidSceneType[FluidSceneType.WindTunnel] || this.props.scene === FluidSceneType[FluidSceneType.Airfoil];
+        if (isTunnel && this.props.showSmoke === true && this.props.showPressure === false) {
             // flip text color due to white background
             document.getElementById('info').setAttribute("style", "color: #000;");
         } else {
@@ -63,6 +129,14 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
     update() {
         if (this.props.animate) {
             this.sim.step();
+            this.props.pressureIters = this.sim.pressure_iters;
+            this.props.pressureResidual = this.sim.pressure_residual;
+            this.props.maxDivergence = this.sim.max_divergence;
+            this.props.meanDivergence = this.sim.mean_divergence;
+            this.props.kineticEnergy = this.sim.kinetic_energy;
+            this.props.totalMass = this.sim.total_mass;
+            this.props.dragCoefficient = this.sim.drag_coefficient;
+            this.props.liftCoefficient = this.sim.lift_coefficient;
         }
     }
 
@@ -79,6 +153,9 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
     }
 
     private initControls(folder: GUI, canvas: HTMLCanvasElement) {
+        const [pressureColormap, pressureScale] = colormapToValues(this.sim.pressure_colormap);
+        const [smokeColormap] = colormapToValues(this.sim.smoke_colormap);
+        const [licColormap] = colormapToValues(this.sim.lic_colormap);
         this.props = {
             scene: FluidSceneType[DEFAULT_SCENE],
             animate: true,
@@ -86,11 +163,48 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
             numIters: this.sim.num_iters,
             density: this.sim.density,
             overRelaxation: this.sim.over_relaxation,
+            pressureSolver: PressureSolverKind[this.sim.pressure_solver],
+            pressureTolerance: this.sim.pressure_tolerance,
+            pressureIters: this.sim.pressure_iters,
+            pressureResidual: this.sim.pressure_residual,
+            advectionScheme: FluidAdvectionScheme[this.sim.advection_scheme],
+            vorticityConfinement: this.sim.vorticity_confinement,
+            viscosity: this.sim.viscosity,
+            smokeDensity: this.sim.smoke_density,
+            boundaryLeft: BoundaryKind[this.sim.boundary(BoundaryEdge.Left)],
+            boundaryRight: BoundaryKind[this.sim.boundary(BoundaryEdge.Right)],
+            boundaryBottom: BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)],
+            boundaryTop: BoundaryKind[this.sim.boundary(BoundaryEdge.Top)],
+            inflowSpeed: this.sim.inflow_speed(BoundaryEdge.Left),
+            inflowProfile: InflowProfile[this.sim.inflow_profile(BoundaryEdge.Left)],
             showObstacle: this.sim.show_obstacle,
             showStreamlines: this.sim.show_streamlines,
             showVelocities: this.sim.show_velocities,
             showPressure: this.sim.show_pressure,
             showSmoke: this.sim.show_smoke,
+            pressureColormap,
+            pressureScale,
+            smokeColormap,
+            showLic: this.sim.show_lic,
+            licModulation: LicModulation[this.sim.lic_modulation],
+            licColormap,
+            licLength: this.sim.lic_length,
+            maxDivergence: this.sim.max_divergence,
+            meanDivergence: this.sim.mean_divergence,
+            kineticEnergy: this.sim.kinetic_energy,
+            totalMass: this.sim.total_mass,
+            showDivergence: this.sim.show_divergence,
+            dragCoefficient: this.sim.drag_coefficient,
+            liftCoefficient: this.sim.lift_coefficient,
+            showForces: this.sim.show_forces,
+            showTracers: this.sim.show_tracers,
+            maxTracerPoints: this.sim.max_tracer_points,
+            addStreaklines: () => this.addTracerRake(false),
+            addPathlines: () => this.addTracerRake(true),
+            clearTracers: () => this.sim.clear_tracers(),
+            loadObstacleMask: () => this.loadObstacleMask(),
+            clearObstacles: () => this.sim.clear_obstacles(),
+            savePng: () => downloadPng(this.sim.draw_png(), 'fluid.png'),
         };
         folder.add(this.props, 'scene', enumToValueList(FluidSceneType)).onChange((_: string) => {
             this.reset();
@@ -99,13 +213,72 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
         folder.add(this.props, 'numIters').name('substeps').disable().listen();
         folder.add(this.props, 'density').disable().listen();
         folder.add(this.props, 'overRelaxation').decimals(2).min(1.00).max(1.99).name('over relaxation').onChange((v: number) => (this.sim.over_relaxation = v)).listen();
+        folder.add(this.props, 'pressureSolver', enumToValueList(PressureSolverKind)).name('pressure solver').onChange((v: string) => {
+            this.sim.pressure_solver = Object.values(PressureSolverKind).indexOf(v);
+        }).listen();
+        folder.add(this.props, 'pressureTolerance', [1e-2, 1e-3, 1e-4, 1e-5, 1e-6]).name('pressure tolerance').onChange((v: number) => (this.sim.pressure_tolerance = v)).listen();
+        folder.add(this.props, 'pressureIters').name('pressure iters').disable().listen();
+        folder.add(this.props, 'pressureResidual').decimals(6).name('pressure residual').disable().listen();
+        folder.add(this.props, 'advectionScheme', enumToValueList(FluidAdvectionScheme)).name('advection').onChange((v: string) => {
+            this.sim.advection_scheme = Object.values(FluidAdvectionScheme).indexOf(v);
+        }).listen();
+        folder.add(this.props, 'vorticityConfinement').decimals(1).min(0.0).max(10.0).step(0.1).name('vorticity confinement').onChange((v: number) => (this.sim.vorticity_confinement = v)).listen();
+        folder.add(this.props, 'viscosity').decimals(4).min(0.0).max(0.05).step(0.0001).onChange((v: number) => (this.sim.viscosity = v)).listen();
+        folder.add(this.props, 'smokeDensity').decimals(2).min(-0.5).max(0.5).step(0.01).name('smoke density').onChange((v: number) => (this.sim.smoke_density = v)).listen();
+        folder.add(this.props, 'loadObstacleMask').name('load obstacle mask');
+        folder.add(this.props, 'clearObstacles').name('clear obstacles');
+        const bounds = folder.addFolder('Boundaries');
+        const boundaryProps: [string, BoundaryEdge][] = [
+            ['boundaryLeft', BoundaryEdge.Left],
+            ['boundaryRight', BoundaryEdge.Right],
+            ['boundaryBottom', BoundaryEdge.Bottom],
+            ['boundaryTop', BoundaryEdge.Top],
+        ];
+        for (const [prop, edge] of boundaryProps) {
+            bounds.add(this.props, prop, enumToValueList(BoundaryKind)).name(BoundaryEdge[edge].toLowerCase()).onChange((v: string) => {
+                this.sim.set_boundary(edge, Object.values(BoundaryKind).indexOf(v));
+                // periodic edges come in pairs
+                this.updateBoundaryProps();
+            }).listen();
+        }
+        bounds.add(this.props, 'inflowSpeed').decimals(2).min(0.0).max(5.0).step(0.1).name('inflow speed').onChange((_: number) => this.updateInflow()).listen();
+        bounds.add(this.props, 'inflowProfile', enumToValueList(InflowProfile)).name('inflow profile').onChange((_: string) => this.updateInflow()).listen();
+        const tracers = folder.addFolder('Tracers');
+        tracers.add(this.props, 'showTracers').name('show tracers').onFinishChange((v: boolean) => (this.sim.show_tracers = v)).listen();
+        tracers.add(this.props, 'maxTracerPoints').min(10).max(1000).step(10).name('max points').onChange((v: number) => (this.sim.max_tracer_points = v)).listen();
+        tracers.add(this.props, 'addStreaklines').name('add streaklines');
+        tracers.add(this.props, 'addPathlines').name('add pathlines');
+        tracers.add(this.props, 'clearTracers').name('clear tracers');
+                const diag = folder.addFolder('Diagnostics');
+        diag.add(this.props, 'maxDivergence').decimals(4).name('max divergence').disable().listen();
+        diag.add(this.props, 'meanDivergence').decimals(4).name('mean divergence').disable().listen();
+        diag.add(this.props, 'kineticEnergy').decimals(4).name('kinetic energy').disable().listen();
+        diag.add(this.props, 'totalMass').decimals(4).name('total smoke').disable().listen();
+        diag.add(this.props, 'dragCoefficient').decimals(3).name('drag coefficient').disable().listen();
+        diag.add(this.props, 'liftCoefficient').decimals(3).name('lift coefficient').disable().listen();
         const sub = folder.addFolder('Rendering');
         sub.add(this.props, 'showObstacle').name('show obstacle').onFinishChange((v: boolean) => (this.sim.show_obstacle = v)).listen();
         sub.add(this.props, 'showStreamlines').name('show streamlines').onFinishChange((v: boolean) => (this.sim.show_streamlines = v)).listen();
         sub.add(this.props, 'showVelocities').name('show velocities').onFinishChange((v: boolean) => (this.sim.show_velocities = v)).listen();
         sub.add(this.props, 'showPressure').name('show pressure').onFinishChange((v: boolean) => (this.sim.show_pressure = v)).listen();
         sub.add(this.props, 'showSmoke').name('show smoke').onFinishChange((v: boolean) => (this.sim.show_smoke = v)).listen();
+        sub.add(this.props, 'pressureColormap', enumToValueList(ColormapKind)).name('pressure colormap').onChange((_: string) => this.updatePressureColormap()).listen();
+        sub.add(this.props, 'pressureScale', enumToValueList(ColormapScale)).name('pressure scale').onChange((_: string) => this.updatePressureColormap()).listen();
+        sub.add(this.props, 'smokeColormap', enumToValueList(ColormapKind)).name('smoke colormap').onChange((v: string) => {
+            this.sim.smoke_colormap = makeColormap(this.rust_wasm, v, ColormapScale[ColormapScale.Linear]);
+        }).listen();
+        sub.add(this.props, 'showLic').name('show lic').onFinishChange((v: boolean) => (this.sim.show_lic = v)).listen();
+        sub.add(this.props, 'licModulation', enumToValueList(LicModulation)).name('lic modulation').onChange((v: string) => {
+            this.sim.lic_modulation = Object.values(LicModulation).indexOf(v);
+        }).listen();
+        sub.add(this.props, 'licColormap', enumToValueList(ColormapKind)).name('lic colormap').onChange((v: string) => {
+            this.sim.lic_colormap = makeColormap(this.rust_wasm, v, ColormapScale[ColormapScale.Linear]);
+        }).listen();
+        sub.add(this.props, 'licLength').min(1).max(50).step(1).name('lic length').onChange((v: number) => (this.sim.lic_length = v)).listen();
+        sub.add(this.props, 'showForces').name('show forces').onFinishChange((v: boolean) => (this.sim.show_forces = v)).listen();
+        sub.add(this.props, 'showDivergence').name('show divergence').onFinishChange((v: boolean) => (this.sim.show_divergence = v)).listen();
         sub.add(this.props, 'animate').listen();
+        sub.add(this.props, 'savePng').name('save png');
 
         // scene interaction
         this.mouseDown = false;
@@ -123,6 +296,63 @@ class FluidDemo implements Demo<FluidSimulation, FluidDemoProps> {
         });
     }
 
+    // seeds a vertical rake of tracers near the left edge
+    private addTracerRake(pathlines: boolean) {
+        const [width, height] = this.sim.domain_size;
+        for (let k = 0; k < TRACER_RAKE_SIZE; k++) {
+            const y = (k + 0.5) / TRACER_RAKE_SIZE * height;
+            if (pathlines) {
+                this.sim.add_pathline(0.05 * width, y);
+            } else {
+                this.sim.add_streakline(0.05 * width, y);
+            }
+        }
+    }
+
+    private updatePressureColormap() {
+        this.sim.pressure_colormap = makeColormap(this.rust_wasm, this.props.pressureColormap, this.props.pressureScale);
+    }
+
+    private updateBoundaryProps() {
+        this.props.boundaryLeft = BoundaryKind[this.sim.boundary(BoundaryEdge.Left)];
+        this.props.boundaryRight = BoundaryKind[this.sim.boundary(BoundaryEdge.Right)];
+        this.props.boundaryBottom = BoundaryKind[this.sim.boundary(BoundaryEdge.Bottom)];
+        this.props.boundaryTop = BoundaryKind[this.sim.boundary(BoundaryEdge.Top)];
+    }
+
+    // all edges share the inflow settings, they only apply to inflow edges
+    private updateInflow() {
+        const profile = Object.values(InflowProfile).indexOf(this.props.inflowProfile);
+        for (const edge of [BoundaryEdge.Left, BoundaryEdge.Right, BoundaryEdge.Bottom, BoundaryEdge.Top]) {
+            this.sim.set_inflow(edge, this.props.inflowSpeed, profile);
+        }
+    }
+
+    // dark opaque pixels of the image, stretched to the canvas, become solid
+    private loadObstacleMask() {
+        const input = document.createElement('input');
+        input.type = 'file';
+        input.accept = 'image/*';
+        input.onchange = () => {
+            if (input.files.length === 0) {
+                return;
+            }
+            const image = new Image();
+            image.onload = () => {
+                const canvas = document.createElement('canvas');
+                canvas.width = this.scene.width;
+                canvas.height = this.scene.height;
+                const context = canvas.getContext('2d');
+                context.drawImage(image, 0, 0, canvas.width, canvas.height);
+                const mask = context.getImageData(0, 0, canvas.width, canvas.height);
+                this.sim.set_obstacle_mask(mask.data as unknown as Uint8Array); // wasm-bindgen doesn't support Uint8ClampedArray
+                URL.revokeObjectURL(image.src);
+            };
+            image.src = URL.createObjectURL(input.files[0]);
+        };
+        input.click();
+    }
+
     private setMousePos(x: number, y: number, reset: boolean) {
         const mx = x - this.mouseOffset.x;
         const my = y - this.mouseOffset.y;
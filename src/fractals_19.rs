@@ -3,21 +3,18 @@
 use glam::{vec2, Vec2};
 use wasm_bindgen::prelude::*;
 
+use crate::colormap::{Colormap, ColormapKind, ColormapScale};
+use crate::raster::render_png;
+
 const DEFAULT_MAX_ITERS: usize = 100;
 const DEFAULT_FRACTAL_POS: Vec2 = Vec2::ZERO;
 const DEFAULT_FRACTAL_C: Vec2 = vec2(-0.6258, 0.4025);
 const FRACTAL_C_STEP_SCALE: f32 = 0.1;
 const DEFAULT_SCALE: f32 = 0.003;
 
-const GRADIENT_COLORS: [[f32; 3]; 7] = [
-    [15.0, 2.0, 66.0],
-    [191.0, 41.0, 12.0],
-    [222.0, 99.0, 11.0],
-    [229.0, 208.0, 14.0],
-    [255.0, 255.0, 255.0],
-    [102.0, 173.0, 183.0],
-    [14.0, 29.0, 104.0],
-];
+// iterations per pass through the colormap, 0 spreads the colormap over
+// max_iters
+const DEFAULT_COLOR_CYCLE: usize = 140;
 
 #[wasm_bindgen(js_name = FractalsSceneType)]
 #[derive(PartialEq, Clone, Copy)]
@@ -40,22 +37,8 @@ pub struct FractalsSimulation {
     height: f32,
     pub draw_mono: bool,
     pub redraw: bool,
-}
-
-fn get_gradient_color(val: f32, steps: f32) -> [u8; 3] {
-    let num_colors = GRADIENT_COLORS.len() as f32;
-    let color0 = f32::floor(val / steps) % num_colors;
-    let color1 = (color0 + 1.0) % num_colors;
-    let step = val % steps;
-
-    let mut color = [0, 0, 0];
-
-    for i in 0..3 {
-        let c0 = GRADIENT_COLORS[color0 as usize][i];
-        let c1 = GRADIENT_COLORS[color1 as usize][i];
-        color[i] = f32::floor(c0 + (c1 - c0) / steps * step) as u8;
-    }
-    return color;
+    colormap: Colormap,
+    pub color_cycle: usize,
 }
 
 #[allow(clippy::inline_always)]
@@ -93,6 +76,8 @@ impl FractalsSimulation {
             height,
             draw_mono: false,
             redraw: true,
+            colormap: Colormap::new(ColormapKind::Fractal, ColormapScale::Linear),
+            color_cycle: DEFAULT_COLOR_CYCLE,
         }
     }
 
@@ -113,12 +98,12 @@ impl FractalsSimulation {
 
             let x = x1;
             x1 = x1 * x1 - x2 * x2;
-            x2 = 2.0 * x * x2;
+            x2 *= 2.0 * x;
 
             x1 += c1;
             x2 += c2;
         }
-        return max_iters;
+        max_iters
     }
 
     pub fn draw_buffer(&mut self, render_buffer: &mut [u8]) {
@@ -153,7 +138,7 @@ impl FractalsSimulation {
                     }
                 } else {
                     if iters < self.max_iters {
-                        let grad_color = get_gradient_color(iters as f32, 20.0);
+                        let grad_color = self.iteration_color(iters);
                         set_color(&mut color, &grad_color);
                     } else {
                         splat_color(&mut color, 0);
@@ -170,6 +155,43 @@ impl FractalsSimulation {
         }
     }
 
+    /// Draws the fractal and encodes it as PNG, regardless of `redraw`
+    pub fn draw_png(&mut self) -> Vec<u8> {
+        self.redraw = true;
+        render_png(self.width as usize, self.height as usize, |buffer| {
+            self.draw_buffer(buffer);
+        })
+    }
+
+    /// Colors escaping points by their iteration count, repeating the
+    /// colormap every `color_cycle` iterations
+    #[must_use]
+    fn iteration_color(&self, iters: usize) -> [u8; 3] {
+        let t = if self.color_cycle > 0 {
+            (iters % self.color_cycle) as f32 / self.color_cycle as f32
+        } else {
+            iters as f32 / self.max_iters as f32
+        };
+        let [r, g, b] = self.colormap.color_255(t, 0.0, 1.0);
+        [
+            f32::floor(r) as u8,
+            f32::floor(g) as u8,
+            f32::floor(b) as u8,
+        ]
+    }
+
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn colormap(&self) -> Colormap {
+        self.colormap.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.colormap = colormap;
+        self.redraw = true;
+    }
+
     pub fn handle_drag(&mut self, dx: f32, dy: f32, alter_c: bool) {
         let d = Vec2::new(dx, dy);
         if alter_c {
@@ -1,8 +1,8 @@
 import GUI from 'lil-gui';
 import * as THREE from 'three';
 
-import { FractalsSimulation, FractalsSceneType } from '../pkg';
-import { Demo, Scene2DCanvas, Scene2DConfig, enumToValueList } from './lib';
+import { FractalsSimulation, FractalsSceneType, ColormapKind, ColormapScale } from '../pkg';
+import { Demo, Scene2DCanvas, Scene2DConfig, enumToValueList, makeColormap, colormapToValues, downloadPng } from './lib';
 
 const DEFAULT_SCENE = FractalsSceneType.Julia;
 const MAX_SCROLL_SCALE_RATE = 0.10;
@@ -12,6 +12,10 @@ type FractalsDemoProps = {
     drawMono: boolean;
     maxIters: number;
     dragAction: 'ChangeFractalC' | 'Move';
+    colormap: string; // enum string value
+    colormapScale: string; // enum string value
+    colorCycle: number;
+    savePng: () => void;
 };
 
 const FractalsDemoConfig: Scene2DConfig = {
@@ -23,11 +27,13 @@ class FractalsDemo implements Demo<FractalsSimulation, FractalsDemoProps> {
     scene: Scene2DCanvas;
     props: FractalsDemoProps;
 
+    private rust_wasm: any;
     private mouseDown: boolean;
     private lastMouse: THREE.Vector2;
     private imageData: ImageData;
 
     constructor(rust_wasm: any, _: WebAssembly.Memory, canvas: HTMLCanvasElement, scene: Scene2DCanvas, folder: GUI) {
+        this.rust_wasm = rust_wasm;
         this.sim = new rust_wasm.FractalsSimulation(DEFAULT_SCENE, scene.width, scene.height, scene.context);
         this.scene = scene;
         this.initControls(folder, canvas);
@@ -52,11 +58,16 @@ class FractalsDemo implements Demo<FractalsSimulation, FractalsDemoProps> {
     }
 
     private initControls(folder: GUI, canvas: HTMLCanvasElement) {
+        const [colormap, colormapScale] = colormapToValues(this.sim.colormap);
         this.props = {
             scene: FractalsSceneType[DEFAULT_SCENE],
             drawMono: this.sim.draw_mono,
             maxIters: this.sim.max_iters,
             dragAction: 'Move',
+            colormap,
+            colormapScale,
+            colorCycle: this.sim.color_cycle,
+            savePng: () => downloadPng(this.sim.draw_png(), 'fractal.png'),
         };
         folder.add(this.props, 'scene', enumToValueList(FractalsSceneType)).onChange((n: string) => {
             this.sim.scene_type = Object.values(FractalsSceneType).indexOf(n);
@@ -70,7 +81,14 @@ class FractalsDemo implements Demo<FractalsSimulation, FractalsDemoProps> {
             this.sim.draw_mono = v;
             this.sim.redraw = true;
         });
+        folder.add(this.props, 'colormap', enumToValueList(ColormapKind)).onChange((_: string) => this.updateColormap());
+        folder.add(this.props, 'colormapScale', enumToValueList(ColormapScale)).name('colormap scale').onChange((_: string) => this.updateColormap());
+        folder.add(this.props, 'colorCycle').min(0).max(500).step(1).name('color cycle').onChange((v: number) => {
+            this.sim.color_cycle = v;
+            this.sim.redraw = true;
+        });
         folder.add(this.props, 'dragAction', ['Move', 'ChangeFractalC']).name('drag action').listen();
+        folder.add(this.props, 'savePng').name('save png');
 
         // scene interaction
         this.mouseDown = false;
@@ -93,6 +111,10 @@ class FractalsDemo implements Demo<FractalsSimulation, FractalsDemoProps> {
         });
     }
 
+    private updateColormap() {
+        this.sim.colormap = makeColormap(this.rust_wasm, this.props.colormap, this.props.colormapScale); // sets redraw
+    }
+
     private startDrag(x: number, y: number) {
         this.mouseDown = true;
         this.lastMouse.x = x;
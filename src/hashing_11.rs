@@ -24,6 +24,10 @@ pub struct Hash {
     table_size: usize,
     cell_start: Vec<usize>,
     cell_entries: Vec<usize>,
+    // id of the last query that visited each bucket, a large query box can
+    // map several cells to the same bucket
+    cell_query: Vec<u32>,
+    query_count: u32,
     pub query_ids: Vec<usize>,
     pub query_size: usize,
 }
@@ -37,6 +41,8 @@ impl Hash {
             table_size,
             cell_start: vec![0; table_size + 1],
             cell_entries: vec![0; max_num_objects],
+            cell_query: vec![0; table_size],
+            query_count: 0,
             query_ids: vec![0; max_num_objects],
             query_size: 0,
         }
@@ -98,11 +104,20 @@ impl Hash {
         let z1 = self.int_coord(pos.z + max_dist);
 
         self.query_size = 0;
+        self.query_count = self.query_count.wrapping_add(1);
+        if self.query_count == 0 {
+            self.cell_query.fill(0);
+            self.query_count = 1;
+        }
 
         for xi in x0..=x1 {
             for yi in y0..=y1 {
                 for zi in z0..=z1 {
                     let h = self.hash_coords(xi, yi, zi);
+                    if self.cell_query[h] == self.query_count {
+                        continue;
+                    }
+                    self.cell_query[h] = self.query_count;
                     let start = self.cell_start[h];
                     let end = self.cell_start[h + 1];
 
@@ -159,6 +174,7 @@ impl HashSimulation {
         }
     }
 
+    #[allow(clippy::needless_range_loop)] // d indexes positions, velocities and bounds alike
     pub fn step(&mut self) {
         // integrate
         for i in 0..self.num_bodies {
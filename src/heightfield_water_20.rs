@@ -73,23 +73,23 @@ impl Ball {
 
         if self.pos.x < -wx {
             self.pos.x = -wx;
-            self.vel.x = -self.restitution * self.vel.x;
+            self.vel.x *= -self.restitution;
         }
         if self.pos.x > wx {
             self.pos.x = wx;
-            self.vel.x = -self.restitution * self.vel.x;
+            self.vel.x *= -self.restitution;
         }
         if self.pos.z < -wz {
             self.pos.z = -wz;
-            self.vel.z = -self.restitution * self.vel.z;
+            self.vel.z *= -self.restitution;
         }
         if self.pos.z > wz {
             self.pos.z = wz;
-            self.vel.z = -self.restitution * self.vel.z;
+            self.vel.z *= -self.restitution;
         }
         if self.pos.y < self.radius {
             self.pos.y = self.radius;
-            self.vel.y = -self.restitution * self.vel.y;
+            self.vel.y *= -self.restitution;
         }
     }
 
@@ -235,11 +235,7 @@ impl HeightFieldWaterSimulation {
 
     #[wasm_bindgen(getter)]
     pub fn ball_positions(&self) -> Vec<f32> {
-        self.balls
-            .iter()
-            .map(|b| b.pos.to_array())
-            .flatten()
-            .collect()
+        self.balls.iter().flat_map(|b| b.pos.to_array()).collect()
     }
 
     // We can copy since we are not performance sensitive for these two methods
@@ -6,19 +6,30 @@
 )]
 
 mod body_chain_challenge;
+mod boundary;
 mod cloth_14;
+mod colormap;
+mod diagnostics;
 mod fire_21;
 mod flip_18;
+mod flip_3d;
 mod fluid_2d_challenge;
 mod fluid_sim_17;
 mod fractals_19;
 mod hashing_11;
 mod heightfield_water_20;
+mod mac_grid;
 mod mesh;
+mod normals;
 mod parallel_cloth_16;
+mod pressure;
+mod raster;
+mod rigid_body;
 mod self_collision_15;
 mod softbodies_10;
 mod softbody_skinning_12;
+mod surface;
+mod tracer;
 
 pub mod util {
     use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader};
@@ -26,7 +37,8 @@ pub mod util {
     #[must_use]
     #[allow(clippy::many_single_char_names)]
     pub fn get_sci_color(val: f32, min: f32, max: f32) -> [f32; 3] {
-        let mut val = val.clamp(min, max - 0.0001);
+        // ranges narrower than the upper margin (e.g. zero divergence at rest) must not panic `clamp`
+        let mut val = val.clamp(min, (max - 0.0001).max(min));
         let d = max - min;
         val = if d == 0.0 { 0.5 } else { (val - min) / d };
         let m = 0.25;
@@ -2,6 +2,8 @@ import { Controller } from 'lil-gui';
 import * as THREE from 'three';
 import { OrbitControls } from "three/examples/jsm/controls/OrbitControls";
 
+import { Colormap, ColormapKind, ColormapScale } from '../pkg';
+
 type Demo<S, T> = {
     sim: S;
     scene: Scene;
@@ -268,4 +270,38 @@ const resizeThreeScene = (scene: Scene3D, width: number, height: number, updateS
 // returns ['EnumOne', 'EnumTwo', ...]
 const enumToValueList = (e: any): any => Object.values(e).filter((i) => typeof i === 'string');
 
-export { Demo, Scene, Scene2DCanvas, Scene2DWebGL, Scene3D, SceneConfig, Scene2DConfig, Scene3DConfig, Grabber, initThreeScene, resizeThreeScene, enumToValueList };
+// example of a gradient defined from JS, used for the 'Custom' colormap
+const CUSTOM_GRADIENT = new Float32Array([
+    0.0, 0.0, 0.0,
+    0.1, 0.2, 0.6,
+    0.4, 0.8, 1.0,
+    1.0, 1.0, 1.0,
+]);
+
+// creates a colormap from ColormapKind and ColormapScale enum string values
+const makeColormap = (rust_wasm: any, kind: string, scale: string): Colormap => {
+    const scaleValue = Object.values(ColormapScale).indexOf(scale);
+    if (kind === ColormapKind[ColormapKind.Custom]) {
+        return rust_wasm.Colormap.from_gradient(CUSTOM_GRADIENT, scaleValue);
+    }
+    return new rust_wasm.Colormap(Object.values(ColormapKind).indexOf(kind), scaleValue);
+}
+
+// returns the kind and scale enum string values of a colormap, which is freed
+const colormapToValues = (colormap: Colormap): [string, string] => {
+    const values: [string, string] = [ColormapKind[colormap.kind], ColormapScale[colormap.scale]];
+    colormap.free();
+    return values;
+}
+
+// offers PNG bytes rendered by a simulation's draw_png as a file download
+const downloadPng = (png: Uint8Array, filename: string) => {
+    const url = URL.createObjectURL(new Blob([png], { type: 'image/png' }));
+    const link = document.createElement('a');
+    link.href = url;
+    link.download = filename;
+    link.click();
+    URL.revokeObjectURL(url);
+}
+
+export { Demo, Scene, Scene2DCanvas, Scene2DWebGL, Scene3D, SceneConfig, Scene2DConfig, Scene3DConfig, Grabber, initThreeScene, resizeThreeScene, enumToValueList, makeColormap, colormapToValues, downloadPng };
@@ -0,0 +1,313 @@
+use crate::boundary::{Boundaries, BoundaryKind};
+use crate::pressure::{PressureSolver, PressureSolverKind};
+
+/// Location of a quantity within a grid cell
+#[derive(Clone, Copy)]
+pub enum Field {
+    /// left face, like the horizontal velocity
+    U,
+    /// bottom face, like the vertical velocity
+    V,
+    /// cell center, like smoke or temperature
+    Center,
+}
+
+/// Staggered 2D grid shared by the Eulerian fluids, holding the face
+/// velocities, the solid state of the cells, the boundary conditions and the
+/// pressure projection.
+///
+/// Cells use the `i * n + j` layout with `n = num_cells_y`, `u[i * n + j]` is
+/// the velocity on the left face and `v[i * n + j]` the one on the bottom face
+/// of cell (i, j). `s` is 0 for solid and 1 for open cells, and the outermost
+/// layer of cells are ghost cells set by the boundary conditions.
+pub struct MacGrid {
+    pub num_cells_x: usize,
+    pub num_cells_y: usize,
+    pub num_cells: usize,
+    pub h: f32,
+    pub u: Vec<f32>,
+    pub v: Vec<f32>,
+    pub s: Vec<f32>,
+    pub p: Vec<f32>,
+    pub div: Vec<f32>,
+    pub boundaries: Boundaries,
+    pub pressure: PressureSolver,
+}
+
+impl MacGrid {
+    #[must_use]
+    pub fn new(num_cells_x: usize, num_cells_y: usize, h: f32, kind: BoundaryKind) -> Self {
+        let num_cells = num_cells_x * num_cells_y;
+        Self {
+            num_cells_x,
+            num_cells_y,
+            num_cells,
+            h,
+            u: vec![0.0; num_cells],
+            v: vec![0.0; num_cells],
+            s: vec![1.0; num_cells],
+            p: vec![0.0; num_cells],
+            div: vec![0.0; num_cells],
+            boundaries: Boundaries::new(num_cells_x, num_cells_y, kind),
+            pressure: PressureSolver::new(num_cells_x, num_cells_y),
+        }
+    }
+
+    /// Applies the boundary conditions to the ghost cells and the boundary
+    /// velocities
+    pub fn apply_boundaries(&mut self) {
+        self.boundaries.set_ghost_cells(&mut self.s);
+        self.boundaries.apply_velocity(&mut self.u, &mut self.v);
+    }
+
+    /// Returns the four grid indices and bilinear weights used to sample a
+    /// quantity stored at `field` at position (x, y)
+    #[must_use]
+    pub fn sample_stencil(&self, x: f32, y: f32, field: Field) -> ([usize; 4], [f32; 4]) {
+        let n = self.num_cells_y;
+        let h = self.h;
+        let h1 = 1.0 / h;
+        let h2 = 0.5 * h;
+        let (x, y) = self.boundaries.wrap(x, y, h);
+        let x = x.clamp(h, self.num_cells_x as f32 * h);
+        let y = y.clamp(h, self.num_cells_y as f32 * h);
+
+        let (dx, dy) = match field {
+            Field::U => (0.0, h2),
+            Field::V => (h2, 0.0),
+            Field::Center => (h2, h2),
+        };
+
+        let x0 = f32::min(f32::floor((x - dx) * h1), (self.num_cells_x - 1) as f32) as usize;
+        let tx = ((x - dx) - x0 as f32 * h) * h1;
+        let x1 = usize::min(x0 + 1, self.num_cells_x - 1);
+
+        let y0 = f32::min(f32::floor((y - dy) * h1), (self.num_cells_y - 1) as f32) as usize;
+        let ty = ((y - dy) - y0 as f32 * h) * h1;
+        let y1 = usize::min(y0 + 1, self.num_cells_y - 1);
+
+        let sx = 1.0 - tx;
+        let sy = 1.0 - ty;
+
+        (
+            [x0 * n + y0, x1 * n + y0, x1 * n + y1, x0 * n + y1],
+            [sx * sy, tx * sy, tx * ty, sx * ty],
+        )
+    }
+
+    /// Samples the values `f` stored at `field` at position (x, y)
+    #[must_use]
+    pub fn sample(&self, f: &[f32], x: f32, y: f32, field: Field) -> f32 {
+        let (ids, weights) = self.sample_stencil(x, y, field);
+        ids.iter().zip(weights).map(|(&id, w)| w * f[id]).sum()
+    }
+
+    /// Samples the horizontal velocity at position (x, y)
+    #[must_use]
+    pub fn sample_u(&self, x: f32, y: f32) -> f32 {
+        self.sample(&self.u, x, y, Field::U)
+    }
+
+    /// Samples the vertical velocity at position (x, y)
+    #[must_use]
+    pub fn sample_v(&self, x: f32, y: f32) -> f32 {
+        self.sample(&self.v, x, y, Field::V)
+    }
+
+    /// Horizontal velocity at the bottom face of cell (i, j)
+    #[must_use]
+    pub fn avg_u(&self, i: usize, j: usize) -> f32 {
+        let n = self.num_cells_y;
+        (self.u[i * n + j - 1]
+            + self.u[i * n + j]
+            + self.u[(i + 1) * n + j - 1]
+            + self.u[(i + 1) * n + j])
+            * 0.25
+    }
+
+    /// Vertical velocity at the left face of cell (i, j)
+    #[must_use]
+    pub fn avg_v(&self, i: usize, j: usize) -> f32 {
+        let n = self.num_cells_y;
+        (self.v[(i - 1) * n + j]
+            + self.v[i * n + j]
+            + self.v[(i - 1) * n + j + 1]
+            + self.v[i * n + j + 1])
+            * 0.25
+    }
+
+    /// Computes the divergence minus `source` of all open interior cells for
+    /// which `is_fluid` holds and returns its maximum magnitude
+    pub fn update_divergence(
+        &mut self,
+        is_fluid: impl Fn(usize) -> bool,
+        source: impl Fn(usize) -> f32,
+    ) -> f32 {
+        let n = self.num_cells_y;
+        let mut max_div: f32 = 0.0;
+        self.div.fill(0.0);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let center = i * n + j;
+                let s = self.s[(i - 1) * n + j]
+                    + self.s[(i + 1) * n + j]
+                    + self.s[center - 1]
+                    + self.s[center + 1];
+                if self.s[center] == 0.0 || !is_fluid(center) || s == 0.0 {
+                    continue;
+                }
+                let div = self.u[(i + 1) * n + j] - self.u[center] + self.v[center + 1]
+                    - self.v[center]
+                    - source(center);
+                self.div[center] = div;
+                max_div = max_div.max(div.abs());
+            }
+        }
+        max_div
+    }
+
+    /// Makes the velocity field divergence free in all open interior cells for
+    /// which `is_fluid` holds, or rather makes the divergence match `source`,
+    /// and accumulates the pressures into `p` in velocity units, callers scale
+    /// them by `density * h / dt`. The optional face `weights` scale the
+    /// correction of each face, see `PressureSolver::solve_weighted`.
+    pub fn solve_incompressibility(
+        &mut self,
+        solver: PressureSolverKind,
+        num_iters: usize,
+        over_relaxation: f32,
+        weights: Option<(&[f32], &[f32])>,
+        is_fluid: impl Fn(usize) -> bool,
+        source: impl Fn(usize) -> f32,
+    ) {
+        self.pressure.periodic_x = self.boundaries.is_periodic_x();
+        self.pressure.periodic_y = self.boundaries.is_periodic_y();
+        match solver {
+            PressureSolverKind::GaussSeidel => {
+                self.solve_gauss_seidel(num_iters, over_relaxation, weights, &is_fluid, &source);
+                self.pressure.num_iters = num_iters;
+                self.pressure.residual = self.update_divergence(&is_fluid, &source);
+            }
+            PressureSolverKind::ConjugateGradient => {
+                self.solve_conjugate_gradient(weights, &is_fluid, &source);
+            }
+        }
+    }
+
+    fn solve_conjugate_gradient(
+        &mut self,
+        weights: Option<(&[f32], &[f32])>,
+        is_fluid: impl Fn(usize) -> bool,
+        source: impl Fn(usize) -> f32,
+    ) {
+        self.update_divergence(&is_fluid, source);
+        let mut p = std::mem::take(&mut self.p);
+        match weights {
+            Some((weight_u, weight_v)) => {
+                self.pressure
+                    .solve_weighted(&self.s, weight_u, weight_v, &self.div, is_fluid, &mut p);
+            }
+            None => self.pressure.solve(&self.s, &self.div, is_fluid, &mut p),
+        }
+
+        let n = self.num_cells_y;
+        self.boundaries.store_periodic_faces(&self.u, &self.v);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let center = i * n + j;
+                if !self.pressure.is_solved(center) {
+                    continue;
+                }
+                let [sx0, sx1, sy0, sy1] = self.face_coeffs(weights, i, j);
+                let pc = p[center];
+                self.u[center] -= sx0 * pc;
+                self.u[(i + 1) * n + j] += sx1 * pc;
+                self.v[center] -= sy0 * pc;
+                self.v[center + 1] += sy1 * pc;
+            }
+        }
+        self.boundaries
+            .merge_periodic_faces(&mut self.u, &mut self.v);
+        self.p = p;
+    }
+
+    fn solve_gauss_seidel(
+        &mut self,
+        num_iters: usize,
+        over_relaxation: f32,
+        weights: Option<(&[f32], &[f32])>,
+        is_fluid: impl Fn(usize) -> bool,
+        source: impl Fn(usize) -> f32,
+    ) {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        let periodic_x = self.boundaries.is_periodic_x();
+        let periodic_y = self.boundaries.is_periodic_y();
+        for _ in 0..num_iters {
+            for i in 1..self.num_cells_x - 1 {
+                for j in 1..self.num_cells_y - 1 {
+                    let center = i * n + j;
+                    if self.s[center] == 0.0 || !is_fluid(center) {
+                        continue;
+                    }
+
+                    let [sx0, sx1, sy0, sy1] = self.face_coeffs(weights, i, j);
+                    let s = sx0 + sx1 + sy0 + sy1;
+                    if s == 0.0 {
+                        continue;
+                    }
+
+                    let right = (i + 1) * n + j;
+                    let div = self.u[right] - self.u[center] + self.v[center + 1]
+                        - self.v[center]
+                        - source(center);
+                    let p = -div / s * over_relaxation;
+                    self.p[center] += p;
+
+                    self.u[center] -= sx0 * p;
+                    self.u[right] += sx1 * p;
+                    self.v[center] -= sy0 * p;
+                    self.v[center + 1] += sy1 * p;
+
+                    // both copies of a periodic seam face stay equal, so that
+                    // the cells at the far end see this correction
+                    if periodic_x && i == 1 {
+                        self.u[(nx - 1) * n + j] = self.u[center];
+                    }
+                    if periodic_x && i == nx - 2 {
+                        self.u[n + j] = self.u[right];
+                    }
+                    if periodic_y && j == 1 {
+                        self.v[i * n + n - 1] = self.v[center];
+                    }
+                    if periodic_y && j == n - 2 {
+                        self.v[i * n + 1] = self.v[center + 1];
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scale of the pressure correction of the left, right, bottom and top
+    /// faces of cell (i, j), zero for faces towards solid cells
+    #[must_use]
+    fn face_coeffs(&self, weights: Option<(&[f32], &[f32])>, i: usize, j: usize) -> [f32; 4] {
+        let n = self.num_cells_y;
+        let center = i * n + j;
+        let right = (i + 1) * n + j;
+        let mut coeffs = [
+            self.s[(i - 1) * n + j],
+            self.s[right],
+            self.s[center - 1],
+            self.s[center + 1],
+        ];
+        // faces of heavier fluid are accelerated less
+        if let Some((weight_u, weight_v)) = weights {
+            coeffs[0] *= weight_u[center];
+            coeffs[1] *= weight_u[right];
+            coeffs[2] *= weight_v[center];
+            coeffs[3] *= weight_v[center + 1];
+        }
+        coeffs
+    }
+}
@@ -0,0 +1,157 @@
+use glam::{Vec2, Vec3, Vec4};
+use rayon::prelude::*;
+
+/// Area-weighted vertex normals and, if UVs are given, tangent frames of a
+/// deforming triangle mesh. Simulations update these after stepping and share
+/// them with JS via pointer getters, just like particle positions.
+pub struct SurfaceNormals {
+    tri_ids: Vec<[usize; 3]>,
+    // triangles around each vertex, the ones of vertex i are
+    // vert_tri_ids[vert_tri_start[i]..vert_tri_start[i + 1]]
+    vert_tri_start: Vec<usize>,
+    vert_tri_ids: Vec<usize>,
+    face_normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    pub normals: Vec<Vec3>,
+    // xyz is the tangent, w the handedness of the bitangent
+    pub tangents: Vec<Vec4>,
+    bitangents: Vec<Vec3>,
+}
+
+impl SurfaceNormals {
+    #[must_use]
+    pub fn new(num_verts: usize, tri_ids: &[[usize; 3]]) -> Self {
+        let mut vert_tri_start = vec![0; num_verts + 1];
+        for tri in tri_ids {
+            for &id in tri {
+                vert_tri_start[id + 1] += 1;
+            }
+        }
+        for i in 0..num_verts {
+            vert_tri_start[i + 1] += vert_tri_start[i];
+        }
+        let mut vert_tri_ids = vec![0; vert_tri_start[num_verts]];
+        let mut fill = vert_tri_start.clone();
+        for (t, tri) in tri_ids.iter().enumerate() {
+            for &id in tri {
+                vert_tri_ids[fill[id]] = t;
+                fill[id] += 1;
+            }
+        }
+
+        Self {
+            tri_ids: tri_ids.to_vec(),
+            vert_tri_start,
+            vert_tri_ids,
+            face_normals: vec![Vec3::ZERO; tri_ids.len()],
+            uvs: vec![],
+            normals: vec![Vec3::ZERO; num_verts],
+            tangents: vec![],
+            bitangents: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn from_flat_ids(num_verts: usize, tri_ids: &[usize]) -> Self {
+        let tri_ids: Vec<[usize; 3]> = tri_ids
+            .chunks_exact(3)
+            .map(|ids| [ids[0], ids[1], ids[2]])
+            .collect();
+        Self::new(num_verts, &tri_ids)
+    }
+
+    /// Enables tangent generation from per-vertex texture coordinates.
+    #[must_use]
+    pub fn with_uvs(mut self, uvs: Vec<Vec2>) -> Self {
+        let num_verts = self.normals.len();
+        self.uvs = uvs;
+        self.tangents = vec![Vec4::ZERO; num_verts];
+        self.bitangents = vec![Vec3::ZERO; num_verts];
+        self
+    }
+
+    pub fn update(&mut self, pos: &[Vec3]) {
+        self.normals.fill(Vec3::ZERO);
+
+        // the unnormalized cross product weights each face normal by its area
+        for &[id0, id1, id2] in &self.tri_ids {
+            let normal = (pos[id1] - pos[id0]).cross(pos[id2] - pos[id0]);
+            self.normals[id0] += normal;
+            self.normals[id1] += normal;
+            self.normals[id2] += normal;
+        }
+        self.normals
+            .iter_mut()
+            .for_each(|n| *n = n.normalize_or_zero());
+
+        if !self.uvs.is_empty() {
+            self.update_tangents(pos);
+        }
+    }
+
+    /// Same as `update`, but the normals are computed on the rayon thread
+    /// pool. Each vertex gathers the normals of its triangles, so no two
+    /// threads write to the same vertex. Tangents are still updated serially.
+    pub fn par_update(&mut self, pos: &[Vec3]) {
+        self.face_normals
+            .par_iter_mut()
+            .zip(self.tri_ids.par_iter())
+            .for_each(|(normal, &[id0, id1, id2])| {
+                *normal = (pos[id1] - pos[id0]).cross(pos[id2] - pos[id0]);
+            });
+        let vert_tri_start = &self.vert_tri_start;
+        let vert_tri_ids = &self.vert_tri_ids;
+        let face_normals = &self.face_normals;
+        self.normals.par_iter_mut().enumerate().for_each(|(i, n)| {
+            *n = vert_tri_ids[vert_tri_start[i]..vert_tri_start[i + 1]]
+                .iter()
+                .map(|&t| face_normals[t])
+                .sum::<Vec3>()
+                .normalize_or_zero();
+        });
+
+        if !self.uvs.is_empty() {
+            self.update_tangents(pos);
+        }
+    }
+
+    fn update_tangents(&mut self, pos: &[Vec3]) {
+        self.tangents.fill(Vec4::ZERO);
+        self.bitangents.fill(Vec3::ZERO);
+
+        for &[id0, id1, id2] in &self.tri_ids {
+            let e0 = pos[id1] - pos[id0];
+            let e1 = pos[id2] - pos[id0];
+            let duv0 = self.uvs[id1] - self.uvs[id0];
+            let duv1 = self.uvs[id2] - self.uvs[id0];
+            let det = duv0.perp_dot(duv1);
+            if det == 0.0 {
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = ((e0 * duv1.y - e1 * duv0.y) * r).extend(0.0);
+            let bitangent = (e1 * duv0.x - e0 * duv1.x) * r;
+            for id in [id0, id1, id2] {
+                self.tangents[id] += tangent;
+                self.bitangents[id] += bitangent;
+            }
+        }
+
+        // Gram-Schmidt orthogonalize against the normal
+        for ((t, b), n) in self
+            .tangents
+            .iter_mut()
+            .zip(&self.bitangents)
+            .zip(&self.normals)
+        {
+            let tangent = t.truncate();
+            let tangent = (tangent - *n * n.dot(tangent)).normalize_or_zero();
+            let w = if n.cross(tangent).dot(*b) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            *t = tangent.extend(w);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use glam::{vec3, Vec3};
 use rayon::prelude::*;
 use wasm_bindgen::prelude::*;
 
+use crate::normals::SurfaceNormals;
+
 // must be exported to init rayon thread pool with web workers
 #[allow(unused_imports)]
 pub use wasm_bindgen_rayon::init_thread_pool;
@@ -39,6 +41,7 @@ impl SolverPass {
 
 #[wasm_bindgen(js_name = ParallelClothSolverKind)]
 #[derive(PartialEq, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)] // exported to JS under these names
 pub enum SolverKind {
     COLORING,
     JACOBI,
@@ -75,7 +78,7 @@ pub struct ParallelClothSimulation {
     obstacle_pos: Vec3,
     #[wasm_bindgen(readonly)]
     pub obstacle_radius: f32,
-    normals: Vec<Vec3>,
+    normals: SurfaceNormals,
 
     grab_inv_mass: f32,
     grab_id: Option<usize>,
@@ -102,10 +105,10 @@ impl ParallelClothSimulation {
         let mut num_x = num_x;
         let mut num_y = num_y;
         if num_x % 2 == 1 {
-            num_x = num_x + 1;
+            num_x += 1;
         }
         if num_y % 2 == 1 {
-            num_y = num_y + 1;
+            num_y += 1;
         }
 
         let num_particles = (num_x + 1) * (num_y + 1);
@@ -214,6 +217,7 @@ impl ParallelClothSimulation {
             }
         }
 
+        let normals = SurfaceNormals::new(num_particles, &tri_ids);
         let dt = TIME_STEP / Into::<f32>::into(num_substeps);
         Self {
             num_particles,
@@ -239,7 +243,7 @@ impl ParallelClothSimulation {
 
             obstacle_pos: DEFAULT_OBSTACLE_POS,
             obstacle_radius: DEFAULT_OBSTACLE_RADIUS,
-            normals: vec![Vec3::ZERO; num_particles],
+            normals,
 
             grab_inv_mass: 0.0,
             grab_id: None,
@@ -253,7 +257,7 @@ impl ParallelClothSimulation {
 
     #[wasm_bindgen(getter)]
     pub fn normals(&self) -> *const Vec3 {
-        self.normals.as_ptr()
+        self.normals.normals.as_ptr()
     }
 
     #[wasm_bindgen(getter)]
@@ -350,7 +354,7 @@ impl ParallelClothSimulation {
             let l0 = self.rest_lengths[cid];
             let dp = n * (l - l0) / w;
 
-            // NOTE: here and below use of add_unsync in `update_normals` are a very simple
+            // NOTE: the use of add_unsync below is a very simple
             // hack to allow possible simultaneous writes from multiple parallel iterator
             // threads. Using a RWLock, for example, would lead to an untenable performance
             // penalty. This kind of approach works since in the case of simultaneous writes,
@@ -418,33 +422,7 @@ impl ParallelClothSimulation {
     }
 
     pub fn update_normals(&mut self) {
-        self.normals.fill(Vec3::ZERO);
-
-        // add normals
-        let pos_cell = SyncUnsafeCell::new(&mut self.pos);
-        let normal_cell = SyncUnsafeCell::new(&mut self.normals);
-        (0..self.num_tris).into_par_iter().for_each(|i| {
-            let [id0, id1, id2] = self.tri_ids[i];
-            let p0: Vec3;
-            let p1: Vec3;
-            let p2: Vec3;
-            unsafe {
-                p0 = get_unsync(pos_cell.get(), id0);
-                p1 = get_unsync(pos_cell.get(), id1);
-                p2 = get_unsync(pos_cell.get(), id2);
-            }
-            let normal = (p1 - p0).cross(p2 - p0);
-            unsafe {
-                add_unsync(normal_cell.get(), id0, normal);
-                add_unsync(normal_cell.get(), id1, normal);
-                add_unsync(normal_cell.get(), id2, normal);
-            }
-        });
-
-        // normalize normals
-        self.normals.par_iter_mut().for_each(|n| {
-            *n = n.normalize();
-        });
+        self.normals.par_update(&self.pos);
     }
 
     pub fn start_grab(&mut self, _: usize, pos: &[f32]) {
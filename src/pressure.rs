@@ -0,0 +1,380 @@
+use wasm_bindgen::prelude::*;
+
+const DEFAULT_TOLERANCE: f32 = 1e-4;
+const DEFAULT_MAX_ITERS: usize = 500;
+const MIC_TUNING: f32 = 0.97;
+const MIC_SAFETY: f32 = 0.25;
+
+#[wasm_bindgen]
+#[derive(PartialEq, Clone, Copy)]
+pub enum PressureSolverKind {
+    GaussSeidel,
+    ConjugateGradient,
+}
+
+/// Pressure projection for the 2D grid fluids using a conjugate gradient
+/// solver with a modified incomplete Cholesky preconditioner, see Bridson,
+/// "Fluid Simulation for Computer Graphics", chapter 5.
+///
+/// Cells use the `i * n + j` layout with `n = num_cells_y`, and `s` holds the
+/// open (1) or solid (0) state of each cell like in the Gauss-Seidel solvers.
+/// Pressure is solved in velocity units, i.e. the face velocity between two
+/// cells is corrected by the difference of their pressures, scaled by the
+/// weight of the face if the solve is weighted.
+///
+/// Periodic domains couple the interior cells at opposite edges across the
+/// ghost cells. The preconditioner ignores these couplings.
+pub struct PressureSolver {
+    num_cells_x: usize,
+    num_cells_y: usize,
+    pub tolerance: f32,
+    pub max_iters: usize,
+    pub periodic_x: bool,
+    pub periodic_y: bool,
+    pub num_iters: usize,
+    pub residual: f32,
+
+    solved: Vec<bool>,
+    has_open_boundary: bool,
+    diag: Vec<f32>,
+    plus_x: Vec<f32>,
+    plus_y: Vec<f32>,
+    precon: Vec<f32>,
+    r: Vec<f32>,
+    z: Vec<f32>,
+    search: Vec<f32>,
+    tmp: Vec<f32>,
+}
+
+impl PressureSolver {
+    #[must_use]
+    pub fn new(num_cells_x: usize, num_cells_y: usize) -> Self {
+        let num_cells = num_cells_x * num_cells_y;
+        Self {
+            num_cells_x,
+            num_cells_y,
+            tolerance: DEFAULT_TOLERANCE,
+            max_iters: DEFAULT_MAX_ITERS,
+            periodic_x: false,
+            periodic_y: false,
+            num_iters: 0,
+            residual: 0.0,
+
+            solved: vec![false; num_cells],
+            has_open_boundary: false,
+            diag: vec![0.0; num_cells],
+            plus_x: vec![0.0; num_cells],
+            plus_y: vec![0.0; num_cells],
+            precon: vec![0.0; num_cells],
+            r: vec![0.0; num_cells],
+            z: vec![0.0; num_cells],
+            search: vec![0.0; num_cells],
+            tmp: vec![0.0; num_cells],
+        }
+    }
+
+    /// Whether the last solve included cell `id`
+    #[must_use]
+    pub fn is_solved(&self, id: usize) -> bool {
+        self.solved[id]
+    }
+
+    /// Solves for the pressures `p` which remove the divergence `div` of all
+    /// interior cells for which `is_fluid` holds. Cells which are not solved
+    /// are treated as zero pressure if open and as walls if solid.
+    pub fn solve(
+        &mut self,
+        s: &[f32],
+        div: &[f32],
+        is_fluid: impl Fn(usize) -> bool,
+        p: &mut [f32],
+    ) {
+        self.solve_faces(s, None, div, is_fluid, p);
+    }
+
+    /// Like `solve`, with the coupling across each face scaled by `weight_u`
+    /// and `weight_v`, which are laid out like the face velocities. Fluids of
+    /// varying density pass the inverse relative density of each face, so
+    /// that pressure accelerates light fluid more than heavy fluid.
+    pub fn solve_weighted(
+        &mut self,
+        s: &[f32],
+        weight_u: &[f32],
+        weight_v: &[f32],
+        div: &[f32],
+        is_fluid: impl Fn(usize) -> bool,
+        p: &mut [f32],
+    ) {
+        self.solve_faces(s, Some((weight_u, weight_v)), div, is_fluid, p);
+    }
+
+    fn solve_faces(
+        &mut self,
+        s: &[f32],
+        weights: Option<(&[f32], &[f32])>,
+        div: &[f32],
+        is_fluid: impl Fn(usize) -> bool,
+        p: &mut [f32],
+    ) {
+        self.build_matrix(s, weights, is_fluid);
+        self.build_preconditioner();
+
+        p.fill(0.0);
+        for (r, (&div, &solved)) in self.r.iter_mut().zip(div.iter().zip(&self.solved)) {
+            *r = if solved { -div } else { 0.0 };
+        }
+        if !self.has_open_boundary {
+            self.remove_mean();
+        }
+
+        self.num_iters = 0;
+        self.residual = max_abs(&self.r);
+        if self.residual <= self.tolerance {
+            return;
+        }
+
+        self.apply_preconditioner();
+        self.search.copy_from_slice(&self.z);
+        let mut sigma = dot(&self.z, &self.r);
+
+        while self.num_iters < self.max_iters {
+            self.num_iters += 1;
+
+            self.apply_matrix();
+            let denom = dot(&self.z, &self.search);
+            if denom == 0.0 {
+                break;
+            }
+            let alpha = sigma / denom;
+            for (p, &d) in p.iter_mut().zip(&self.search) {
+                *p += alpha * d;
+            }
+            for (r, &z) in self.r.iter_mut().zip(&self.z) {
+                *r -= alpha * z;
+            }
+
+            self.residual = max_abs(&self.r);
+            if self.residual <= self.tolerance {
+                break;
+            }
+
+            self.apply_preconditioner();
+            let sigma_new = dot(&self.z, &self.r);
+            let beta = sigma_new / sigma;
+            for (d, &z) in self.search.iter_mut().zip(&self.z) {
+                *d = z + beta * *d;
+            }
+            sigma = sigma_new;
+        }
+    }
+
+    /// Left, right, bottom and top neighbors of an interior cell
+    #[must_use]
+    fn neighbors(&self, id: usize) -> [usize; 4] {
+        let n = self.num_cells_y;
+        let nx = self.num_cells_x;
+        let (i, j) = (id / n, id % n);
+        let wrap_x = (nx - 3) * n;
+        let wrap_y = n - 3;
+        [
+            if self.periodic_x && i == 1 {
+                id + wrap_x
+            } else {
+                id - n
+            },
+            if self.periodic_x && i == nx - 2 {
+                id - wrap_x
+            } else {
+                id + n
+            },
+            if self.periodic_y && j == 1 {
+                id + wrap_y
+            } else {
+                id - 1
+            },
+            if self.periodic_y && j == n - 2 {
+                id - wrap_y
+            } else {
+                id + 1
+            },
+        ]
+    }
+
+    /// Couplings of an interior cell to its left, right, bottom and top
+    /// neighbors. Across periodic edges, the cells at both ends share the
+    /// face at the far end.
+    #[must_use]
+    fn face_weights(&self, s: &[f32], weights: Option<(&[f32], &[f32])>, id: usize) -> [f32; 4] {
+        let n = self.num_cells_y;
+        let [left, right, bottom, top] = self.neighbors(id);
+        let open = [s[left], s[right], s[bottom], s[top]];
+        match weights {
+            Some((weight_u, weight_v)) => [
+                open[0] * weight_u[left + n],
+                open[1] * weight_u[id + n],
+                open[2] * weight_v[bottom + 1],
+                open[3] * weight_v[id + 1],
+            ],
+            None => open,
+        }
+    }
+
+    fn build_matrix(
+        &mut self,
+        s: &[f32],
+        weights: Option<(&[f32], &[f32])>,
+        is_fluid: impl Fn(usize) -> bool,
+    ) {
+        let n = self.num_cells_y;
+
+        self.solved.fill(false);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let id = i * n + j;
+                let num_open: f32 = self.neighbors(id).iter().map(|&nb| s[nb]).sum();
+                self.solved[id] = s[id] != 0.0 && num_open != 0.0 && is_fluid(id);
+            }
+        }
+
+        self.has_open_boundary = false;
+        self.diag.fill(0.0);
+        self.plus_x.fill(0.0);
+        self.plus_y.fill(0.0);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let id = i * n + j;
+                if !self.solved[id] {
+                    continue;
+                }
+                let nbs = self.neighbors(id);
+                let w = self.face_weights(s, weights, id);
+                self.diag[id] = w.iter().sum();
+                for nb in nbs {
+                    if s[nb] != 0.0 && !self.solved[nb] {
+                        self.has_open_boundary = true;
+                    }
+                }
+                if self.solved[nbs[1]] {
+                    self.plus_x[id] = -w[1];
+                }
+                if self.solved[nbs[3]] {
+                    self.plus_y[id] = -w[3];
+                }
+            }
+        }
+    }
+
+    /// Without any open boundary the pressure is only defined up to a
+    /// constant, and the system is only solvable if the divergence sums to
+    /// zero. Removing the mean makes it consistent in the presence of
+    /// round-off or moving obstacles.
+    fn remove_mean(&mut self) {
+        let num_solved = self.solved.iter().filter(|&&solved| solved).count();
+        if num_solved == 0 {
+            return;
+        }
+        let mean = self.r.iter().sum::<f32>() / num_solved as f32;
+        for (r, &solved) in self.r.iter_mut().zip(&self.solved) {
+            if solved {
+                *r -= mean;
+            }
+        }
+    }
+
+    fn build_preconditioner(&mut self) {
+        let n = self.num_cells_y;
+
+        self.precon.fill(0.0);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let id = i * n + j;
+                if !self.solved[id] {
+                    continue;
+                }
+                let left = id - n;
+                let bottom = id - 1;
+                let px = self.plus_x[left] * self.precon[left];
+                let py = self.plus_y[bottom] * self.precon[bottom];
+                let mut e = self.diag[id]
+                    - px * px
+                    - py * py
+                    - MIC_TUNING
+                        * (self.plus_x[left] * self.plus_y[left] * self.precon[left].powi(2)
+                            + self.plus_y[bottom]
+                                * self.plus_x[bottom]
+                                * self.precon[bottom].powi(2));
+                if e < MIC_SAFETY * self.diag[id] {
+                    e = self.diag[id];
+                }
+                self.precon[id] = 1.0 / e.sqrt();
+            }
+        }
+    }
+
+    /// z = M^-1 r by forward and backward substitution
+    fn apply_preconditioner(&mut self) {
+        let n = self.num_cells_y;
+
+        self.tmp.fill(0.0);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let id = i * n + j;
+                if !self.solved[id] {
+                    continue;
+                }
+                let left = id - n;
+                let bottom = id - 1;
+                let t = self.r[id]
+                    - self.plus_x[left] * self.precon[left] * self.tmp[left]
+                    - self.plus_y[bottom] * self.precon[bottom] * self.tmp[bottom];
+                self.tmp[id] = t * self.precon[id];
+            }
+        }
+
+        self.z.fill(0.0);
+        for i in (1..self.num_cells_x - 1).rev() {
+            for j in (1..self.num_cells_y - 1).rev() {
+                let id = i * n + j;
+                if !self.solved[id] {
+                    continue;
+                }
+                let t = self.tmp[id]
+                    - self.plus_x[id] * self.precon[id] * self.z[id + n]
+                    - self.plus_y[id] * self.precon[id] * self.z[id + 1];
+                self.z[id] = t * self.precon[id];
+            }
+        }
+    }
+
+    /// z = A search
+    fn apply_matrix(&mut self) {
+        let n = self.num_cells_y;
+        let d = &self.search;
+
+        self.z.fill(0.0);
+        for i in 1..self.num_cells_x - 1 {
+            for j in 1..self.num_cells_y - 1 {
+                let id = i * n + j;
+                if !self.solved[id] {
+                    continue;
+                }
+                let [left, right, bottom, top] = self.neighbors(id);
+                self.z[id] = self.diag[id] * d[id]
+                    + self.plus_x[id] * d[right]
+                    + self.plus_x[left] * d[left]
+                    + self.plus_y[id] * d[top]
+                    + self.plus_y[bottom] * d[bottom];
+            }
+        }
+    }
+}
+
+#[must_use]
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[must_use]
+fn max_abs(a: &[f32]) -> f32 {
+    a.iter().fold(0.0, |max, a| f32::max(max, a.abs()))
+}
@@ -0,0 +1,283 @@
+//! Software rasterizer for RGBA byte buffers and a dependency-free PNG encoder,
+//! so the 2D demos can render frames without a canvas or WebGL context
+
+use glam::{Vec2, Vec3};
+
+// deflate stored blocks hold at most 65535 bytes
+const MAX_STORED_BLOCK_LEN: usize = 0xFFFF;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Draws shapes given in pixel coordinates, with the origin in the upper left
+/// corner, into a row-major RGBA buffer with colors in [0, 1]
+pub struct Raster<'a> {
+    width: usize,
+    height: usize,
+    pixels: &'a mut [u8],
+}
+
+impl<'a> Raster<'a> {
+    /// # Panics
+    /// Will panic if `pixels` does not hold `4 * width * height` bytes
+    pub fn new(width: usize, height: usize, pixels: &'a mut [u8]) -> Self {
+        assert_eq!(pixels.len(), 4 * width * height, "buffer size mismatch");
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn clear(&mut self, color: Vec3) {
+        let color = to_rgba(color);
+        for p in self.pixels.chunks_exact_mut(4) {
+            p.copy_from_slice(&color);
+        }
+    }
+
+    /// Blends `color` over the pixel (x, y), pixels outside are ignored
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Vec3, alpha: f32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let p = 4 * (y as usize * self.width + x as usize);
+        let alpha = alpha.clamp(0.0, 1.0);
+        let src = to_rgba(color);
+        let dest = &mut self.pixels[p..p + 4];
+        for (d, s) in dest.iter_mut().zip(&src[0..3]) {
+            let c = f32::from(*d);
+            *d = (c + (f32::from(*s) - c) * alpha).round() as u8;
+        }
+        dest[3] = 255;
+    }
+
+    /// Calls `f` with every pixel of the bounding box [min, max] clipped to
+    /// the raster and the position of the pixel center
+    fn for_each_pixel(
+        &mut self,
+        min: Vec2,
+        max: Vec2,
+        mut f: impl FnMut(&mut Self, i32, i32, Vec2),
+    ) {
+        let x0 = f32::max(min.x.floor(), 0.0) as i32;
+        let y0 = f32::max(min.y.floor(), 0.0) as i32;
+        let x1 = f32::min(max.x.ceil(), self.width as f32) as i32;
+        let y1 = f32::min(max.y.ceil(), self.height as f32) as i32;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                f(self, x, y, Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, min: Vec2, max: Vec2, color: Vec3, alpha: f32) {
+        self.for_each_pixel(min, max, |r, x, y, _| r.blend_pixel(x, y, color, alpha));
+    }
+
+    /// Fills a disk, disks smaller than a pixel still cover their center pixel
+    pub fn fill_disk(&mut self, center: Vec2, radius: f32, color: Vec3) {
+        let radius = radius.max(0.5);
+        let r = Vec2::splat(radius);
+        self.for_each_pixel(center - r, center + r, |raster, x, y, p| {
+            if p.distance_squared(center) <= radius * radius {
+                raster.blend_pixel(x, y, color, 1.0);
+            }
+        });
+    }
+
+    pub fn stroke_circle(&mut self, center: Vec2, radius: f32, line_width: f32, color: Vec3) {
+        let half_width = 0.5 * line_width.max(1.0);
+        let r = Vec2::splat(radius + half_width);
+        self.for_each_pixel(center - r, center + r, |raster, x, y, p| {
+            if (p.distance(center) - radius).abs() <= half_width {
+                raster.blend_pixel(x, y, color, 1.0);
+            }
+        });
+    }
+
+    /// Draws the segment from `a` to `b`, lines thinner than a pixel are drawn
+    /// one pixel wide
+    pub fn draw_line(&mut self, a: Vec2, b: Vec2, line_width: f32, color: Vec3) {
+        let half_width = 0.5 * line_width.max(1.0);
+        let d = b - a;
+        let len2 = d.length_squared();
+        let r = Vec2::splat(half_width);
+        self.for_each_pixel(a.min(b) - r, a.max(b) + r, |raster, x, y, p| {
+            let t = if len2 > 0.0 {
+                ((p - a).dot(d) / len2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            if p.distance_squared(a + t * d) <= half_width * half_width {
+                raster.blend_pixel(x, y, color, 1.0);
+            }
+        });
+    }
+
+    pub fn draw_polyline(&mut self, points: &[Vec2], line_width: f32, color: Vec3) {
+        for w in points.windows(2) {
+            self.draw_line(w[0], w[1], line_width, color);
+        }
+    }
+
+    /// Fills a closed polygon with the even-odd rule
+    pub fn fill_polygon(&mut self, points: &[Vec2], color: Vec3) {
+        if points.len() < 3 {
+            return;
+        }
+        let min = points.iter().fold(Vec2::INFINITY, |m, &p| m.min(p));
+        let max = points.iter().fold(Vec2::NEG_INFINITY, |m, &p| m.max(p));
+        let y0 = f32::max(min.y.floor(), 0.0) as i32;
+        let y1 = f32::min(max.y.ceil(), self.height as f32) as i32;
+
+        let mut crossings = vec![];
+        for y in y0..y1 {
+            let py = y as f32 + 0.5;
+            crossings.clear();
+            let mut prev = points[points.len() - 1];
+            for &curr in points {
+                if (curr.y > py) != (prev.y > py) {
+                    crossings.push(prev.x + (py - prev.y) / (curr.y - prev.y) * (curr.x - prev.x));
+                }
+                prev = curr;
+            }
+            crossings.sort_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                // pixels whose centers lie within the span
+                let x0 = f32::max((span[0] - 0.5).ceil(), 0.0) as i32;
+                let x1 = f32::min((span[1] - 0.5).ceil(), self.width as f32) as i32;
+                for x in x0..x1 {
+                    self.blend_pixel(x, y, color, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Fills a list of triangles given as consecutive vertex triples
+    pub fn fill_triangles(&mut self, vertices: &[Vec2], color: Vec3) {
+        for t in vertices.chunks_exact(3) {
+            self.fill_polygon(t, color);
+        }
+    }
+
+    /// Draws a list of segments given as consecutive vertex pairs
+    pub fn draw_lines(&mut self, vertices: &[Vec2], line_width: f32, color: Vec3) {
+        for l in vertices.chunks_exact(2) {
+            self.draw_line(l[0], l[1], line_width, color);
+        }
+    }
+}
+
+fn to_rgba(color: Vec3) -> [u8; 4] {
+    let c = (255.0 * color.clamp(Vec3::ZERO, Vec3::ONE)).round();
+    [c.x as u8, c.y as u8, c.z as u8, 255]
+}
+
+/// Renders a frame of the given size with `draw` into a buffer that starts
+/// out opaque white and encodes it as PNG
+pub fn render_png(width: usize, height: usize, draw: impl FnOnce(&mut [u8])) -> Vec<u8> {
+    let mut pixels = vec![255; 4 * width * height];
+    draw(&mut pixels);
+    encode_png(width, height, &pixels)
+}
+
+/// Encodes a row-major RGBA buffer as an uncompressed 8 bit RGBA PNG
+///
+/// # Panics
+/// Will panic if `rgba` does not hold `4 * width * height` bytes
+#[must_use]
+pub fn encode_png(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    assert_eq!(rgba.len(), 4 * width * height, "buffer size mismatch");
+
+    // every scanline starts with its filter type, 0 for none
+    let mut scanlines = Vec::with_capacity(rgba.len() + height);
+    for row in rgba.chunks_exact(4 * width) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth, color type RGBA, compression, filter, interlace
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let num_blocks = usize::max(data.len().div_ceil(MAX_STORED_BLOCK_LEN), 1);
+    let mut out = Vec::with_capacity(data.len() + 5 * num_blocks + 6);
+    // deflate with a 32K window and no preset dictionary
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(u8::from(is_final));
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1, 0);
+    // the sums cannot overflow within 5552 bytes
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(0xFFFF_FFFF, |crc: u32, &byte| {
+        CRC_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
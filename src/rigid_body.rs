@@ -0,0 +1,307 @@
+use std::f32::consts::PI;
+
+use glam::{Mat2, Vec2};
+
+const RESTITUTION: f32 = 0.2;
+const FRICTION: f32 = 0.4;
+// points this close to a body are in contact, e.g. the corners of
+// stacked boxes which lie exactly on the side of the box below
+const CONTACT_MARGIN: f32 = 1e-3;
+const CIRCLE_NUM_SEGS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+    Box { half_extents: Vec2 },
+}
+
+/// A 2D rigid body. Kinematic bodies, which are moved by the user and are
+/// not affected by collisions, have zero inverse mass and inertia.
+pub struct RigidBody {
+    pub shape: Shape,
+    pub pos: Vec2,
+    pub angle: f32,
+    pub vel: Vec2,
+    pub omega: f32,
+    pub inv_mass: f32,
+    pub inv_inertia: f32,
+}
+
+impl RigidBody {
+    #[must_use]
+    pub fn new(shape: Shape, pos: Vec2, angle: f32, density: f32) -> Self {
+        let (mass, inertia) = match shape {
+            Shape::Circle { radius } => {
+                let mass = density * PI * radius * radius;
+                (mass, 0.5 * mass * radius * radius)
+            }
+            Shape::Box { half_extents } => {
+                let mass = density * 4.0 * half_extents.x * half_extents.y;
+                (mass, mass * half_extents.length_squared() / 3.0)
+            }
+        };
+        Self {
+            shape,
+            pos,
+            angle,
+            vel: Vec2::ZERO,
+            omega: 0.0,
+            inv_mass: 1.0 / mass,
+            inv_inertia: 1.0 / inertia,
+        }
+    }
+
+    #[must_use]
+    pub fn kinematic(shape: Shape, pos: Vec2, vel: Vec2) -> Self {
+        Self {
+            shape,
+            pos,
+            angle: 0.0,
+            vel,
+            omega: 0.0,
+            inv_mass: 0.0,
+            inv_inertia: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub fn point_vel(&self, x: Vec2) -> Vec2 {
+        self.vel + self.omega * (x - self.pos).perp()
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vec2, x: Vec2) {
+        self.vel += impulse * self.inv_mass;
+        self.omega += (x - self.pos).perp_dot(impulse) * self.inv_inertia;
+    }
+
+    #[must_use]
+    pub fn area(&self) -> f32 {
+        match self.shape {
+            Shape::Circle { radius } => PI * radius * radius,
+            Shape::Box { half_extents } => 4.0 * half_extents.x * half_extents.y,
+        }
+    }
+
+    #[must_use]
+    pub fn bounding_radius(&self) -> f32 {
+        match self.shape {
+            Shape::Circle { radius } => radius,
+            Shape::Box { half_extents } => half_extents.length(),
+        }
+    }
+
+    /// Applies a linear and an angular impulse from a surrounding fluid,
+    /// together with the velocity change `gravity` of one step. The body
+    /// accelerates `added_mass` of the fluid along with it, and scaling the
+    /// inertia by the same ratio is a rough approximation. As the fluid
+    /// pushing the body cannot make it faster than the fluid itself, a
+    /// speed gain beyond `fluid_speed` is an overshoot of the explicit
+    /// coupling and is clamped.
+    pub fn apply_fluid_impulse(
+        &mut self,
+        impulse: Vec2,
+        angular_impulse: f32,
+        gravity: Vec2,
+        added_mass: f32,
+        fluid_speed: f32,
+    ) {
+        if self.inv_mass == 0.0 {
+            return;
+        }
+        let scale = 1.0 / (1.0 + added_mass * self.inv_mass);
+        let max_speed = f32::max(self.vel.length(), fluid_speed) + gravity.length();
+        let max_omega = f32::max(self.omega.abs(), fluid_speed / self.bounding_radius());
+        self.vel =
+            (self.vel + (impulse * self.inv_mass + gravity) * scale).clamp_length_max(max_speed);
+        self.omega =
+            (self.omega + angular_impulse * self.inv_inertia * scale).clamp(-max_omega, max_omega);
+    }
+
+    pub fn integrate(&mut self, dt: f32) {
+        self.pos += self.vel * dt;
+        self.angle += self.omega * dt;
+    }
+
+    /// Signed distance of `x` to the surface, negative inside, and the
+    /// outward normal of the closest surface point
+    #[must_use]
+    pub fn distance(&self, x: Vec2) -> (f32, Vec2) {
+        match self.shape {
+            Shape::Circle { radius } => {
+                let d = x - self.pos;
+                let len = d.length();
+                let normal = if len > 0.0 { d / len } else { Vec2::Y };
+                (len - radius, normal)
+            }
+            Shape::Box { half_extents } => {
+                let rot = Mat2::from_angle(self.angle);
+                let q = rot.transpose() * (x - self.pos);
+                let d = q.abs() - half_extents;
+                if d.x > 0.0 || d.y > 0.0 {
+                    let outside = d.max(Vec2::ZERO);
+                    let normal = rot * (outside * q.signum()).normalize();
+                    (outside.length(), normal)
+                } else if d.x > d.y {
+                    (d.x, rot * Vec2::new(q.x.signum(), 0.0))
+                } else {
+                    (d.y, rot * Vec2::new(0.0, q.y.signum()))
+                }
+            }
+        }
+    }
+
+    fn corners(&self, half_extents: Vec2) -> [Vec2; 4] {
+        let rot = Mat2::from_angle(self.angle);
+        [
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ]
+        .map(|c| self.pos + rot * (c * half_extents))
+    }
+
+    /// Furthest point of the body in direction `dir`
+    fn support(&self, dir: Vec2) -> Vec2 {
+        match self.shape {
+            Shape::Circle { radius } => self.pos + dir * radius,
+            Shape::Box { half_extents } => self
+                .corners(half_extents)
+                .into_iter()
+                .max_by(|a, b| a.dot(dir).total_cmp(&b.dot(dir)))
+                .unwrap_or(self.pos),
+        }
+    }
+
+    /// Surface points which may penetrate a body in direction `dir`, the
+    /// deepest point of circles and all corners of boxes
+    fn contact_points(&self, dir: Vec2) -> Vec<Vec2> {
+        match self.shape {
+            Shape::Circle { .. } => vec![self.support(dir)],
+            Shape::Box { half_extents } => self.corners(half_extents).to_vec(),
+        }
+    }
+
+    /// Resolves a penetration of depth `depth` at `x`, where `normal` points
+    /// from `b` towards `a`, by separating the bodies and applying a
+    /// collision impulse with restitution and Coulomb friction
+    fn resolve_contact(a: &mut Self, b: &mut Self, x: Vec2, normal: Vec2, depth: f32) {
+        let inv_mass = a.inv_mass + b.inv_mass;
+        if inv_mass == 0.0 {
+            return;
+        }
+        a.pos += normal * depth * a.inv_mass / inv_mass;
+        b.pos -= normal * depth * b.inv_mass / inv_mass;
+
+        let vel = a.point_vel(x) - b.point_vel(x);
+        let vn = vel.dot(normal);
+        if vn >= 0.0 {
+            return;
+        }
+        let ra = x - a.pos;
+        let rb = x - b.pos;
+        let eff_inv_mass = |dir: Vec2| {
+            inv_mass
+                + a.inv_inertia * ra.perp_dot(dir).powi(2)
+                + b.inv_inertia * rb.perp_dot(dir).powi(2)
+        };
+        let jn = -(1.0 + RESTITUTION) * vn / eff_inv_mass(normal);
+
+        let tangent_vel = vel - vn * normal;
+        let mut impulse = jn * normal;
+        if tangent_vel.length_squared() > 0.0 {
+            let tangent = tangent_vel.normalize();
+            let jt = -tangent_vel.length() / eff_inv_mass(tangent);
+            impulse += jt.max(-FRICTION * jn) * tangent;
+        }
+        a.apply_impulse(impulse, x);
+        b.apply_impulse(-impulse, x);
+    }
+
+    /// Collides two bodies, testing each against the other
+    pub fn collide(a: &mut Self, b: &mut Self) {
+        Self::collide_points(a, b);
+        Self::collide_points(b, a);
+    }
+
+    /// Tests the contact points of `a` against the shape of `b`. All contacts
+    /// share the normal of `b` towards the center of `a`, as the normals at
+    /// the points themselves are ambiguous near corners, and their depth is
+    /// measured along it.
+    fn collide_points(a: &mut Self, b: &mut Self) {
+        let (_, normal) = b.distance(a.pos);
+        let surface = b.support(normal).dot(normal);
+        let contacts = a
+            .contact_points(-normal)
+            .into_iter()
+            .filter(|&x| b.distance(x).0 < CONTACT_MARGIN)
+            .map(|x| (x, normal, surface - x.dot(normal)))
+            .collect();
+        Self::resolve_contacts(a, b, contacts);
+    }
+
+    /// Resolves the penetrating points among `contacts` at once, at their
+    /// centroid, so that a box landing flat on two corners does not start
+    /// to spin
+    fn resolve_contacts(a: &mut Self, b: &mut Self, contacts: Vec<(Vec2, Vec2, f32)>) {
+        let mut num = 0;
+        let mut x = Vec2::ZERO;
+        let mut normal = Vec2::ZERO;
+        let mut depth: f32 = 0.0;
+        for (contact_x, contact_normal, contact_depth) in contacts {
+            if contact_depth > 0.0 {
+                num += 1;
+                x += contact_x;
+                normal += contact_normal;
+                depth = depth.max(contact_depth);
+            }
+        }
+        if num > 0 {
+            Self::resolve_contact(a, b, x / num as f32, normal.normalize(), depth);
+        }
+    }
+
+    /// Keeps the body inside the box from `min` to `max`
+    pub fn collide_with_walls(&mut self, min: Vec2, max: Vec2) {
+        let mut wall = Self::kinematic(Shape::Circle { radius: 0.0 }, Vec2::ZERO, Vec2::ZERO);
+        for (normal, offset) in [
+            (Vec2::X, min.x),
+            (Vec2::NEG_X, -max.x),
+            (Vec2::Y, min.y),
+            (Vec2::NEG_Y, -max.y),
+        ] {
+            let contacts = self
+                .contact_points(-normal)
+                .into_iter()
+                .map(|x| (x, normal, offset - x.dot(normal)))
+                .collect();
+            Self::resolve_contacts(self, &mut wall, contacts);
+        }
+    }
+
+    /// Appends a triangle fan of the body and its outline, with a spoke for
+    /// circles to show their rotation
+    pub fn append_mesh(&self, triangles: &mut Vec<Vec2>, lines: &mut Vec<Vec2>) {
+        let outline: Vec<Vec2> = match self.shape {
+            Shape::Circle { radius } => {
+                lines.push(self.pos);
+                lines.push(self.pos + Mat2::from_angle(self.angle) * Vec2::new(radius, 0.0));
+                (0..CIRCLE_NUM_SEGS)
+                    .map(|i| {
+                        let phi = self.angle + 2.0 * PI * i as f32 / CIRCLE_NUM_SEGS as f32;
+                        self.pos + radius * Vec2::new(phi.cos(), phi.sin())
+                    })
+                    .collect()
+            }
+            Shape::Box { half_extents } => self.corners(half_extents).to_vec(),
+        };
+        for i in 0..outline.len() {
+            let next = outline[(i + 1) % outline.len()];
+            triangles.push(self.pos);
+            triangles.push(outline[i]);
+            triangles.push(next);
+            lines.push(outline[i]);
+            lines.push(next);
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use glam::{vec3, Vec3};
+use glam::{vec2, vec3, Vec3, Vec4};
 use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
 
-use crate::hashing_11::Hash;
+use crate::{hashing_11::Hash, normals::SurfaceNormals};
 
 const GRAVITY: Vec3 = vec3(0.0, -10.0, 0.0);
 const TIME_STEP: f32 = 1.0 / 60.0;
@@ -108,6 +108,7 @@ pub struct SelfCollisionSimulation {
     rest_pos: Vec<Vec3>,
     vel: Vec<Vec3>,
     inv_mass: Vec<f32>,
+    normals: SurfaceNormals,
     thickness: f32,
     pub handle_collisions: bool,
     hash: AdjHash,
@@ -154,6 +155,18 @@ impl SelfCollisionSimulation {
             }
         }
 
+        // texture coordinates span the cloth for tangent generation
+        let mut uvs = vec![];
+        for i in 0..NUM_X {
+            for j in 0..NUM_Y {
+                uvs.push(vec2(
+                    i as f32 / (NUM_X - 1) as f32,
+                    j as f32 / (NUM_Y - 1) as f32,
+                ));
+            }
+        }
+        let normals = SurfaceNormals::new(num_particles, &tri_ids).with_uvs(uvs);
+
         let dt = TIME_STEP / Into::<f32>::into(num_substeps);
         let mut cloth = Self {
             num_particles,
@@ -171,6 +184,7 @@ impl SelfCollisionSimulation {
             rest_pos: vec![Vec3::ZERO; num_particles],
             vel: vec![Vec3::ZERO; num_particles],
             inv_mass: vec![0.0; num_particles],
+            normals,
             thickness: DEFAULT_THICKNESS,
             handle_collisions: true,
             hash: AdjHash::new(SPACING, num_particles),
@@ -199,6 +213,18 @@ impl SelfCollisionSimulation {
         self.pos.as_ptr()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn normals(&self) -> *const Vec3 {
+        // See above comment for `pos` re: safety
+        self.normals.normals.as_ptr()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tangents(&self) -> *const Vec4 {
+        // See above comment for `pos` re: safety
+        self.normals.tangents.as_ptr()
+    }
+
     // We can copy since we are not performance sensitive for these two methods
     #[wasm_bindgen(getter)]
     pub fn edge_ids(&self) -> Vec<usize> {
@@ -217,7 +243,7 @@ impl SelfCollisionSimulation {
             for j in 0..NUM_Y {
                 let id = i * NUM_Y + j;
                 self.pos[id] = vec3(
-                    -1.0 * NUM_X as f32 * SPACING * 0.5 + i as f32 * SPACING,
+                    -(NUM_X as f32) * SPACING * 0.5 + i as f32 * SPACING,
                     0.2 + j as f32 * SPACING,
                     0.0,
                 );
@@ -236,6 +262,7 @@ impl SelfCollisionSimulation {
 
         self.rest_pos.copy_from_slice(&self.pos);
         self.vel.fill(Vec3::ZERO);
+        self.normals.update(&self.pos);
     }
 
     #[wasm_bindgen(setter)]
@@ -319,6 +346,7 @@ impl SelfCollisionSimulation {
                 self.vel[i] = (self.pos[i] - self.prev[i]) * self.inv_dt;
             }
         }
+        self.normals.update(&self.pos);
     }
 
     fn solve_constraints(&mut self) {
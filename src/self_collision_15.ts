@@ -47,6 +47,8 @@ class SelfCollisionDemo implements Demo<SelfCollisionSimulation, SelfCollisionDe
     private frontMesh: THREE.Mesh;
     private backMesh: THREE.Mesh;
     private positions: Float32Array; // mapped to WASM memory
+    private normals: Float32Array; // mapped to WASM memory
+    private tangents: Float32Array; // mapped to WASM memory
 
     constructor(rust_wasm: any, memory: WebAssembly.Memory, canvas: HTMLCanvasElement, scene: Scene3D, folder: GUI) {
         this.memory = memory;
@@ -120,7 +122,11 @@ class SelfCollisionDemo implements Demo<SelfCollisionSimulation, SelfCollisionDe
         // linear heap, it will be constant thereafter, so we don't need to refresh the pointer
         // moving forward.
         const positionsPtr = this.sim.pos;
+        const normalsPtr = this.sim.normals;
+        const tangentsPtr = this.sim.tangents;
         this.positions = new Float32Array(this.memory.buffer, positionsPtr, this.sim.num_particles * 3);
+        this.normals = new Float32Array(this.memory.buffer, normalsPtr, this.sim.num_particles * 3);
+        this.tangents = new Float32Array(this.memory.buffer, tangentsPtr, this.sim.num_particles * 4);
 
         // visual edge mesh
         let geometry = new THREE.BufferGeometry();
@@ -134,6 +140,8 @@ class SelfCollisionDemo implements Demo<SelfCollisionSimulation, SelfCollisionDe
         // visual tri mesh
         geometry = new THREE.BufferGeometry();
         geometry.setAttribute('position', new THREE.BufferAttribute(this.positions, 3));
+        geometry.setAttribute('normal', new THREE.BufferAttribute(this.normals, 3));
+        geometry.setAttribute('tangent', new THREE.BufferAttribute(this.tangents, 4));
         geometry.setIndex(triIds);
         const frontMaterial = new THREE.MeshPhongMaterial({ color: 0xff0000, side: THREE.FrontSide });
         this.frontMesh = new THREE.Mesh(geometry, frontMaterial);
@@ -145,7 +153,6 @@ class SelfCollisionDemo implements Demo<SelfCollisionSimulation, SelfCollisionDe
         this.backMesh.castShadow = true;
         this.backMesh.layers.enable(1);
         this.scene.scene.add(this.backMesh);
-        geometry.computeVertexNormals();
         geometry.computeBoundingSphere()
 
         this.updateMesh();
@@ -155,7 +162,8 @@ class SelfCollisionDemo implements Demo<SelfCollisionSimulation, SelfCollisionDe
         this.frontMesh.geometry.attributes.position.needsUpdate = true;
         this.edgeMesh.geometry.attributes.position.needsUpdate = true;
         if (!this.props.showEdges) {
-            this.frontMesh.geometry.computeVertexNormals();
+            this.frontMesh.geometry.attributes.normal.needsUpdate = true;
+            this.frontMesh.geometry.attributes.tangent.needsUpdate = true;
         }
         this.frontMesh.geometry.computeBoundingSphere();
     }
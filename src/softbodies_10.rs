@@ -2,13 +2,19 @@ use glam::{vec3, Vec3};
 use js_sys::Math::random;
 use wasm_bindgen::prelude::*;
 
-use crate::mesh::{self, TetMeshData};
+use crate::{
+    hashing_11::Hash,
+    mesh::{self, TetMeshData},
+    normals::SurfaceNormals,
+};
 
 const DEFAULT_BODIES_CAPACITY: usize = 10;
 const GRAVITY: Vec3 = vec3(0.0, -10.0, 0.0);
 const TIME_STEP: f32 = 1.0 / 60.0;
 const VOL_ID_ORDER: [[usize; 3]; 4] = [[1, 3, 2], [0, 2, 3], [0, 3, 1], [0, 1, 2]];
 const SQUASH_TO_Y: f32 = 0.5;
+const SELF_COLLISION_THICKNESS: f32 = 0.01;
+const SELF_COLLISION_SPACING: f32 = 0.05;
 
 pub struct SoftBody {
     pub num_particles: usize,
@@ -32,6 +38,22 @@ pub struct SoftBody {
 
     pub edge_compliance: f32,
     pub vol_compliance: f32,
+
+    // inverted tets get one vertex flipped back through its opposite face, restoring
+    // the rest volume regardless of `vol_compliance`
+    pub handle_inversions: bool,
+    pub num_inverted_tets: usize,
+
+    pub normals: SurfaceNormals,
+
+    // self collision handling between surface vertices and surface triangles
+    pub handle_self_collisions: bool,
+    surface_tri_ids: Vec<[usize; 3]>,
+    surface_vert_ids: Vec<usize>,
+    surface_pos: Vec<Vec3>,
+    first_adj_id: Vec<usize>,
+    adj_ids: Vec<usize>,
+    hash: Hash,
 }
 
 impl SoftBody {
@@ -46,6 +68,17 @@ impl SoftBody {
         let num_tets = mesh.tet_ids.len();
         let num_edges = mesh.tet_edge_ids.len();
         let dt = TIME_STEP / Into::<f32>::into(num_substeps);
+
+        let surface_tri_ids: Vec<[usize; 3]> = mesh
+            .tet_surface_tri_ids
+            .chunks_exact(3)
+            .map(|ids| [ids[0], ids[1], ids[2]])
+            .collect();
+        let mut surface_vert_ids: Vec<usize> = surface_tri_ids.iter().flatten().copied().collect();
+        surface_vert_ids.sort_unstable();
+        surface_vert_ids.dedup();
+        let num_surface_verts = surface_vert_ids.len();
+
         let mut body = Self {
             num_particles,
             num_tets,
@@ -68,6 +101,19 @@ impl SoftBody {
 
             edge_compliance,
             vol_compliance,
+
+            handle_inversions: true,
+            num_inverted_tets: 0,
+
+            normals: SurfaceNormals::new(num_particles, &surface_tri_ids),
+
+            handle_self_collisions: false,
+            surface_tri_ids,
+            surface_vert_ids,
+            surface_pos: vec![Vec3::ZERO; num_surface_verts],
+            first_adj_id: vec![0; num_particles + 1],
+            adj_ids: Vec::with_capacity(num_edges),
+            hash: Hash::new(SELF_COLLISION_SPACING, num_surface_verts),
         };
         body.init();
         body
@@ -95,6 +141,33 @@ impl SoftBody {
             let id1 = self.edge_ids[2 * i + 1];
             self.edge_lens[i] = self.pos[id0].distance(self.pos[id1]);
         }
+
+        // vertices connected by an edge are excluded from self collision
+        let mut num_adj = vec![0; self.num_particles];
+        for &id in &self.edge_ids {
+            num_adj[id] += 1;
+        }
+        let mut first = 0;
+        for (i, num) in num_adj.iter().enumerate() {
+            self.first_adj_id[i] = first;
+            first += num;
+        }
+        self.first_adj_id[self.num_particles] = first; // guard
+        self.adj_ids.resize(first, 0);
+        num_adj.fill(0);
+        for i in 0..self.edge_lens.len() {
+            let id0 = self.edge_ids[2 * i];
+            let id1 = self.edge_ids[2 * i + 1];
+            self.adj_ids[self.first_adj_id[id0] + num_adj[id0]] = id1;
+            num_adj[id0] += 1;
+            self.adj_ids[self.first_adj_id[id1] + num_adj[id1]] = id0;
+            num_adj[id1] += 1;
+        }
+        for i in 0..self.num_particles {
+            self.adj_ids[self.first_adj_id[i]..self.first_adj_id[i + 1]].sort_unstable();
+        }
+
+        self.normals.update(&self.pos);
     }
 
     fn pre_solve(&mut self) {
@@ -115,6 +188,12 @@ impl SoftBody {
     fn solve(&mut self) {
         self.solve_edges();
         self.solve_volumes();
+        if self.handle_inversions {
+            self.solve_inversions();
+        }
+        if self.handle_self_collisions {
+            self.solve_self_collisions();
+        }
     }
 
     fn post_solve(&mut self) {
@@ -183,12 +262,138 @@ impl SoftBody {
         }
     }
 
+    #[must_use]
+    fn is_inverted(&self, i: usize) -> bool {
+        // signs differ (or the tet collapsed) with respect to the rest configuration
+        self.get_tet_volume(i) * self.rest_vol[i] <= 0.0 && self.rest_vol[i] != 0.0
+    }
+
+    fn solve_inversions(&mut self) {
+        for i in 0..self.num_tets {
+            if !self.is_inverted(i) {
+                continue;
+            }
+
+            // the volume is linear in each vertex with the gradient `grad`, and
+            // the height of a vertex over its opposite face is vol / |grad|.
+            // The free vertex closest to its face is flipped through it, which
+            // restores the orientation with the smallest displacement
+            let tet = self.tet_ids[i];
+            let mut flip = None;
+            let mut max_len2 = 0.0;
+            for (j, &id) in tet.iter().enumerate() {
+                if self.inv_mass[id] == 0.0 {
+                    continue;
+                }
+                let order = VOL_ID_ORDER[j];
+                let temp0 = self.pos[tet[order[1]]] - self.pos[tet[order[0]]];
+                let temp1 = self.pos[tet[order[2]]] - self.pos[tet[order[0]]];
+                let grad = temp0.cross(temp1) / 6.0;
+                let len2 = grad.length_squared();
+                if len2 > max_len2 {
+                    max_len2 = len2;
+                    flip = Some((id, grad));
+                }
+            }
+            if let Some((id, grad)) = flip {
+                let c = self.get_tet_volume(i) - self.rest_vol[i];
+                self.pos[id] -= grad * (c / max_len2);
+            }
+        }
+    }
+
+    fn update_num_inverted_tets(&mut self) {
+        self.num_inverted_tets = (0..self.num_tets).filter(|&i| self.is_inverted(i)).count();
+    }
+
+    fn is_adjacent(&self, id: usize, tri: [usize; 3]) -> bool {
+        tri.iter().any(|&tri_id| {
+            tri_id == id
+                || self.adj_ids[self.first_adj_id[tri_id]..self.first_adj_id[tri_id + 1]]
+                    .binary_search(&id)
+                    .is_ok()
+        })
+    }
+
+    fn solve_self_collisions(&mut self) {
+        for (i, &id) in self.surface_vert_ids.iter().enumerate() {
+            self.surface_pos[i] = self.pos[id];
+        }
+        self.hash.create(&self.surface_pos);
+
+        for t in 0..self.surface_tri_ids.len() {
+            let tri = self.surface_tri_ids[t];
+            let [id0, id1, id2] = tri;
+            let p0 = self.pos[id0];
+            let p1 = self.pos[id1];
+            let p2 = self.pos[id2];
+
+            let mut normal = (p1 - p0).cross(p2 - p0);
+            let len = normal.length();
+            if len == 0.0 {
+                continue;
+            }
+            normal /= len;
+            let prev_normal =
+                (self.prev[id1] - self.prev[id0]).cross(self.prev[id2] - self.prev[id0]);
+
+            let center = (p0 + p1 + p2) / 3.0;
+            let rmax = center
+                .distance(p0)
+                .max(center.distance(p1))
+                .max(center.distance(p2));
+            self.hash.query(&center, rmax + SELF_COLLISION_THICKNESS);
+
+            for q in 0..self.hash.query_size {
+                let id = self.surface_vert_ids[self.hash.query_ids[q]];
+                if self.is_adjacent(id, tri) {
+                    continue;
+                }
+                let p = self.pos[id];
+
+                // barycentric coords of the projection onto the triangle plane
+                let d = (p - p0).dot(normal);
+                let bary = get_barycentric(p - normal * d, p0, p1, p2);
+                if bary.min_element() < 0.0 {
+                    continue;
+                }
+
+                // keep the vertex on the side of the triangle it came from
+                let side = if (self.prev[id] - self.prev[id0]).dot(prev_normal) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let c = side * d - SELF_COLLISION_THICKNESS;
+                if c >= 0.0 {
+                    continue;
+                }
+
+                let w = self.inv_mass[id]
+                    + bary.x * bary.x * self.inv_mass[id0]
+                    + bary.y * bary.y * self.inv_mass[id1]
+                    + bary.z * bary.z * self.inv_mass[id2];
+                if w == 0.0 {
+                    continue;
+                }
+                let grad = normal * side;
+                let s = -c / w;
+                self.pos[id] += grad * s * self.inv_mass[id];
+                self.pos[id0] -= grad * s * bary.x * self.inv_mass[id0];
+                self.pos[id1] -= grad * s * bary.y * self.inv_mass[id1];
+                self.pos[id2] -= grad * s * bary.z * self.inv_mass[id2];
+            }
+        }
+    }
+
     pub fn step(&mut self) {
         for _ in 0..self.num_substeps {
             self.pre_solve();
             self.solve();
             self.post_solve();
         }
+        self.update_num_inverted_tets();
+        self.normals.update(&self.pos);
     }
 
     fn get_tet_volume(&self, i: usize) -> f32 {
@@ -208,6 +413,16 @@ impl SoftBody {
         for i in 0..self.num_particles {
             self.pos[i].y = SQUASH_TO_Y;
         }
+        self.update_num_inverted_tets();
+        self.normals.update(&self.pos);
+    }
+
+    pub fn reset(&mut self, vertices: &[Vec3]) {
+        self.pos.copy_from_slice(vertices);
+        self.prev.copy_from_slice(vertices);
+        self.vel.fill(Vec3::ZERO);
+        self.update_num_inverted_tets();
+        self.normals.update(&self.pos);
     }
 
     pub fn translate(&mut self, displacement: Vec3) {
@@ -215,6 +430,7 @@ impl SoftBody {
             self.pos[i] += displacement;
             self.prev[i] += displacement;
         }
+        self.normals.update(&self.pos);
     }
 
     pub fn start_grab(&mut self, pos: &Vec3) {
@@ -250,12 +466,33 @@ impl SoftBody {
     }
 }
 
+#[must_use]
+fn get_barycentric(p: Vec3, p0: Vec3, p1: Vec3, p2: Vec3) -> Vec3 {
+    let e0 = p1 - p0;
+    let e1 = p2 - p0;
+    let e2 = p - p0;
+    let d00 = e0.dot(e0);
+    let d01 = e0.dot(e1);
+    let d11 = e1.dot(e1);
+    let d20 = e2.dot(e0);
+    let d21 = e2.dot(e1);
+    let denom = d00 * d11 - d01 * d01;
+    if denom == 0.0 {
+        return Vec3::splat(-1.0);
+    }
+    let b1 = (d11 * d20 - d01 * d21) / denom;
+    let b2 = (d00 * d21 - d01 * d20) / denom;
+    vec3(1.0 - b1 - b2, b1, b2)
+}
+
 #[wasm_bindgen]
 pub struct SoftBodiesSimulation {
     bodies: Vec<SoftBody>,
     num_substeps: u8,
     edge_compliance: f32,
     vol_compliance: f32,
+    handle_inversions: bool,
+    handle_self_collisions: bool,
     // stored for reset
     mesh: TetMeshData,
 }
@@ -274,6 +511,8 @@ impl SoftBodiesSimulation {
             num_substeps,
             edge_compliance,
             vol_compliance,
+            handle_inversions: true,
+            handle_self_collisions: false,
             mesh,
         };
         sim.reset();
@@ -287,14 +526,21 @@ impl SoftBodiesSimulation {
         self.mesh.tet_surface_tri_ids.clone()
     }
 
-    pub fn reset(&mut self) {
-        self.bodies.clear();
-        self.bodies.push(SoftBody::new(
+    fn create_body(&self) -> SoftBody {
+        let mut body = SoftBody::new(
             self.num_substeps,
             self.edge_compliance,
             self.vol_compliance,
             &self.mesh,
-        ));
+        );
+        body.handle_inversions = self.handle_inversions;
+        body.handle_self_collisions = self.handle_self_collisions;
+        body
+    }
+
+    pub fn reset(&mut self) {
+        self.bodies.clear();
+        self.bodies.push(self.create_body());
     }
 
     pub fn add_body(&mut self) {
@@ -303,12 +549,7 @@ impl SoftBodiesSimulation {
             0.0,
             -1.0 + 2.0 * random() as f32,
         );
-        let mut body = SoftBody::new(
-            self.num_substeps,
-            self.edge_compliance,
-            self.vol_compliance,
-            &self.mesh,
-        );
+        let mut body = self.create_body();
         body.translate(displacement);
         self.bodies.push(body);
     }
@@ -327,6 +568,11 @@ impl SoftBodiesSimulation {
         self.bodies.iter().map(|b| b.num_tets).sum()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn num_inverted_tets(&self) -> usize {
+        self.bodies.iter().map(|b| b.num_inverted_tets).sum()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn dt(&self) -> f32 {
         self.bodies[0].dt
@@ -354,6 +600,12 @@ impl SoftBodiesSimulation {
         self.bodies[id].pos.as_ptr()
     }
 
+    #[wasm_bindgen]
+    pub fn normals(&self, id: usize) -> *const Vec3 {
+        // See above comment for `pos` re: safety
+        self.bodies[id].normals.normals.as_ptr()
+    }
+
     #[wasm_bindgen(setter)]
     pub fn set_solver_substeps(&mut self, num_substeps: u8) {
         self.num_substeps = num_substeps;
@@ -378,6 +630,22 @@ impl SoftBodiesSimulation {
             .for_each(|b| b.vol_compliance = compliance);
     }
 
+    #[wasm_bindgen(setter)]
+    pub fn set_inversion_handling(&mut self, enabled: bool) {
+        self.handle_inversions = enabled;
+        self.bodies
+            .iter_mut()
+            .for_each(|b| b.handle_inversions = enabled);
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_self_collisions(&mut self, enabled: bool) {
+        self.handle_self_collisions = enabled;
+        self.bodies
+            .iter_mut()
+            .for_each(|b| b.handle_self_collisions = enabled);
+    }
+
     pub fn step(&mut self) {
         self.bodies.iter_mut().for_each(SoftBody::step);
     }
@@ -10,10 +10,13 @@ const DEFAULT_VOL_COMPLIANCE = 0.0;
 
 type SoftBodiesDemoProps = {
     tets: number;
+    invertedTets: number;
     animate: boolean;
     substeps: number;
     volumeCompliance: number;
     edgeCompliance: number;
+    inversionHandling: boolean;
+    selfCollisions: boolean;
     squash: () => void;
     addBody: () => void;
 };
@@ -51,6 +54,7 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
             this.sim.step();
             this.updateMeshes();
             this.grabber.increaseTime(this.sim.dt);
+            this.props.invertedTets = this.sim.num_inverted_tets;
         }
     }
 
@@ -67,12 +71,16 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
         let animateController: Controller;
         this.props = {
             tets: this.sim.num_tets,
+            invertedTets: 0,
             animate: true,
             substeps: DEFAULT_NUM_SOLVER_SUBSTEPS,
             volumeCompliance: DEFAULT_VOL_COMPLIANCE,
             edgeCompliance: DEFAULT_EDGE_COMPLIANCE,
+            inversionHandling: true,
+            selfCollisions: false,
             squash: () => {
                 this.sim.squash();
+                this.props.invertedTets = this.sim.num_inverted_tets;
                 this.props.animate = false;
                 animateController.updateDisplay();
                 this.updateMeshes();
@@ -83,9 +91,12 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
             },
         };
         this.tetsController = folder.add(this.props, 'tets').name('tetrahedra').disable();
+        folder.add(this.props, 'invertedTets').name('inverted tetrahedra').disable().listen();
         folder.add(this.props, 'substeps').min(1).max(30).step(1).onChange((v: number) => (this.sim.solver_substeps = v));
         folder.add(this.props, 'volumeCompliance').name('volume compliance').min(0).max(500).step(5).onChange((v: number) => (this.sim.volume_compliance = v));
         folder.add(this.props, 'edgeCompliance').name('edge compliance').min(0).max(500).step(5).onChange((v: number) => (this.sim.edge_compliance = v));
+        folder.add(this.props, 'inversionHandling').name('inversion handling').onChange((v: boolean) => (this.sim.inversion_handling = v));
+        folder.add(this.props, 'selfCollisions').name('self collisions').onChange((v: boolean) => (this.sim.self_collisions = v));
         animateController = folder.add(this.props, 'animate');
         folder.add(this.props, 'squash');
         folder.add(this.props, 'addBody').name('add body');
@@ -104,10 +115,13 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
         // more efficient ways to do this, but a simple implementation works for this demo.
         const positionsPtr = this.sim.pos(id);
         const positions = new Float32Array(this.memory.buffer, positionsPtr, this.sim.num_particles_per_body * 3);
+        const normalsPtr = this.sim.normals(id);
+        const normals = new Float32Array(this.memory.buffer, normalsPtr, this.sim.num_particles_per_body * 3);
 
         // visual tri mesh
         const geometry = new THREE.BufferGeometry();
         geometry.setAttribute('position', new THREE.BufferAttribute(positions, 3));
+        geometry.setAttribute('normal', new THREE.BufferAttribute(normals, 3));
         geometry.setIndex(surface_tri_ids);
         const visMaterial = new THREE.MeshPhongMaterial({ color: 0xF02000, side: THREE.DoubleSide });
         visMaterial.flatShading = true;
@@ -117,7 +131,6 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
         surfaceMesh.userData = { id }; // for raycasting
         this.scene.scene.add(surfaceMesh);
         this.surfaceMeshes.push(surfaceMesh);
-        geometry.computeVertexNormals();
         geometry.computeBoundingSphere();
 
         this.props.tets = this.sim.num_tets;
@@ -130,9 +143,12 @@ class SoftBodiesDemo implements Demo<SoftBodiesSimulation, SoftBodiesDemoProps>
         // mapped to WASM memory, see comment in `initMesh`
         const positionsPtr = this.sim.pos(id);
         const positions = new Float32Array(this.memory.buffer, positionsPtr, this.sim.num_particles_per_body * 3);
+        const normalsPtr = this.sim.normals(id);
+        const normals = new Float32Array(this.memory.buffer, normalsPtr, this.sim.num_particles_per_body * 3);
         this.surfaceMeshes[id].geometry.setAttribute('position', new THREE.BufferAttribute(positions, 3));
+        this.surfaceMeshes[id].geometry.setAttribute('normal', new THREE.BufferAttribute(normals, 3));
         this.surfaceMeshes[id].geometry.attributes.position.needsUpdate = true;
-        this.surfaceMeshes[id].geometry.computeVertexNormals();
+        this.surfaceMeshes[id].geometry.attributes.normal.needsUpdate = true;
         this.surfaceMeshes[id].geometry.computeBoundingSphere();
     }
 
@@ -1,16 +1,163 @@
-use glam::{vec3, Mat3, Vec3};
+use glam::{Mat3, Vec3};
 use wasm_bindgen::prelude::*;
 
 use crate::{
     hashing_11::Hash,
-    mesh::{self, SkinnedTetMeshData},
+    mesh::{self, SkinnedTetMeshData, TetMeshData},
+    normals::SurfaceNormals,
+    softbodies_10::SoftBody,
 };
 
-const GRAVITY: Vec3 = vec3(0.0, -10.0, 0.0);
-const TIME_STEP: f32 = 1.0 / 60.0;
 const SPACING: f32 = 0.05;
-const VOL_ID_ORDER: [[usize; 3]; 4] = [[1, 3, 2], [0, 2, 3], [0, 3, 1], [0, 1, 2]];
-const SQUASH_TO_Y: f32 = 0.5;
+
+/// Binds the vertices of an arbitrary (visual) surface mesh to the tets of a
+/// simulation mesh via barycentric coordinates.
+pub struct Skinning {
+    // tet id and first three barycentric coords per surface vertex
+    info: Vec<Option<(usize, [f32; 3])>>,
+    pub num_extrapolated: usize,
+    pub max_extrapolation_dist: f32,
+}
+
+#[must_use]
+fn get_barycentric(tet_pos: &[Vec3], tet: [usize; 4], p: Vec3) -> [f32; 3] {
+    let id3 = tet[3];
+    let mat = Mat3::from_cols(
+        tet_pos[tet[0]] - tet_pos[id3],
+        tet_pos[tet[1]] - tet_pos[id3],
+        tet_pos[tet[2]] - tet_pos[id3],
+    );
+    (mat.inverse() * (p - tet_pos[id3])).to_array()
+}
+
+// how far outside of the tet the barycentric coords lie, zero or less if inside
+#[must_use]
+fn get_outside_dist(bary: [f32; 3]) -> f32 {
+    let bary3 = 1.0 - bary[0] - bary[1] - bary[2];
+    f32::max(-bary3, f32::max(-bary[0], f32::max(-bary[1], -bary[2])))
+}
+
+#[must_use]
+fn interpolate(tet_pos: &[Vec3], tet: [usize; 4], [b0, b1, b2]: [f32; 3]) -> Vec3 {
+    let b3 = 1.0 - b0 - b1 - b2;
+    tet_pos[tet[0]] * b0 + tet_pos[tet[1]] * b1 + tet_pos[tet[2]] * b2 + tet_pos[tet[3]] * b3
+}
+
+impl Skinning {
+    /// Computes the skinning info for `surface_pos` in the rest configuration
+    /// `tet_pos`. Vertices outside of all tets are bound to the nearest tet,
+    /// with extrapolated barycentric coords.
+    #[must_use]
+    pub fn new(tet_pos: &[Vec3], tet_ids: &[[usize; 4]], surface_pos: &[Vec3]) -> Self {
+        let num_surface_verts = surface_pos.len();
+
+        // create a hash for all vertices of the surface (visual) mesh
+        let mut hash = Hash::new(SPACING, num_surface_verts);
+        hash.create(surface_pos);
+        let mut min_dist = vec![f32::MAX; num_surface_verts];
+        let mut info: Vec<Option<(usize, [f32; 3])>> = vec![None; num_surface_verts];
+
+        let mut tet_centers = vec![Vec3::ZERO; tet_ids.len()];
+        for (i, &tet) in tet_ids.iter().enumerate() {
+            let tet_center = tet.iter().map(|&id| tet_pos[id]).sum::<Vec3>() / 4.0;
+            tet_centers[i] = tet_center;
+
+            let mut rmax: f32 = 0.0;
+            for id in tet {
+                let r = tet_center.distance(tet_pos[id]);
+                rmax = rmax.max(r);
+            }
+            rmax += SPACING;
+
+            hash.query(&tet_center, rmax);
+            for j in 0..hash.query_size {
+                let id = hash.query_ids[j];
+
+                // we already have skinning info
+                if min_dist[id] <= 0.0 {
+                    continue;
+                }
+
+                if surface_pos[id].distance_squared(tet_center) > rmax * rmax {
+                    continue;
+                }
+
+                // compute barycentric coords for candidate
+                let bary = get_barycentric(tet_pos, tet, surface_pos[id]);
+                let dist = get_outside_dist(bary);
+                if dist < min_dist[id] {
+                    min_dist[id] = dist;
+                    info[id] = Some((i, bary));
+                }
+            }
+        }
+
+        // bind vertices far from all tets to the tet with the nearest center
+        for id in 0..num_surface_verts {
+            if info[id].is_some() {
+                continue;
+            }
+            let nearest = tet_centers
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    surface_pos[id]
+                        .distance_squared(**a)
+                        .total_cmp(&surface_pos[id].distance_squared(**b))
+                })
+                .map(|(i, _)| i);
+            if let Some(i) = nearest {
+                let bary = get_barycentric(tet_pos, tet_ids[i], surface_pos[id]);
+                min_dist[id] = get_outside_dist(bary);
+                info[id] = Some((i, bary));
+            }
+        }
+
+        // measure extrapolation as the distance to the clamped barycentric point
+        let mut num_extrapolated = 0;
+        let mut max_extrapolation_dist: f32 = 0.0;
+        for id in 0..num_surface_verts {
+            if let Some((i, bary)) = info[id] {
+                if min_dist[id] <= 0.0 {
+                    continue;
+                }
+                let bary3 = 1.0 - bary[0] - bary[1] - bary[2];
+                let mut clamped = [bary[0].max(0.0), bary[1].max(0.0), bary[2].max(0.0)];
+                let sum = clamped.iter().sum::<f32>() + bary3.max(0.0);
+                if sum > 0.0 {
+                    clamped.iter_mut().for_each(|b| *b /= sum);
+                }
+                let closest = interpolate(tet_pos, tet_ids[i], clamped);
+                num_extrapolated += 1;
+                max_extrapolation_dist =
+                    max_extrapolation_dist.max(closest.distance(surface_pos[id]));
+            }
+        }
+
+        Self {
+            info,
+            num_extrapolated,
+            max_extrapolation_dist,
+        }
+    }
+
+    #[must_use]
+    pub fn from_body(body: &SoftBody, surface_pos: &[Vec3]) -> Self {
+        Self::new(&body.pos, &body.tet_ids, surface_pos)
+    }
+
+    pub fn update(&self, tet_pos: &[Vec3], tet_ids: &[[usize; 4]], surface_pos: &mut [Vec3]) {
+        for (pos, info) in surface_pos.iter_mut().zip(&self.info) {
+            if let Some((tet_id, bary)) = *info {
+                *pos = interpolate(tet_pos, tet_ids[tet_id], bary);
+            }
+        }
+    }
+
+    pub fn update_from_body(&self, body: &SoftBody, surface_pos: &mut [Vec3]) {
+        self.update(&body.pos, &body.tet_ids, surface_pos);
+    }
+}
 
 #[wasm_bindgen]
 pub struct SkinnedSoftbodySimulation {
@@ -22,31 +169,15 @@ pub struct SkinnedSoftbodySimulation {
     pub num_tets: usize,
     #[wasm_bindgen(readonly)]
     pub num_surface_verts: usize,
-    num_substeps: u8,
-    #[wasm_bindgen(readonly)]
-    pub dt: f32,
-    inv_dt: f32,
 
-    tet_ids: Vec<[usize; 4]>,
-    edge_ids: Vec<usize>,
-    skinning_info: Vec<Option<(usize, [f32; 3])>>,
-
-    pos: Vec<Vec3>,
+    body: SoftBody,
+    skinning: Skinning,
     surface_pos: Vec<Vec3>,
-    prev: Vec<Vec3>,
-    vel: Vec<Vec3>,
-    inv_mass: Vec<f32>,
-    rest_vol: Vec<f32>,
-    edge_lens: Vec<f32>,
-
-    grab_inv_mass: f32,
-    grab_id: Option<usize>,
-
-    pub edge_compliance: f32,
-    pub vol_compliance: f32,
+    surface_normals: SurfaceNormals,
 
     // stored for reset
     mesh: SkinnedTetMeshData,
+    tet_mesh: TetMeshData,
 }
 
 #[wasm_bindgen]
@@ -55,41 +186,33 @@ impl SkinnedSoftbodySimulation {
     #[wasm_bindgen(constructor)]
     pub fn new(num_substeps: u8, edge_compliance: f32, vol_compliance: f32) -> Self {
         let mesh = mesh::get_dragon();
-        let num_particles = mesh.tet_vertices.len();
-        let num_tets = mesh.tet_ids.len();
-        let num_surface_verts = mesh.surface_vertices.len();
-        let dt = TIME_STEP / Into::<f32>::into(num_substeps);
-        let mut body = Self {
-            num_particles,
-            num_tris: mesh.surface_tri_ids.len() / 3,
-            num_tets,
-            num_surface_verts,
-            num_substeps,
-            dt,
-            inv_dt: 1.0 / dt,
-
-            edge_ids: mesh.tet_edge_ids.clone(),
+        let tet_mesh = TetMeshData {
+            vertices: mesh.tet_vertices.clone(),
             tet_ids: mesh.tet_ids.clone(),
-            skinning_info: vec![None; num_surface_verts],
-
-            pos: mesh.tet_vertices.clone(),
+            tet_edge_ids: mesh.tet_edge_ids.clone(),
+            tet_surface_tri_ids: vec![],
+        };
+        let mut body = SoftBody::new(num_substeps, edge_compliance, vol_compliance, &tet_mesh);
+        // the skinned dragon has always run without inversion recovery
+        body.handle_inversions = false;
+        let skinning = Skinning::from_body(&body, &mesh.surface_vertices);
+        let mut surface_normals =
+            SurfaceNormals::from_flat_ids(mesh.surface_vertices.len(), &mesh.surface_tri_ids);
+        surface_normals.update(&mesh.surface_vertices);
+
+        Self {
+            num_particles: body.num_particles,
+            num_tris: mesh.surface_tri_ids.len() / 3,
+            num_tets: body.num_tets,
+            num_surface_verts: mesh.surface_vertices.len(),
+            body,
+            skinning,
             surface_pos: mesh.surface_vertices.clone(),
-            prev: mesh.tet_vertices.clone(),
-            vel: vec![Vec3::ZERO; num_particles],
-            inv_mass: vec![0.0; num_particles],
-            rest_vol: vec![0.0; num_tets],
-            edge_lens: vec![0.0; mesh.tet_edge_ids.len() / 2],
-
-            grab_inv_mass: 0.0,
-            grab_id: None,
-
-            edge_compliance,
-            vol_compliance,
+            surface_normals,
 
             mesh,
-        };
-        body.init();
-        body
+            tet_mesh,
+        }
     }
 
     #[wasm_bindgen(getter)]
@@ -99,7 +222,7 @@ impl SkinnedSoftbodySimulation {
         // occurred (which move the location).
         // Positions is a Vec<Vec3>, which is a linear array of f32s in
         // memory.
-        self.pos.as_ptr()
+        self.body.pos.as_ptr()
     }
 
     #[wasm_bindgen(getter)]
@@ -108,17 +231,40 @@ impl SkinnedSoftbodySimulation {
         self.surface_pos.as_ptr()
     }
 
+    #[wasm_bindgen(getter)]
+    pub fn surface_normals(&self) -> *const Vec3 {
+        // See above comment for `pos` re: safety
+        self.surface_normals.normals.as_ptr()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dt(&self) -> f32 {
+        self.body.dt
+    }
+
+    // number of surface vertices outside of all tets and their max distance to
+    // the tet they are bound to
+    #[wasm_bindgen(getter)]
+    pub fn num_extrapolated_verts(&self) -> usize {
+        self.skinning.num_extrapolated
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_extrapolation_dist(&self) -> f32 {
+        self.skinning.max_extrapolation_dist
+    }
+
     // We can copy since we are not performance sensitive for these three methods
     #[wasm_bindgen(getter)]
     pub fn tet_ids(&self) -> Vec<usize> {
         // NOTE: this heap allocates for the return value!
-        self.tet_ids.iter().flat_map(|e| e.to_vec()).collect()
+        self.body.tet_ids.iter().flat_map(|e| e.to_vec()).collect()
     }
 
     #[wasm_bindgen(getter)]
     pub fn edge_ids(&self) -> Vec<usize> {
         // NOTE: this heap allocates for the return value!
-        self.edge_ids.clone()
+        self.body.edge_ids.clone()
     }
 
     #[must_use]
@@ -130,278 +276,50 @@ impl SkinnedSoftbodySimulation {
 
     #[wasm_bindgen(setter)]
     pub fn set_solver_substeps(&mut self, num_substeps: u8) {
-        self.num_substeps = num_substeps;
-        self.dt = TIME_STEP / Into::<f32>::into(num_substeps);
-        self.inv_dt = 1.0 / self.dt;
+        self.body.set_solver_substeps(num_substeps);
     }
 
-    fn compute_skinning_info(&mut self) {
-        // create a hash for all vertices of the surface (visual) mesh
-        let mut hash = Hash::new(SPACING, self.num_surface_verts);
-        hash.create(&self.surface_pos);
-        let mut min_dist = vec![f32::MAX; self.num_surface_verts];
-
-        let mut tet_center;
-        let mut mat;
-        let mut bary;
-
-        for i in 0..self.num_tets {
-            tet_center = Vec3::ZERO;
-            for j in 0..4 {
-                tet_center += self.pos[self.tet_ids[i][j]] / 4.0;
-            }
-
-            let mut rmax: f32 = 0.0;
-            for j in 0..4 {
-                let r = tet_center.distance(self.pos[self.tet_ids[i][j]]);
-                rmax = rmax.max(r);
-            }
-            rmax += SPACING;
-
-            hash.query(&tet_center, rmax);
-            if hash.query_size == 0 {
-                continue;
-            }
-
-            let tet = self.tet_ids[i];
-            let id0 = tet[0];
-            let id1 = tet[1];
-            let id2 = tet[2];
-            let id3 = tet[3];
-
-            mat = Mat3::from_cols(
-                self.pos[id0] - self.pos[id3],
-                self.pos[id1] - self.pos[id3],
-                self.pos[id2] - self.pos[id3],
-            );
-            mat = mat.inverse();
-
-            for j in 0..hash.query_size {
-                let id = hash.query_ids[j];
-
-                // we already have skinning info
-                if min_dist[id] <= 0.0 {
-                    continue;
-                }
-
-                if self.surface_pos[id].distance_squared(tet_center) > rmax * rmax {
-                    continue;
-                }
-
-                // compute barycentric coords for candidate
-                bary = self.surface_pos[id] - self.pos[id3];
-                bary = mat * bary;
-                let bary3 = 1.0 - bary[0] - bary[1] - bary[2];
-
-                let mut dist: f32 = 0.0;
-                for k in 0..3 {
-                    dist = dist.max(-bary[k]);
-                }
-                dist = dist.max(-bary3);
+    #[wasm_bindgen(setter)]
+    pub fn set_edge_compliance(&mut self, compliance: f32) {
+        self.body.edge_compliance = compliance;
+    }
 
-                if dist < min_dist[id] {
-                    min_dist[id] = dist;
-                    self.skinning_info[id] = Some((i, [bary[0], bary[1], bary[2]]));
-                }
-            }
-        }
+    #[wasm_bindgen(setter)]
+    pub fn set_vol_compliance(&mut self, compliance: f32) {
+        self.body.vol_compliance = compliance;
     }
 
     pub fn reset(&mut self) {
-        self.pos.copy_from_slice(&self.mesh.tet_vertices);
+        // reset in place, JS holds views into the position buffers
+        self.body.reset(&self.tet_mesh.vertices);
         self.surface_pos
             .copy_from_slice(&self.mesh.surface_vertices);
-        self.prev.copy_from_slice(&self.pos);
-        self.vel.fill(Vec3::ZERO);
-    }
-
-    fn init(&mut self) {
-        for i in 0..self.num_tets {
-            let vol = self.get_tet_volume(i);
-            self.rest_vol[i] = vol;
-            let inv_mass = if vol > 0.0 { 1.0 / (vol / 4.0) } else { 0.0 };
-            let tet = self.tet_ids[i];
-            self.inv_mass[tet[0]] += inv_mass;
-            self.inv_mass[tet[1]] += inv_mass;
-            self.inv_mass[tet[2]] += inv_mass;
-            self.inv_mass[tet[3]] += inv_mass;
-        }
-        for i in 0..self.edge_lens.len() {
-            let id0 = self.edge_ids[2 * i];
-            let id1 = self.edge_ids[2 * i + 1];
-            self.edge_lens[i] = self.pos[id0].distance(self.pos[id1]);
-        }
-
-        self.compute_skinning_info();
-    }
-
-    fn pre_solve(&mut self) {
-        for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
-                continue;
-            }
-            self.vel[i] += GRAVITY * self.dt;
-            self.prev[i] = self.pos[i];
-            self.pos[i] += self.vel[i] * self.dt;
-            if self.pos[i].y < 0.0 {
-                self.pos[i] = self.prev[i];
-                self.pos[i].y = 0.0;
-            }
-        }
-    }
-
-    fn solve(&mut self) {
-        self.solve_edges();
-        self.solve_volumes();
-    }
-
-    fn post_solve(&mut self) {
-        for i in 0..self.num_particles {
-            if self.inv_mass[i] == 0.0 {
-                continue;
-            }
-            self.vel[i] = (self.pos[i] - self.prev[i]) * self.inv_dt;
-        }
-    }
-
-    fn solve_edges(&mut self) {
-        let alpha = self.edge_compliance * self.inv_dt * self.inv_dt;
-        for i in 0..self.edge_lens.len() {
-            let id0 = self.edge_ids[2 * i];
-            let id1 = self.edge_ids[2 * i + 1];
-            let w0 = self.inv_mass[id0];
-            let w1 = self.inv_mass[id1];
-            let w = w0 + w1;
-            if w == 0.0 {
-                continue;
-            }
-
-            let mut temp = self.pos[id0] - self.pos[id1];
-            let len = temp.length();
-            if len == 0.0 {
-                continue;
-            }
-            temp /= len;
-            let rest_len = self.edge_lens[i];
-            let c = len - rest_len;
-            let s = -c / (w + alpha);
-            self.pos[id0] += temp * s * w0;
-            self.pos[id1] += temp * -s * w1;
-        }
-    }
-
-    fn solve_volumes(&mut self) {
-        let alpha = self.vol_compliance * self.inv_dt * self.inv_dt;
-        for i in 0..self.num_tets {
-            let mut w = 0.0;
-            let tet = self.tet_ids[i];
-            let mut grads = [Vec3::ZERO; 4];
-            for j in 0..4 {
-                let order = VOL_ID_ORDER[j];
-                let id0 = tet[order[0]];
-                let id1 = tet[order[1]];
-                let id2 = tet[order[2]];
-
-                let temp0 = self.pos[id1] - self.pos[id0];
-                let temp1 = self.pos[id2] - self.pos[id0];
-                grads[j] = temp0.cross(temp1) / 6.0;
-                w += self.inv_mass[tet[j]] * grads[j].length_squared();
-            }
-            if w == 0.0 {
-                continue;
-            }
-            let vol = self.get_tet_volume(i);
-            let rest_vol = self.rest_vol[i];
-            let c = vol - rest_vol;
-            let s = -c / (w + alpha);
-            for (j, grad) in grads.iter().enumerate() {
-                let id = self.tet_ids[i][j];
-                self.pos[id] += *grad * s * self.inv_mass[id];
-            }
-        }
+        self.surface_normals.update(&self.surface_pos);
     }
 
     pub fn step(&mut self) {
-        for _ in 0..self.num_substeps {
-            self.pre_solve();
-            self.solve();
-            self.post_solve();
-        }
-
-        self.update_surface();
-    }
-
-    fn update_surface(&mut self) {
-        for i in 0..self.num_surface_verts {
-            if let Some((tetid, [b0, b1, b2])) = self.skinning_info[i] {
-                let b3 = 1.0 - b0 - b1 - b2;
-                let tet = self.tet_ids[tetid];
-                let id0 = tet[0];
-                let id1 = tet[1];
-                let id2 = tet[2];
-                let id3 = tet[3];
-                self.surface_pos[i] = Vec3::ZERO;
-                self.surface_pos[i] += self.pos[id0] * b0;
-                self.surface_pos[i] += self.pos[id1] * b1;
-                self.surface_pos[i] += self.pos[id2] * b2;
-                self.surface_pos[i] += self.pos[id3] * b3;
-            }
-        }
-    }
-
-    #[must_use]
-    fn get_tet_volume(&self, i: usize) -> f32 {
-        let tet = self.tet_ids[i];
-        let id0 = tet[0];
-        let id1 = tet[1];
-        let id2 = tet[2];
-        let id3 = tet[3];
-        let temp0 = self.pos[id1] - self.pos[id0];
-        let temp1 = self.pos[id2] - self.pos[id0];
-        let temp2 = self.pos[id3] - self.pos[id0];
-        let temp3 = temp0.cross(temp1);
-        temp3.dot(temp2) / 6.0
+        self.body.step();
+        self.skinning
+            .update_from_body(&self.body, &mut self.surface_pos);
+        self.surface_normals.update(&self.surface_pos);
     }
 
     pub fn squash(&mut self) {
-        for i in 0..self.num_particles {
-            self.pos[i].y = SQUASH_TO_Y;
-        }
-        self.update_surface();
+        self.body.squash();
+        self.skinning
+            .update_from_body(&self.body, &mut self.surface_pos);
+        self.surface_normals.update(&self.surface_pos);
     }
 
     pub fn start_grab(&mut self, _: usize, pos: &[f32]) {
-        let pos = Vec3::from_slice(pos);
-        let mut min_d2 = f32::MAX;
-        self.grab_id = None;
-        for i in 0..self.num_particles {
-            let d2 = (pos - self.pos[i]).length_squared();
-            if d2 < min_d2 {
-                min_d2 = d2;
-                self.grab_id = Some(i);
-            }
-        }
-
-        if let Some(i) = self.grab_id {
-            self.grab_inv_mass = self.inv_mass[i];
-            self.inv_mass[i] = 0.0;
-            self.pos[i] = pos;
-        }
+        self.body.start_grab(&Vec3::from_slice(pos));
     }
 
     pub fn move_grabbed(&mut self, _: usize, pos: &[f32]) {
-        let pos = Vec3::from_slice(pos);
-        if let Some(i) = self.grab_id {
-            self.pos[i] = pos;
-        }
+        self.body.move_grabbed(&Vec3::from_slice(pos));
     }
 
     pub fn end_grab(&mut self, _: usize, vel: &[f32]) {
-        let vel = Vec3::from_slice(vel);
-        if let Some(i) = self.grab_id {
-            self.inv_mass[i] = self.grab_inv_mass;
-            self.vel[i] = vel;
-        }
-        self.grab_id = None;
+        self.body.end_grab(&Vec3::from_slice(vel));
     }
 }
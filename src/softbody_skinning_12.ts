@@ -12,6 +12,8 @@ type SkinnedSoftbodyDemoProps = {
     tets: number;
     triangles: number;
     vertices: number;
+    extrapolatedVertices: number;
+    maxExtrapolationDist: number;
     animate: boolean;
     substeps: number;
     volumeCompliance: number;
@@ -37,6 +39,7 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
     private surfaceMesh: THREE.Mesh;
     private tetPositions: Float32Array; // mapped to WASM memory
     private surfacePositions: Float32Array; // mapped to WASM memory
+    private surfaceNormals: Float32Array; // mapped to WASM memory
 
     constructor(rust_wasm: any, memory: WebAssembly.Memory, canvas: HTMLCanvasElement, scene: Scene3D, folder: GUI) {
         this.memory = memory;
@@ -68,6 +71,8 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
             tets: this.sim.num_tets,
             triangles: this.sim.num_tris,
             vertices: this.sim.num_surface_verts,
+            extrapolatedVertices: this.sim.num_extrapolated_verts,
+            maxExtrapolationDist: this.sim.max_extrapolation_dist,
             animate: true,
             substeps: DEFAULT_NUM_SOLVER_SUBSTEPS,
             volumeCompliance: DEFAULT_VOL_COMPLIANCE,
@@ -83,6 +88,8 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
         folder.add(this.props, 'tets').name('tetrahedra').disable();
         folder.add(this.props, 'triangles').disable();
         folder.add(this.props, 'vertices').disable();
+        folder.add(this.props, 'extrapolatedVertices').name('extrapolated vertices').disable();
+        folder.add(this.props, 'maxExtrapolationDist').name('max extrapolation').decimals(4).disable();
         folder.add(this.props, 'substeps').min(1).max(30).step(1).onChange((v: number) => (this.sim.solver_substeps = v));
         folder.add(this.props, 'volumeCompliance').name('volume compliance').min(0).max(250).step(2.5).onChange((v: number) => (this.sim.vol_compliance = v));
         folder.add(this.props, 'edgeCompliance').name('edge compliance').min(0).max(100).step(1).onChange((v: number) => (this.sim.edge_compliance = v));
@@ -110,6 +117,8 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
         this.tetPositions = new Float32Array(this.memory.buffer, tetPositionsPtr, this.sim.num_particles * 3);
         const surfacePositionsPtr = this.sim.surface_pos;
         this.surfacePositions = new Float32Array(this.memory.buffer, surfacePositionsPtr, this.sim.num_surface_verts * 3);
+        const surfaceNormalsPtr = this.sim.surface_normals;
+        this.surfaceNormals = new Float32Array(this.memory.buffer, surfaceNormalsPtr, this.sim.num_surface_verts * 3);
 
         // visual tet mesh
         let geometry = new THREE.BufferGeometry();
@@ -123,13 +132,13 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
         // visual tri mesh
         geometry = new THREE.BufferGeometry();
         geometry.setAttribute('position', new THREE.BufferAttribute(this.surfacePositions, 3));
+        geometry.setAttribute('normal', new THREE.BufferAttribute(this.surfaceNormals, 3));
         geometry.setIndex(surface_tri_ids);
         const visMaterial = new THREE.MeshPhongMaterial({ color: 0xf78a1d });
         this.surfaceMesh = new THREE.Mesh(geometry, visMaterial);
         this.surfaceMesh.castShadow = true;
         this.surfaceMesh.layers.enable(1);
         this.scene.scene.add(this.surfaceMesh);
-        geometry.computeVertexNormals();
         geometry.computeBoundingSphere();
 
         this.updateMesh();
@@ -138,7 +147,7 @@ class SkinnedSoftbodyDemo implements Demo<SkinnedSoftbodySimulation, SkinnedSoft
     private updateMesh() {
         this.surfaceMesh.geometry.attributes.position.needsUpdate = true;
         this.tetMesh.geometry.attributes.position.needsUpdate = true;
-        this.surfaceMesh.geometry.computeVertexNormals();
+        this.surfaceMesh.geometry.attributes.normal.needsUpdate = true;
         this.surfaceMesh.geometry.computeBoundingSphere();
     }
 }
@@ -0,0 +1,221 @@
+use glam::Vec2;
+
+/// Liquid surface reconstructed from particles. The particles define a level
+/// set on a grid of nodes, see Zhu and Bridson, "Animating Sand as a Fluid",
+/// which is smoothed and contoured with marching squares. The nodes use the
+/// `i * num_nodes_y + j` layout and node (0, 0) lies at the origin.
+pub struct ParticleSurface {
+    num_nodes_x: usize,
+    num_nodes_y: usize,
+    spacing: f32,
+    phi: Vec<f32>,
+    smoothed: Vec<f32>,
+    weight: Vec<f32>,
+    center: Vec<Vec2>,
+    /// Triangles covering the liquid, three vertices each
+    pub triangles: Vec<Vec2>,
+    /// Segments of the liquid surface, two vertices each
+    pub lines: Vec<Vec2>,
+}
+
+impl ParticleSurface {
+    #[must_use]
+    pub fn new(width: f32, height: f32, spacing: f32) -> Self {
+        let num_nodes_x = f32::ceil(width / spacing) as usize + 1;
+        let num_nodes_y = f32::ceil(height / spacing) as usize + 1;
+        let num_nodes = num_nodes_x * num_nodes_y;
+        Self {
+            num_nodes_x,
+            num_nodes_y,
+            spacing,
+            phi: vec![0.0; num_nodes],
+            smoothed: vec![0.0; num_nodes],
+            weight: vec![0.0; num_nodes],
+            center: vec![Vec2::ZERO; num_nodes],
+            triangles: vec![],
+            lines: vec![],
+        }
+    }
+
+    /// Rebuilds the level set and the surface mesh
+    pub fn update(
+        &mut self,
+        positions: &[Vec2],
+        particle_radius: f32,
+        kernel_radius: f32,
+        num_smoothing_iters: usize,
+    ) {
+        self.update_level_set(
+            positions,
+            particle_radius,
+            kernel_radius,
+            num_smoothing_iters,
+        );
+        self.extract();
+    }
+
+    /// Rebuilds only the level set. Each node measures its distance to the
+    /// kernel weighted mean of the nearby particles, minus the particle
+    /// radius, which is negative inside the liquid.
+    pub fn update_level_set(
+        &mut self,
+        positions: &[Vec2],
+        particle_radius: f32,
+        kernel_radius: f32,
+        num_smoothing_iters: usize,
+    ) {
+        let n = self.num_nodes_y;
+        let inv_spacing = 1.0 / self.spacing;
+        let inv_kernel_radius_sq = 1.0 / (kernel_radius * kernel_radius);
+
+        // splat particles to the nodes within the kernel radius
+        self.weight.fill(0.0);
+        self.center.fill(Vec2::ZERO);
+        for &x in positions {
+            let x0 = f32::max(f32::ceil((x.x - kernel_radius) * inv_spacing), 0.0) as usize;
+            let y0 = f32::max(f32::ceil((x.y - kernel_radius) * inv_spacing), 0.0) as usize;
+            let x1 = usize::min(
+                f32::max(f32::floor((x.x + kernel_radius) * inv_spacing), 0.0) as usize,
+                self.num_nodes_x - 1,
+            );
+            let y1 = usize::min(
+                f32::max(f32::floor((x.y + kernel_radius) * inv_spacing), 0.0) as usize,
+                self.num_nodes_y - 1,
+            );
+            for i in x0..=x1 {
+                for j in y0..=y1 {
+                    let node = Vec2::new(i as f32, j as f32) * self.spacing;
+                    let s = (node - x).length_squared() * inv_kernel_radius_sq;
+                    if s < 1.0 {
+                        let w = (1.0 - s) * (1.0 - s) * (1.0 - s);
+                        self.weight[i * n + j] += w;
+                        self.center[i * n + j] += w * x;
+                    }
+                }
+            }
+        }
+
+        // nodes away from all particles are at least a kernel radius outside
+        for i in 0..self.num_nodes_x {
+            for j in 0..self.num_nodes_y {
+                let id = i * n + j;
+                self.phi[id] = if self.weight[id] > 0.0 {
+                    let node = Vec2::new(i as f32, j as f32) * self.spacing;
+                    let center = self.center[id] / self.weight[id];
+                    f32::min((node - center).length() - particle_radius, kernel_radius)
+                } else {
+                    kernel_radius
+                };
+            }
+        }
+
+        for _ in 0..num_smoothing_iters {
+            self.smooth();
+        }
+    }
+
+    /// Mean curvature of the level set at the node nearest to `x`, positive
+    /// where the liquid is convex. Zero where the level set is flat.
+    #[must_use]
+    pub fn curvature(&self, x: Vec2) -> f32 {
+        let n = self.num_nodes_y;
+        let i = (x.x / self.spacing).round() as usize;
+        let j = (x.y / self.spacing).round() as usize;
+        if i < 1 || j < 1 || i >= self.num_nodes_x - 1 || j >= self.num_nodes_y - 1 {
+            return 0.0;
+        }
+        let id = i * n + j;
+        let phi = &self.phi;
+        let s = self.spacing;
+        let phi_x = (phi[id + n] - phi[id - n]) / (2.0 * s);
+        let phi_y = (phi[id + 1] - phi[id - 1]) / (2.0 * s);
+        let phi_xx = (phi[id + n] - 2.0 * phi[id] + phi[id - n]) / (s * s);
+        let phi_yy = (phi[id + 1] - 2.0 * phi[id] + phi[id - 1]) / (s * s);
+        let phi_xy =
+            (phi[id + n + 1] - phi[id + n - 1] - phi[id - n + 1] + phi[id - n - 1]) / (4.0 * s * s);
+        let grad_sq = phi_x * phi_x + phi_y * phi_y;
+        if grad_sq < 1e-6 {
+            return 0.0;
+        }
+        (phi_xx * phi_y * phi_y - 2.0 * phi_x * phi_y * phi_xy + phi_yy * phi_x * phi_x)
+            / (grad_sq * grad_sq.sqrt())
+    }
+
+    /// Laplacian smoothing, which keeps flat parts of the surface in place
+    /// and rounds off corners and thin spikes
+    fn smooth(&mut self) {
+        let n = self.num_nodes_y;
+        self.smoothed.copy_from_slice(&self.phi);
+        for i in 1..self.num_nodes_x - 1 {
+            for j in 1..self.num_nodes_y - 1 {
+                let id = i * n + j;
+                let avg = 0.25
+                    * (self.phi[id - n] + self.phi[id + n] + self.phi[id - 1] + self.phi[id + 1]);
+                self.smoothed[id] = 0.5 * (self.phi[id] + avg);
+            }
+        }
+        std::mem::swap(&mut self.phi, &mut self.smoothed);
+    }
+
+    /// Marching squares. Walks around each cell collecting the inside corners
+    /// and the zero crossings on its edges into a polygon, which is fan
+    /// triangulated. Consecutive crossings bound a piece of the surface. In
+    /// the ambiguous saddle cases, this connects the two inside corners.
+    fn extract(&mut self) {
+        let n = self.num_nodes_y;
+        self.triangles.clear();
+        self.lines.clear();
+
+        for i in 0..self.num_nodes_x - 1 {
+            for j in 0..self.num_nodes_y - 1 {
+                let ids = [
+                    i * n + j,
+                    (i + 1) * n + j,
+                    (i + 1) * n + j + 1,
+                    i * n + j + 1,
+                ];
+                let corners = [
+                    Vec2::new(i as f32, j as f32),
+                    Vec2::new((i + 1) as f32, j as f32),
+                    Vec2::new((i + 1) as f32, (j + 1) as f32),
+                    Vec2::new(i as f32, (j + 1) as f32),
+                ]
+                .map(|c| c * self.spacing);
+                let phi = ids.map(|id| self.phi[id]);
+                if phi.iter().all(|&p| p > 0.0) {
+                    continue;
+                }
+
+                // polygon vertices, flagged if they lie on the surface
+                let mut polygon = [(Vec2::ZERO, false); 8];
+                let mut len = 0;
+                for a in 0..4 {
+                    let b = (a + 1) % 4;
+                    if phi[a] <= 0.0 {
+                        polygon[len] = (corners[a], false);
+                        len += 1;
+                    }
+                    if (phi[a] <= 0.0) != (phi[b] <= 0.0) {
+                        let t = phi[a] / (phi[a] - phi[b]);
+                        polygon[len] = (corners[a] + (corners[b] - corners[a]) * t, true);
+                        len += 1;
+                    }
+                }
+
+                for k in 1..len - 1 {
+                    self.triangles.push(polygon[0].0);
+                    self.triangles.push(polygon[k].0);
+                    self.triangles.push(polygon[k + 1].0);
+                }
+                for k in 0..len {
+                    let (p, p_on_surface) = polygon[k];
+                    let (q, q_on_surface) = polygon[(k + 1) % len];
+                    if p_on_surface && q_on_surface {
+                        self.lines.push(p);
+                        self.lines.push(q);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Flow visualization with streamlines and passive tracer particles, which
+//! are advected through a velocity field but do not act on it
+
+use std::collections::VecDeque;
+
+use glam::Vec2;
+
+/// Advances `pos` by `dt` through the velocity field `vel` with the classical
+/// fourth order Runge-Kutta method
+#[must_use]
+pub fn rk4(pos: Vec2, dt: f32, vel: impl Fn(Vec2) -> Vec2) -> Vec2 {
+    let k1 = vel(pos);
+    let k2 = vel(pos + 0.5 * dt * k1);
+    let k3 = vel(pos + 0.5 * dt * k2);
+    let k4 = vel(pos + dt * k3);
+    pos + dt / 6.0 * (k1 + 2.0 * k2 + 2.0 * k3 + k4)
+}
+
+/// Traces the streamline through `seed` in the frozen velocity field `vel`
+/// with `num_segs` segments of arc length `seg_len`, upstream for negative
+/// lengths. `constrain` maps positions into the domain, or returns `None` for
+/// positions outside of the fluid where the streamline ends, as does it at
+/// stagnation points.
+pub fn streamline(
+    seed: Vec2,
+    seg_len: f32,
+    num_segs: usize,
+    vel: impl Fn(Vec2) -> Vec2,
+    constrain: impl Fn(Vec2) -> Option<Vec2>,
+) -> Vec<Vec2> {
+    // unit speed along the field makes the parameter the arc length
+    let dir = |p: Vec2| vel(p).normalize_or_zero();
+    let mut points = vec![seed];
+    let mut p = seed;
+    for _ in 0..num_segs {
+        if dir(p) == Vec2::ZERO {
+            break;
+        }
+        match constrain(rk4(p, seg_len, dir)) {
+            Some(next) => p = next,
+            None => break,
+        }
+        points.push(p);
+    }
+    points
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum TracerKind {
+    /// particles released at the seed every step
+    Streakline,
+    /// the trajectory of a single particle released at the seed
+    Pathline,
+}
+
+struct Tracer {
+    kind: TracerKind,
+    seed: Vec2,
+    // streaklines start with the newest particle at the seed, pathlines end
+    // with the current position of their particle
+    points: VecDeque<Vec2>,
+}
+
+/// Streaklines and pathlines made of at most `max_points` points each
+pub struct Tracers {
+    tracers: Vec<Tracer>,
+    pub max_points: usize,
+}
+
+impl Tracers {
+    #[must_use]
+    pub fn new(max_points: usize) -> Self {
+        Self {
+            tracers: vec![],
+            max_points,
+        }
+    }
+
+    /// Adds a tracer seeded at `seed` and returns its id
+    pub fn add(&mut self, kind: TracerKind, seed: Vec2) -> usize {
+        self.tracers.push(Tracer {
+            kind,
+            seed,
+            points: VecDeque::from([seed]),
+        });
+        self.tracers.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.tracers.clear();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tracers.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tracers.is_empty()
+    }
+
+    /// Points of tracer `id` in order along the line
+    pub fn points(&self, id: usize) -> impl Iterator<Item = Vec2> + '_ {
+        self.tracers[id].points.iter().copied()
+    }
+
+    /// Advects all tracer particles by `dt` through the velocity field `vel`,
+    /// see `streamline` for `constrain`. Particles leaving the fluid are
+    /// dropped from streaklines, while pathlines stop there.
+    pub fn step(
+        &mut self,
+        dt: f32,
+        vel: impl Fn(Vec2) -> Vec2,
+        constrain: impl Fn(Vec2) -> Option<Vec2>,
+    ) {
+        let max_points = self.max_points.max(1);
+        for tracer in &mut self.tracers {
+            match tracer.kind {
+                TracerKind::Streakline => {
+                    let points = std::mem::take(&mut tracer.points);
+                    tracer.points = points
+                        .into_iter()
+                        .filter_map(|p| constrain(rk4(p, dt, &vel)))
+                        .collect();
+                    tracer.points.push_front(tracer.seed);
+                    tracer.points.truncate(max_points);
+                }
+                TracerKind::Pathline => {
+                    let last = tracer.points[tracer.points.len() - 1];
+                    if let Some(p) = constrain(rk4(last, dt, &vel)) {
+                        tracer.points.push_back(p);
+                    }
+                    while tracer.points.len() > max_points {
+                        tracer.points.pop_front();
+                    }
+                }
+            }
+        }
+    }
+}